@@ -1,5 +1,6 @@
 use robius_authentication::{
-    AndroidText, BiometricStrength, Context, Policy, PolicyBuilder, Text, WindowsText,
+    AndroidText, BiometricStrength, Context, LinuxText, Policy, PolicyBuilder, Text,
+    WindowsText,
 };
 
 const POLICY: Policy = PolicyBuilder::new()
@@ -16,6 +17,7 @@ const TEXT: Text = Text {
         description: None,
     },
     apple: "authenticate",
+    linux: LinuxText::new("Description"),
     windows: WindowsText::new("Title", "Description").unwrap(),
 };
 
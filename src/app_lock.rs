@@ -0,0 +1,639 @@
+//! Locking the app after a period of inactivity.
+
+use std::{
+    fmt,
+    sync::{Arc, Condvar, Mutex, MutexGuard, Weak},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{Context, Error, Policy, Result, TextBuf};
+
+/// The idle timeout used by default.
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// A source of the current time, see [`AppLock::with_clock`].
+pub trait Clock: Send + Sync + 'static {
+    /// Returns the current time.
+    fn now(&self) -> Instant;
+}
+
+/// The system's monotonic clock, used by default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    #[inline]
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only advances when told to, for deterministic tests.
+///
+/// Clones share the same time.
+#[derive(Clone, Debug)]
+pub struct ManualClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl ManualClock {
+    /// Returns a new clock, starting at the current time.
+    pub fn new() -> Self {
+        Self {
+            now: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Advances the clock by `duration`.
+    ///
+    /// The [`AppLock`]s using the clock only notice once
+    /// [`AppLock::check`] is called.
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) += duration;
+    }
+}
+
+impl Default for ManualClock {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// The state of an [`AppLock`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LockState {
+    /// The app is locked.
+    Locked,
+    /// The user is being prompted to unlock the app.
+    Unlocking,
+    /// The app is unlocked.
+    Unlocked,
+}
+
+/// Locks the app after a period of inactivity or when the user walks away,
+/// and unlocks it by authenticating the user.
+///
+/// The app starts locked. While it is unlocked, the app reports user
+/// activity using [`Self::touch`], and is locked once no activity was
+/// reported for the idle timeout. On Linux, the app is also locked when the
+/// session is locked, the system is about to sleep, etc., see
+/// [`Context::watch_invalidation`]. On other targets, the app should call
+/// [`Self::lock`] in response to the platform's lifecycle events.
+///
+/// The state can be observed using [`Self::subscribe`].
+///
+/// ```no_run
+/// use std::{thread, time::Duration};
+///
+/// use robius_authentication::{AppLock, Context, LockState, PolicyBuilder, Text};
+///
+/// let policy = PolicyBuilder::new().build().unwrap();
+/// let text = Text::from_reason("unlock the app").unwrap();
+/// let app_lock = AppLock::new(Context::new(()), policy, text)
+///     .with_idle_timeout(Some(Duration::from_secs(60)));
+///
+/// let mut states = app_lock.subscribe();
+/// thread::spawn(move || {
+///     while let Some(state) = states.changed() {
+///         if state == LockState::Locked {
+///             println!("hiding the app's contents");
+///         }
+///     }
+/// });
+///
+/// app_lock.unlock(|result| println!("unlocked: {result:?}"))?;
+/// // Called from the app's input handlers.
+/// app_lock.touch();
+/// # Ok::<_, robius_authentication::Error>(())
+/// ```
+pub struct AppLock {
+    shared: Arc<Shared>,
+    context: Context,
+    policy: Policy,
+    text: TextBuf,
+    #[cfg(target_os = "linux")]
+    _watch: Option<crate::linux::InvalidationWatch>,
+}
+
+impl fmt::Debug for AppLock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.shared.lock();
+        f.debug_struct("AppLock")
+            .field("state", &inner.state)
+            .field("idle_timeout", &inner.idle_timeout)
+            .field("context", &self.context)
+            .field("policy", &self.policy)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AppLock {
+    /// Returns a new, locked app lock, which unlocks by authenticating using
+    /// the given policy and text.
+    pub fn new(context: Context, policy: Policy, text: TextBuf) -> Self {
+        let shared = Arc::new(Shared {
+            inner: Mutex::new(Inner {
+                state: LockState::Locked,
+                version: 0,
+                attempt: 0,
+                last_activity: Instant::now(),
+                idle_timeout: Some(DEFAULT_IDLE_TIMEOUT),
+                clock: Arc::new(SystemClock),
+                closed: false,
+            }),
+            changed: Condvar::new(),
+        });
+
+        let timer = Arc::downgrade(&shared);
+        // The app lock works without the timer, so failing to spawn it is
+        // ignored, as is failing to watch for the user walking away.
+        let _ = thread::Builder::new()
+            .name("robius-authentication-app-lock".to_owned())
+            .spawn(move || run_timer(timer));
+
+        #[cfg(target_os = "linux")]
+        let _watch = {
+            let shared = Arc::downgrade(&shared);
+            context
+                .watch_invalidation(move |_| {
+                    if let Some(shared) = shared.upgrade() {
+                        shared.lock_app();
+                    }
+                })
+                .ok()
+        };
+
+        Self {
+            shared,
+            context,
+            policy,
+            text,
+            #[cfg(target_os = "linux")]
+            _watch,
+        }
+    }
+
+    /// Sets how long the app may be idle before it is locked, or `None` to
+    /// only lock it explicitly.
+    ///
+    /// Defaults to [`DEFAULT_IDLE_TIMEOUT`].
+    #[must_use]
+    pub fn with_idle_timeout(self, idle_timeout: Option<Duration>) -> Self {
+        self.shared
+            .update(|inner| inner.idle_timeout = idle_timeout);
+        self
+    }
+
+    /// Sets the clock used to measure inactivity, e.g., a [`ManualClock`] for
+    /// tests.
+    ///
+    /// Defaults to [`SystemClock`].
+    #[must_use]
+    pub fn with_clock(self, clock: impl Clock) -> Self {
+        self.shared.update(|inner| {
+            inner.clock = Arc::new(clock);
+            inner.last_activity = inner.clock.now();
+        });
+        self
+    }
+
+    /// Returns the current state.
+    pub fn state(&self) -> LockState {
+        self.shared.lock().state
+    }
+
+    /// Returns a receiver of the state, which is notified whenever it
+    /// changes.
+    pub fn subscribe(&self) -> StateReceiver {
+        StateReceiver {
+            shared: self.shared.clone(),
+            version: self.shared.lock().version,
+        }
+    }
+
+    /// Reports user activity, postponing the idle timeout.
+    ///
+    /// This has no effect unless the app is unlocked.
+    pub fn touch(&self) {
+        self.shared.update(|inner| {
+            if inner.state == LockState::Unlocked {
+                inner.last_activity = inner.clock.now();
+            }
+        });
+    }
+
+    /// Locks the app immediately.
+    ///
+    /// If the user is currently being prompted to unlock the app, the app
+    /// stays locked even if authentication succeeds. The reuse windows of the
+    /// context's actions are also reset, see
+    /// [`ActionRegistry::revoke`](crate::ActionRegistry::revoke).
+    pub fn lock(&self) {
+        self.context.actions().revoke();
+        self.shared.lock_app();
+    }
+
+    /// Locks the app if it was idle for longer than the idle timeout.
+    ///
+    /// This is done automatically in the background using the system's
+    /// clock, so it only needs to be called after advancing a
+    /// [`ManualClock`].
+    pub fn check(&self) {
+        self.shared.check();
+    }
+
+    /// Prompts the user to unlock the app, see [`Context::authenticate`].
+    ///
+    /// The `callback` is called with the result of authentication, after the
+    /// state has been updated. If the app was locked while the user was being
+    /// prompted, the callback is called with [`Error::SystemCanceled`]. If
+    /// the app is already unlocked, the callback is called immediately.
+    ///
+    /// Returns [`Error::Busy`] if the user is already being prompted.
+    pub fn unlock<F>(&self, callback: F) -> Result<()>
+    where
+        F: Fn(Result<()>) + Send + 'static,
+    {
+        let attempt = {
+            let mut inner = self.shared.lock();
+            match inner.state {
+                LockState::Unlocked => {
+                    drop(inner);
+                    callback(Ok(()));
+                    return Ok(());
+                }
+                LockState::Unlocking => return Err(Error::Busy),
+                LockState::Locked => inner.begin_unlock(),
+            }
+        };
+        self.shared.changed.notify_all();
+
+        let shared = Arc::downgrade(&self.shared);
+        let result = self
+            .context
+            .authenticate(self.text.as_text(), &self.policy, move |result| {
+                let result = match shared.upgrade() {
+                    Some(shared) => shared.finish_unlock(attempt, result),
+                    None => result,
+                };
+                callback(result);
+            });
+        if result.is_err() {
+            self.shared.update(|inner| {
+                if inner.is_unlocking(attempt) {
+                    inner.set_state(LockState::Locked);
+                }
+            });
+        }
+        result
+    }
+}
+
+impl Drop for AppLock {
+    fn drop(&mut self) {
+        self.shared.update(|inner| inner.closed = true);
+    }
+}
+
+/// Receives the state of an [`AppLock`], see [`AppLock::subscribe`].
+pub struct StateReceiver {
+    shared: Arc<Shared>,
+    /// The version of the state last seen.
+    version: u64,
+}
+
+impl fmt::Debug for StateReceiver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StateReceiver")
+            .field("state", &self.shared.lock().state)
+            .finish_non_exhaustive()
+    }
+}
+
+impl StateReceiver {
+    /// Returns the current state, marking it as seen.
+    pub fn get(&mut self) -> LockState {
+        let inner = self.shared.lock();
+        self.version = inner.version;
+        inner.state
+    }
+
+    /// Returns whether the state changed since it was last seen.
+    pub fn has_changed(&self) -> bool {
+        self.shared.lock().version != self.version
+    }
+
+    /// Blocks until the state changes, returning the new state.
+    ///
+    /// Returns `None` once the [`AppLock`] is dropped.
+    pub fn changed(&mut self) -> Option<LockState> {
+        self.wait(None)
+    }
+
+    /// Blocks until the state changes or the timeout elapses, returning the
+    /// new state if it changed.
+    ///
+    /// Returns `None` on timeout or once the [`AppLock`] is dropped.
+    pub fn changed_timeout(&mut self, timeout: Duration) -> Option<LockState> {
+        self.wait(Some(Instant::now() + timeout))
+    }
+
+    fn wait(&mut self, deadline: Option<Instant>) -> Option<LockState> {
+        let mut inner = self.shared.lock();
+        while inner.version == self.version && !inner.closed {
+            inner = match deadline {
+                None => self
+                    .shared
+                    .changed
+                    .wait(inner)
+                    .unwrap_or_else(|e| e.into_inner()),
+                Some(deadline) => {
+                    let timeout = deadline.checked_duration_since(Instant::now())?;
+                    self.shared
+                        .changed
+                        .wait_timeout(inner, timeout)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0
+                }
+            };
+        }
+        if inner.version == self.version {
+            return None;
+        }
+        self.version = inner.version;
+        Some(inner.state)
+    }
+}
+
+/// The state shared with the timer, the callbacks and the receivers.
+struct Shared {
+    inner: Mutex<Inner>,
+    /// Notified whenever `inner` is updated.
+    changed: Condvar,
+}
+
+struct Inner {
+    state: LockState,
+    /// Incremented whenever the state changes.
+    version: u64,
+    /// Incremented whenever the user is prompted, so that the result of a
+    /// prompt started before the app was locked again is ignored.
+    attempt: u64,
+    last_activity: Instant,
+    idle_timeout: Option<Duration>,
+    clock: Arc<dyn Clock>,
+    /// Whether the app lock was dropped.
+    closed: bool,
+}
+
+impl Inner {
+    fn set_state(&mut self, state: LockState) {
+        if self.state != state {
+            self.state = state;
+            self.version += 1;
+        }
+    }
+
+    /// Starts prompting the user, returning the attempt.
+    fn begin_unlock(&mut self) -> u64 {
+        self.attempt += 1;
+        self.set_state(LockState::Unlocking);
+        self.attempt
+    }
+
+    /// Returns whether the user is being prompted by the given attempt.
+    fn is_unlocking(&self, attempt: u64) -> bool {
+        self.state == LockState::Unlocking && self.attempt == attempt
+    }
+
+    /// Returns when the app is locked due to inactivity, if it is unlocked.
+    fn deadline(&self) -> Option<Instant> {
+        match self.state {
+            LockState::Unlocked => Some(self.last_activity + self.idle_timeout?),
+            LockState::Locked | LockState::Unlocking => None,
+        }
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Updates the state and wakes up the timer and the receivers.
+    fn update(&self, f: impl FnOnce(&mut Inner)) {
+        f(&mut self.lock());
+        self.changed.notify_all();
+    }
+
+    fn lock_app(&self) {
+        self.update(|inner| inner.set_state(LockState::Locked));
+    }
+
+    fn check(&self) {
+        self.update(|inner| {
+            if inner
+                .deadline()
+                .is_some_and(|deadline| inner.clock.now() >= deadline)
+            {
+                inner.set_state(LockState::Locked);
+            }
+        });
+    }
+
+    fn finish_unlock(&self, attempt: u64, result: Result<()>) -> Result<()> {
+        let mut inner = self.lock();
+        let result = match (inner.is_unlocking(attempt), result) {
+            (true, Ok(())) => {
+                inner.last_activity = inner.clock.now();
+                inner.set_state(LockState::Unlocked);
+                Ok(())
+            }
+            // Locked while prompting, possibly followed by another prompt.
+            (false, Ok(())) => Err(Error::SystemCanceled),
+            (unlocking, Err(error)) => {
+                if unlocking {
+                    inner.set_state(LockState::Locked);
+                }
+                Err(error)
+            }
+        };
+        drop(inner);
+        self.changed.notify_all();
+        result
+    }
+}
+
+/// Locks the app once the idle timeout elapses, until the app lock is
+/// dropped.
+fn run_timer(shared: Weak<Shared>) {
+    loop {
+        let Some(shared) = shared.upgrade() else {
+            return;
+        };
+        shared.check();
+
+        let inner = shared.lock();
+        if inner.closed {
+            return;
+        }
+        // Woken up whenever the state or the activity changes.
+        match inner.deadline() {
+            Some(deadline) => {
+                let timeout = deadline.saturating_duration_since(inner.clock.now());
+                drop(shared.changed.wait_timeout(inner, timeout));
+            }
+            None => drop(shared.changed.wait(inner)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ActionRegistry, PolicyBuilder, Text};
+
+    const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+    /// Returns an unlocked app lock using `clock`.
+    fn unlocked(clock: &ManualClock) -> AppLock {
+        let policy = PolicyBuilder::new().build().unwrap();
+        let text = Text::from_reason("unlock the app").unwrap();
+        let app_lock = AppLock::new(Context::new(()), policy, text)
+            .with_idle_timeout(Some(IDLE_TIMEOUT))
+            .with_clock(clock.clone());
+        unlock(&app_lock);
+        app_lock
+    }
+
+    /// Unlocks the app as if the user authenticated.
+    fn unlock(app_lock: &AppLock) {
+        let attempt = app_lock.shared.lock().begin_unlock();
+        app_lock.shared.finish_unlock(attempt, Ok(())).unwrap();
+    }
+
+    #[test]
+    fn lock_revokes_the_actions() {
+        let actions = ActionRegistry::new();
+        let generation = actions.authenticated().generation();
+        let policy = PolicyBuilder::new().build().unwrap();
+        let text = Text::from_reason("unlock the app").unwrap();
+        let app_lock = AppLock::new(Context::new(()).with_actions(actions), policy, text);
+        unlock(&app_lock);
+        app_lock.lock();
+        assert_eq!(app_lock.state(), LockState::Locked);
+        let actions = app_lock.context.actions();
+        assert_ne!(actions.authenticated().generation(), generation);
+    }
+
+    #[test]
+    fn idle_timeout() {
+        let clock = ManualClock::new();
+        let app_lock = unlocked(&clock);
+        clock.advance(IDLE_TIMEOUT - Duration::from_secs(1));
+        app_lock.check();
+        assert_eq!(app_lock.state(), LockState::Unlocked);
+
+        clock.advance(Duration::from_secs(1));
+        app_lock.check();
+        assert_eq!(app_lock.state(), LockState::Locked);
+    }
+
+    #[test]
+    fn touch_postpones_the_idle_timeout() {
+        let clock = ManualClock::new();
+        let app_lock = unlocked(&clock);
+        clock.advance(IDLE_TIMEOUT / 2);
+        app_lock.touch();
+        clock.advance(IDLE_TIMEOUT / 2);
+        app_lock.check();
+        assert_eq!(app_lock.state(), LockState::Unlocked);
+
+        clock.advance(IDLE_TIMEOUT / 2);
+        app_lock.check();
+        assert_eq!(app_lock.state(), LockState::Locked);
+
+        // Touching the locked app has no effect.
+        let last_activity = app_lock.shared.lock().last_activity;
+        clock.advance(IDLE_TIMEOUT);
+        app_lock.touch();
+        assert_eq!(app_lock.shared.lock().last_activity, last_activity);
+    }
+
+    #[test]
+    fn without_idle_timeout() {
+        let clock = ManualClock::new();
+        let app_lock = unlocked(&clock).with_idle_timeout(None);
+        clock.advance(DEFAULT_IDLE_TIMEOUT * 10);
+        app_lock.check();
+        assert_eq!(app_lock.state(), LockState::Unlocked);
+    }
+
+    #[test]
+    fn locked_while_unlocking() {
+        let clock = ManualClock::new();
+        let app_lock = unlocked(&clock);
+        app_lock.lock();
+        let attempt = app_lock.shared.lock().begin_unlock();
+        app_lock.lock();
+        let result = app_lock.shared.finish_unlock(attempt, Ok(()));
+        assert!(matches!(result, Err(Error::SystemCanceled)));
+        assert_eq!(app_lock.state(), LockState::Locked);
+    }
+
+    #[test]
+    fn stale_prompt_is_ignored() {
+        let clock = ManualClock::new();
+        let app_lock = unlocked(&clock);
+        app_lock.lock();
+        let stale = app_lock.shared.lock().begin_unlock();
+        app_lock.lock();
+        let attempt = app_lock.shared.lock().begin_unlock();
+
+        // The first prompt neither unlocks nor locks the app.
+        let result = app_lock.shared.finish_unlock(stale, Ok(()));
+        assert!(matches!(result, Err(Error::SystemCanceled)));
+        assert_eq!(app_lock.state(), LockState::Unlocking);
+        let result = app_lock
+            .shared
+            .finish_unlock(stale, Err(Error::UserCanceled));
+        assert!(matches!(result, Err(Error::UserCanceled)));
+        assert_eq!(app_lock.state(), LockState::Unlocking);
+
+        app_lock.shared.finish_unlock(attempt, Ok(())).unwrap();
+        assert_eq!(app_lock.state(), LockState::Unlocked);
+    }
+
+    #[test]
+    fn state_receiver() {
+        let clock = ManualClock::new();
+        let app_lock = unlocked(&clock);
+        let mut states = app_lock.subscribe();
+        assert!(!states.has_changed());
+        assert_eq!(states.get(), LockState::Unlocked);
+        assert_eq!(states.changed_timeout(Duration::from_millis(10)), None);
+
+        clock.advance(IDLE_TIMEOUT);
+        app_lock.check();
+        assert!(states.has_changed());
+        assert_eq!(states.get(), LockState::Locked);
+        assert!(!states.has_changed());
+
+        unlock(&app_lock);
+        assert_eq!(states.changed(), Some(LockState::Unlocked));
+
+        let waiter = thread::spawn(move || (states.changed(), states.changed()));
+        app_lock.lock();
+        drop(app_lock);
+        // The receiver ends once the app lock is dropped.
+        assert_eq!(waiter.join().unwrap(), (Some(LockState::Locked), None));
+    }
+}
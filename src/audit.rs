@@ -0,0 +1,797 @@
+//! Recording authentication events for auditing.
+
+use std::{
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{AuthenticationMethod, Error, Identity, Outcome, PolicyBuilder, Result, Text};
+
+#[cfg(feature = "audit-chain")]
+mod chain;
+
+#[cfg(feature = "audit-chain")]
+pub use chain::{verify_audit_log, AuditKey, AuditLogError, AuditLogSummary};
+
+/// Receives a record of every authentication request and its outcome, see
+/// [`Context::with_audit_sink`](crate::Context::with_audit_sink).
+///
+/// Records are passed to the sink on the thread that requested or completed
+/// the authentication, so it should not block for long. A sink can't fail
+/// authentication; it is responsible for handling its own errors.
+///
+/// The trait is implemented for closures:
+/// ```
+/// use robius_authentication::{AuditRecord, Context};
+///
+/// let context = Context::new(()).with_audit_sink(|record: &AuditRecord| {
+///     eprintln!("{record:?}");
+/// });
+/// ```
+pub trait AuditSink: Send + Sync {
+    /// Records an authentication event.
+    fn record(&self, record: &AuditRecord);
+}
+
+impl<F> AuditSink for F
+where
+    F: Fn(&AuditRecord) + Send + Sync,
+{
+    #[inline]
+    fn record(&self, record: &AuditRecord) {
+        self(record)
+    }
+}
+
+/// What happened to an authentication request, see [`AuditRecord`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AuditEvent {
+    /// The authentication was requested.
+    Requested,
+    /// The user authenticated.
+    Succeeded,
+    /// The authentication failed with the given error, e.g., `UserCanceled`.
+    ///
+    /// The error is the name of the [`Error`] variant.
+    Failed(String),
+    /// The authentication was not prompted for, because the action was
+    /// authenticated within its reuse window, see
+    /// [`Action::with_reuse_window`](crate::Action::with_reuse_window).
+    Reused,
+}
+
+/// A record of an authentication event, see [`AuditSink`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AuditRecord {
+    /// The ID of the request, shared by the records of a request and its
+    /// outcome.
+    ///
+    /// IDs are unique within the current process, in increasing order.
+    pub request: u64,
+    /// When the event happened.
+    pub time: SystemTime,
+    /// What happened.
+    pub event: AuditEvent,
+    /// The audit label or id of the action, see
+    /// [`Context::authenticate_action`](crate::Context::authenticate_action),
+    /// or the polkit action of
+    /// [`Context::authorize_and_run`](crate::Context::authorize_and_run).
+    pub action: Option<String>,
+    /// The policy of the request, in [`AuditEvent::Requested`] records.
+    pub policy: Option<PolicyBuilder>,
+    /// The text displayed on the current target, in
+    /// [`AuditEvent::Requested`] records.
+    pub text: Option<String>,
+    /// The user who approved the authentication, if known, in
+    /// [`AuditEvent::Succeeded`] records, see [`Outcome::user`].
+    pub user: Option<String>,
+    /// The method the user authenticated with, if known, in
+    /// [`AuditEvent::Succeeded`] records, see [`Outcome::method`].
+    pub method: Option<AuthenticationMethod>,
+}
+
+impl AuditRecord {
+    fn new(request: u64, event: AuditEvent, action: Option<&str>) -> Self {
+        Self {
+            request,
+            time: SystemTime::now(),
+            event,
+            action: action.map(ToOwned::to_owned),
+            policy: None,
+            text: None,
+            user: None,
+            method: None,
+        }
+    }
+
+    /// Returns the record as a JSON object, see [`JsonLinesSink`].
+    ///
+    /// The text is replaced by the result of `redact`, and omitted if it
+    /// returns `None`.
+    fn to_json(&self, redact: &dyn Fn(&str) -> Option<String>) -> String {
+        let mut json = format!(
+            "{{\"v\":{},\"time\":\"{}\",\"request\":{},\"event\":",
+            JsonLinesSink::FORMAT_VERSION,
+            rfc3339(self.time),
+            self.request,
+        );
+        match &self.event {
+            AuditEvent::Requested => json.push_str("\"requested\""),
+            AuditEvent::Succeeded => json.push_str("\"succeeded\""),
+            AuditEvent::Failed(error) => {
+                json.push_str("\"failed\",\"error\":");
+                push_string(&mut json, error);
+            }
+            AuditEvent::Reused => json.push_str("\"reused\""),
+        }
+        if let Some(action) = &self.action {
+            json.push_str(",\"action\":");
+            push_string(&mut json, action);
+        }
+        if let Some(policy) = &self.policy {
+            json.push_str(",\"methods\":[");
+            let methods = [
+                ("biometrics", policy.biometrics.is_some()),
+                ("password", policy.password),
+                ("companion", policy.companion),
+            ];
+            let methods: Vec<_> = methods
+                .iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(method, _)| format!("\"{method}\""))
+                .collect();
+            json.push_str(&methods.join(","));
+            json.push_str("],\"identity\":");
+            match &policy.identity {
+                Identity::CurrentUser => json.push_str("\"current_user\""),
+                Identity::Administrator => json.push_str("\"administrator\""),
+                Identity::User(name) => {
+                    json.push_str("{\"user\":");
+                    push_string(&mut json, name);
+                    json.push('}');
+                }
+            }
+        }
+        if let Some(text) = self.text.as_deref().and_then(redact) {
+            json.push_str(",\"text\":");
+            push_string(&mut json, &text);
+        }
+        if let Some(user) = &self.user {
+            json.push_str(",\"user\":");
+            push_string(&mut json, user);
+        }
+        if let Some(method) = self.method {
+            json.push_str(",\"method\":\"");
+            json.push_str(method_name(method));
+            json.push('"');
+        }
+        json.push('}');
+        json
+    }
+}
+
+/// Returns the name of the method in records, e.g., `password`.
+fn method_name(method: AuthenticationMethod) -> &'static str {
+    match method {
+        AuthenticationMethod::Biometrics => "biometrics",
+        AuthenticationMethod::Password => "password",
+        AuthenticationMethod::Companion => "companion",
+    }
+}
+
+/// Appends `value` to `json` as a JSON string.
+fn push_string(json: &mut String, value: &str) {
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+/// Formats the time as an RFC 3339 timestamp in UTC with millisecond
+/// precision, e.g., `2024-05-01T12:34:56.789Z`.
+fn rfc3339(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // The civil date of the days since the epoch, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis(),
+    )
+}
+
+/// Returns the text displayed on the current target.
+fn displayed_text<'a>(text: &'a Text) -> &'a str {
+    cfg_if::cfg_if! {
+        if #[cfg(target_os = "android")] {
+            text.android.description.unwrap_or(text.android.title)
+        } else if #[cfg(target_vendor = "apple")] {
+            text.apple
+        } else if #[cfg(target_os = "windows")] {
+            &text.windows.description
+        } else {
+            text.linux.message
+        }
+    }
+}
+
+/// Returns the name of the error variant, e.g., `UserCanceled`.
+fn error_name(error: &Error) -> String {
+    let name = format!("{error:?}");
+    match name.find('(') {
+        Some(end) => name[..end].to_owned(),
+        None => name,
+    }
+}
+
+/// The audit sink of a [`Context`](crate::Context), if any.
+#[derive(Clone, Default)]
+pub(crate) struct Auditor {
+    sink: Option<Arc<dyn AuditSink>>,
+}
+
+impl fmt::Debug for Auditor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Auditor")
+            .field("enabled", &self.sink.is_some())
+            .finish()
+    }
+}
+
+impl Auditor {
+    pub(crate) fn new(sink: impl AuditSink + 'static) -> Self {
+        Self {
+            sink: Some(Arc::new(sink)),
+        }
+    }
+
+    /// Records that authentication was requested, returning the request to
+    /// record its outcome.
+    pub(crate) fn requested(
+        &self,
+        action: Option<&str>,
+        policy: Option<&PolicyBuilder>,
+        text: Option<&Text>,
+    ) -> AuditRequest {
+        static NEXT_REQUEST: AtomicU64 = AtomicU64::new(1);
+
+        let Some(sink) = &self.sink else {
+            return AuditRequest::default();
+        };
+        let request = NEXT_REQUEST.fetch_add(1, Ordering::Relaxed);
+        let mut record = AuditRecord::new(request, AuditEvent::Requested, action);
+        record.policy = policy.cloned();
+        record.text = text.map(|text| displayed_text(text).to_owned());
+        sink.record(&record);
+        AuditRequest {
+            sink: Some(sink.clone()),
+            request,
+            action: action.map(ToOwned::to_owned),
+        }
+    }
+}
+
+/// A requested authentication whose outcome is yet to be recorded.
+#[derive(Clone, Default)]
+pub(crate) struct AuditRequest {
+    sink: Option<Arc<dyn AuditSink>>,
+    request: u64,
+    action: Option<String>,
+}
+
+impl AuditRequest {
+    /// Records the outcome of the authentication.
+    pub(crate) fn finished(&self, result: &Result<Outcome>) {
+        match result {
+            Ok(outcome) => self.record(AuditEvent::Succeeded, |record| {
+                record.user = outcome.user.clone();
+                record.method = outcome.method;
+            }),
+            Err(e) => self.failed(e),
+        }
+    }
+
+    /// Records that the authentication failed.
+    pub(crate) fn failed(&self, error: &Error) {
+        self.record(AuditEvent::Failed(error_name(error)), |_| {});
+    }
+
+    /// Records that a previous authentication was reused.
+    pub(crate) fn reused(&self) {
+        self.record(AuditEvent::Reused, |_| {});
+    }
+
+    fn record(&self, event: AuditEvent, update: impl FnOnce(&mut AuditRecord)) {
+        if let Some(sink) = &self.sink {
+            let mut record = AuditRecord::new(self.request, event, self.action.as_deref());
+            update(&mut record);
+            sink.record(&record);
+        }
+    }
+}
+
+type Redaction = Box<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+/// An [`AuditSink`] appending the records to a [JSON Lines] file, rotating it
+/// once it exceeds a size.
+///
+/// Each line is a JSON object with the following fields, in this order:
+/// - `v`: the version of the format, currently `1`, see
+///   [`Self::FORMAT_VERSION`].
+/// - `time`: when the event happened, as an RFC 3339 timestamp in UTC with
+///   millisecond precision, e.g., `"2024-05-01T12:34:56.789Z"`.
+/// - `request`: the ID of the request, see [`AuditRecord::request`].
+/// - `event`: `"requested"`, `"succeeded"`, `"failed"` or `"reused"`.
+/// - `error`: for `"failed"` events, the name of the [`Error`] variant,
+///   e.g., `"UserCanceled"`.
+/// - `action`: optional, see [`AuditRecord::action`].
+/// - `methods`: for `"requested"` events, the methods allowed by the policy,
+///   any of `"biometrics"`, `"password"` and `"companion"`.
+/// - `identity`: for `"requested"` events, the identity that must approve the
+///   authentication, i.e., `"current_user"`, `"administrator"` or
+///   `{"user": "..."}`.
+/// - `text`: for `"requested"` events, the text displayed on the current
+///   target, unless redacted, see [`Self::with_text_redaction`].
+/// - `user`: for `"succeeded"` events, the user who approved the
+///   authentication, if known.
+/// - `method`: for `"succeeded"` events, the method the user authenticated
+///   with, if known, i.e., `"biometrics"`, `"password"` or `"companion"`.
+/// - `seq`, `prev` and `mac`: if the records are hash-chained, the sequence
+///   number of the record, starting at 1, and the HMAC-SHA256 of the previous
+///   and of this record in hexadecimal, see `Self::with_hash_chain` with the
+///   `audit-chain` feature.
+///
+/// Optional fields are omitted rather than `null`. New fields may be added
+/// without changing the version, so readers should ignore unknown fields.
+///
+/// ```json
+/// {"v":1,"time":"2024-05-01T12:34:56.789Z","request":1,"event":"requested","action":"export-keys","methods":["biometrics","password"],"identity":"current_user","text":"Authenticate to export your keys."}
+/// {"v":1,"time":"2024-05-01T12:35:01.042Z","request":1,"event":"succeeded","action":"export-keys","user":"alice","method":"biometrics"}
+/// ```
+///
+/// [JSON Lines]: https://jsonlines.org/
+pub struct JsonLinesSink {
+    writer: Mutex<Writer>,
+    redaction: Redaction,
+}
+
+struct Writer {
+    file: RotatingFile,
+    #[cfg(feature = "audit-chain")]
+    chain: Option<chain::Chain>,
+}
+
+impl fmt::Debug for JsonLinesSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let writer = self.lock_writer();
+        let mut f = f.debug_struct("JsonLinesSink");
+        f.field("path", &writer.file.path)
+            .field("max_size", &writer.file.max_size)
+            .field("max_files", &writer.file.max_files);
+        #[cfg(feature = "audit-chain")]
+        f.field("hash_chain", &writer.chain.is_some());
+        f.finish_non_exhaustive()
+    }
+}
+
+impl JsonLinesSink {
+    /// The version of the format, see the [type documentation](Self).
+    pub const FORMAT_VERSION: u32 = 1;
+
+    /// Returns a sink appending to the file at `path`, creating it if it
+    /// doesn't exist.
+    ///
+    /// By default, the file is not rotated and the text is recorded as is.
+    pub fn new(path: impl Into<PathBuf>) -> io::Result<Self> {
+        Ok(Self {
+            writer: Mutex::new(Writer {
+                file: RotatingFile::open(path.into())?,
+                #[cfg(feature = "audit-chain")]
+                chain: None,
+            }),
+            redaction: Box::new(|text| Some(text.to_owned())),
+        })
+    }
+
+    /// Rotates the file once writing a record would make it larger than
+    /// `max_size` bytes, keeping at most `max_files` previous files.
+    ///
+    /// The previous files are named after the file with a `.1`, `.2`, etc.
+    /// suffix, from newest to oldest, e.g., `audit.jsonl.1`. If `max_files` is
+    /// zero, the file is truncated instead.
+    #[must_use]
+    pub fn with_rotation(self, max_size: u64, max_files: usize) -> Self {
+        {
+            let mut writer = self.lock_writer();
+            writer.file.max_size = Some(max_size);
+            writer.file.max_files = max_files;
+        }
+        self
+    }
+
+    /// Chains the records with an HMAC keyed with `key`, so that tampering
+    /// with the log can be detected using [`verify_audit_log`].
+    ///
+    /// Each record includes its sequence number and the MAC of the previous
+    /// record, and is followed by its own MAC, see the [type
+    /// documentation](Self). The sequence number and MAC of the last record
+    /// are stored in a head file named after the log with a `.head` suffix,
+    /// e.g., `audit.jsonl.head`, so that deleting the last records is detected
+    /// too.
+    ///
+    /// The chain is resumed if the log was already chained with the key.
+    /// Returns an error if the log is not empty and not chained, or if its
+    /// head file was modified.
+    ///
+    /// ```no_run
+    /// use robius_authentication::{AuditKey, Context, JsonLinesSink};
+    ///
+    /// let key = AuditKey::from_keyring("com.example.app", "audit").unwrap();
+    /// let sink = JsonLinesSink::new("audit.jsonl")?.with_hash_chain(key)?;
+    /// let context = Context::new(()).with_audit_sink(sink);
+    /// # Ok::<_, std::io::Error>(())
+    /// ```
+    #[cfg(feature = "audit-chain")]
+    pub fn with_hash_chain(self, key: AuditKey) -> io::Result<Self> {
+        {
+            let mut writer = self.lock_writer();
+            writer.chain = Some(chain::Chain::resume(key, &writer.file.path)?);
+        }
+        Ok(self)
+    }
+
+    /// Sets a function that redacts the text of the prompt before it is
+    /// recorded.
+    ///
+    /// The function is given the text displayed on the current target and
+    /// returns the text to record. If it returns `None`, the text is omitted.
+    ///
+    /// ```no_run
+    /// use robius_authentication::JsonLinesSink;
+    ///
+    /// // Omits the text, e.g., if it may contain personal data.
+    /// let sink = JsonLinesSink::new("audit.jsonl")?.with_text_redaction(|_| None);
+    /// # Ok::<_, std::io::Error>(())
+    /// ```
+    #[must_use]
+    pub fn with_text_redaction<F>(self, redact: F) -> Self
+    where
+        F: Fn(&str) -> Option<String> + Send + Sync + 'static,
+    {
+        Self {
+            redaction: Box::new(redact),
+            ..self
+        }
+    }
+
+    fn lock_writer(&self) -> std::sync::MutexGuard<'_, Writer> {
+        self.writer.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl AuditSink for JsonLinesSink {
+    fn record(&self, record: &AuditRecord) {
+        let line = record.to_json(&self.redaction);
+        let writer = &mut *self.lock_writer();
+        // Errors can't be reported to the app, and must not prevent
+        // authentication.
+        #[cfg(feature = "audit-chain")]
+        if let Some(chain) = &mut writer.chain {
+            let _ = chain.write(&mut writer.file, &line);
+            return;
+        }
+        let _ = writer.file.write(format!("{line}\n").as_bytes());
+    }
+}
+
+/// A file that is appended to, rotating it once it exceeds a size.
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: Option<u64>,
+    max_files: usize,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            file,
+            size,
+            max_size: None,
+            max_files: 0,
+        })
+    }
+
+    /// Writes `data` in a single write, rotating the file first if it would
+    /// exceed the maximum size.
+    ///
+    /// Returns whether the file was rotated.
+    fn write(&mut self, data: &[u8]) -> io::Result<bool> {
+        let rotate = self
+            .max_size
+            .is_some_and(|max_size| self.size > 0 && self.size + data.len() as u64 > max_size);
+        if rotate {
+            self.rotate()?;
+        }
+        self.file.write_all(data)?;
+        self.file.flush()?;
+        self.size += data.len() as u64;
+        Ok(rotate)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.max_files == 0 {
+            self.file.set_len(0)?;
+        } else {
+            for index in (1..self.max_files).rev() {
+                let from = rotated_path(&self.path, index);
+                if from.exists() {
+                    fs::rename(from, rotated_path(&self.path, index + 1))?;
+                }
+            }
+            fs::rename(&self.path, rotated_path(&self.path, 1))?;
+            self.file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+        }
+        self.size = 0;
+        Ok(())
+    }
+}
+
+/// Returns the path of the `index`th previous file of a rotated file.
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{index}"));
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{process, time::Duration};
+
+    use super::*;
+
+    /// Returns the path of a log in a new temporary directory.
+    fn log_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("robius-audit-{}-{name}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("audit.jsonl")
+    }
+
+    /// Returns the request IDs of the records in the file at `path`.
+    fn requests(path: &Path) -> Vec<u64> {
+        let log = fs::read_to_string(path).unwrap();
+        log.lines()
+            .map(|line| {
+                let start = line.find("\"request\":").unwrap() + 10;
+                let end = start + line[start..].find(',').unwrap();
+                line[start..end].parse().unwrap()
+            })
+            .collect()
+    }
+
+    /// Writes a record for each of the requests using the sink.
+    fn write(sink: &JsonLinesSink, requests: impl IntoIterator<Item = u64>) {
+        for request in requests {
+            sink.record(&AuditRecord::new(request, AuditEvent::Requested, None));
+        }
+    }
+
+    fn time(secs: u64, millis: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs) + Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn rotation() {
+        let path = log_path("rotation");
+        let sink = JsonLinesSink::new(&path).unwrap().with_rotation(1 << 20, 2);
+        write(&sink, 1..=3);
+        assert_eq!(requests(&path), [1, 2, 3]);
+        assert!(!rotated_path(&path, 1).exists());
+
+        // Each record exceeds the size, so the file is rotated before each.
+        let sink = sink.with_rotation(1, 2);
+        write(&sink, 4..=6);
+        assert_eq!(requests(&path), [6]);
+        assert_eq!(requests(&rotated_path(&path, 1)), [5]);
+        assert_eq!(requests(&rotated_path(&path, 2)), [4]);
+        assert!(!rotated_path(&path, 3).exists());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn rotation_without_previous_files_truncates() {
+        let path = log_path("truncation");
+        write(&JsonLinesSink::new(&path).unwrap(), 1..=2);
+        // The size of an existing file counts towards the maximum.
+        let sink = JsonLinesSink::new(&path).unwrap().with_rotation(1, 0);
+        write(&sink, 3..=4);
+        assert_eq!(requests(&path), [4]);
+        assert!(!rotated_path(&path, 1).exists());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn text_redaction() {
+        let mut record = AuditRecord::new(1, AuditEvent::Requested, None);
+        record.text = Some("Authenticate to export your keys.".to_owned());
+        let record_text = |sink: JsonLinesSink, path: &Path| {
+            sink.record(&record);
+            fs::read_to_string(path).unwrap()
+        };
+
+        let path = log_path("redaction");
+        let sink = JsonLinesSink::new(&path).unwrap();
+        let log = record_text(sink, &path);
+        assert!(
+            log.contains(r#""text":"Authenticate to export your keys."}"#),
+            "{log}"
+        );
+
+        fs::remove_file(&path).unwrap();
+        let sink = JsonLinesSink::new(&path)
+            .unwrap()
+            .with_text_redaction(|text| Some(text.replace("keys", "[redacted]")));
+        let log = record_text(sink, &path);
+        assert!(
+            log.contains(r#""text":"Authenticate to export your [redacted]."}"#),
+            "{log}"
+        );
+
+        fs::remove_file(&path).unwrap();
+        let sink = JsonLinesSink::new(&path)
+            .unwrap()
+            .with_text_redaction(|_| None);
+        let log = record_text(sink, &path);
+        assert!(log.trim_end().ends_with(r#""event":"requested"}"#), "{log}");
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn strings_are_escaped() {
+        let mut json = String::new();
+        push_string(&mut json, "a\"b\\c\nd\re\tf\u{0}g\u{1b}h\u{7f}i\u{85}j é");
+        // Other characters are written as is, the file being UTF-8.
+        assert_eq!(json, r#""a\"b\\c\nd\re\tf\u0000g\u001bh\u007fi\u0085j é""#);
+    }
+
+    #[test]
+    fn timestamps() {
+        for (time, expected) in [
+            (UNIX_EPOCH, "1970-01-01T00:00:00.000Z"),
+            // Times before the epoch are clamped to it.
+            (
+                UNIX_EPOCH - Duration::from_secs(1),
+                "1970-01-01T00:00:00.000Z",
+            ),
+            (time(86_399, 999), "1970-01-01T23:59:59.999Z"),
+            (time(946_684_799, 999), "1999-12-31T23:59:59.999Z"),
+            (time(946_684_800, 0), "2000-01-01T00:00:00.000Z"),
+            // 2000 is a leap year, as it is divisible by 400.
+            (time(951_782_400, 0), "2000-02-29T00:00:00.000Z"),
+            (time(1_709_210_096, 5), "2024-02-29T12:34:56.005Z"),
+            // 2100 is not a leap year, as it is divisible by 100.
+            (time(4_107_542_399, 0), "2100-02-28T23:59:59.000Z"),
+            (time(4_107_542_400, 0), "2100-03-01T00:00:00.000Z"),
+        ] {
+            assert_eq!(rfc3339(time), expected);
+        }
+    }
+
+    #[test]
+    fn line_layout() {
+        let keep = |text: &str| Some(text.to_owned());
+        let mut requested = AuditRecord::new(1, AuditEvent::Requested, Some("export-keys"));
+        requested.time = time(1_714_566_896, 789);
+        requested.policy = Some(
+            PolicyBuilder::new()
+                .biometrics(None)
+                .companion(false)
+                .identity(Identity::user_static("admin")),
+        );
+        requested.text = Some("Authenticate to export your keys.".to_owned());
+        assert_eq!(
+            requested.to_json(&keep),
+            r#"{"v":1,"time":"2024-05-01T12:34:56.789Z","request":1,"event":"requested","action":"export-keys","methods":["password"],"identity":{"user":"admin"},"text":"Authenticate to export your keys."}"#
+        );
+
+        let mut succeeded = AuditRecord::new(1, AuditEvent::Succeeded, Some("export-keys"));
+        succeeded.time = time(1_714_566_901, 42);
+        succeeded.user = Some("admin".to_owned());
+        succeeded.method = Some(AuthenticationMethod::Password);
+        assert_eq!(
+            succeeded.to_json(&keep),
+            r#"{"v":1,"time":"2024-05-01T12:35:01.042Z","request":1,"event":"succeeded","action":"export-keys","user":"admin","method":"password"}"#
+        );
+
+        let mut failed = AuditRecord::new(2, AuditEvent::Failed("UserCanceled".to_owned()), None);
+        failed.time = time(1_714_566_902, 0);
+        assert_eq!(
+            failed.to_json(&keep),
+            r#"{"v":1,"time":"2024-05-01T12:35:02.000Z","request":2,"event":"failed","error":"UserCanceled"}"#
+        );
+
+        let mut reused = AuditRecord::new(3, AuditEvent::Reused, Some("export-keys"));
+        reused.time = time(1_714_566_903, 0);
+        assert_eq!(
+            reused.to_json(&keep),
+            r#"{"v":1,"time":"2024-05-01T12:35:03.000Z","request":3,"event":"reused","action":"export-keys"}"#
+        );
+    }
+
+    /// Returns an auditor and the records it received.
+    fn auditor() -> (Auditor, Arc<Mutex<Vec<AuditRecord>>>) {
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let auditor = Auditor::new({
+            let records = records.clone();
+            move |record: &AuditRecord| records.lock().unwrap().push(record.clone())
+        });
+        (auditor, records)
+    }
+
+    #[test]
+    fn succeeded_records_include_the_method() {
+        let (auditor, records) = auditor();
+        let request = auditor.requested(Some("export-keys"), None, None);
+        request.finished(&Ok(Outcome {
+            user: Some("alice".to_owned()),
+            method: Some(AuthenticationMethod::Biometrics),
+        }));
+        request.finished(&Ok(Outcome::default()));
+
+        let records = records.lock().unwrap();
+        assert_eq!(records[1].method, Some(AuthenticationMethod::Biometrics));
+        let json = records[1].to_json(&|text| Some(text.to_owned()));
+        assert!(
+            json.ends_with(r#""action":"export-keys","user":"alice","method":"biometrics"}"#),
+            "{json}"
+        );
+        assert_eq!(records[2].method, None);
+        let json = records[2].to_json(&|text| Some(text.to_owned()));
+        assert!(!json.contains("method"), "{json}");
+    }
+}
@@ -0,0 +1,891 @@
+//! Chaining audit records with an HMAC to detect tampering.
+//!
+//! Each record includes its sequence number and the MAC of the previous
+//! record, and is authenticated by its own MAC. The sequence number and MAC
+//! of the last record are stored in a separate, authenticated head file, so
+//! that deleting the last records is detected too.
+
+use std::{
+    error, fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use super::{rotated_path, RotatingFile};
+use crate::Result;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// The length of keys and MACs in bytes.
+const LEN: usize = 32;
+
+/// The previous MAC of the first record.
+const GENESIS: [u8; LEN] = [0; LEN];
+
+/// The secret key authenticating a hash-chained audit log, see
+/// [`JsonLinesSink::with_hash_chain`](crate::JsonLinesSink::with_hash_chain).
+///
+/// Anyone with the key can forge records, so it must not be readable by
+/// processes that can write the log, e.g., it should be stored in the
+/// platform's keyring rather than next to the log.
+#[derive(Clone)]
+pub struct AuditKey([u8; LEN]);
+
+impl fmt::Debug for AuditKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AuditKey(..)")
+    }
+}
+
+impl AuditKey {
+    /// Returns a new random key.
+    pub fn generate() -> io::Result<Self> {
+        let mut key = [0; LEN];
+        getrandom::getrandom(&mut key).map_err(io::Error::other)?;
+        Ok(Self(key))
+    }
+
+    /// Returns the key with the given bytes.
+    #[inline]
+    pub const fn from_bytes(bytes: [u8; LEN]) -> Self {
+        Self(bytes)
+    }
+
+    /// Loads the key from the file at `path`, generating it if the file
+    /// doesn't exist.
+    ///
+    /// The file contains the key in hexadecimal. A generated key's file is
+    /// only readable by the current user on Unix.
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        if let Some(key) = Self::load_from_file(path)? {
+            return Ok(key);
+        }
+        let key = Self::generate()?;
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(path)?;
+        writeln!(file, "{}", encode(&key.0))?;
+        file.sync_all()?;
+        Ok(key)
+    }
+
+    /// Loads the key from the file at `path`, like [`Self::from_file`], but
+    /// returns `None` instead of generating it, e.g., to verify a log.
+    pub fn load_from_file(path: impl AsRef<Path>) -> io::Result<Option<Self>> {
+        match fs::read_to_string(path) {
+            Ok(hex) => decode(hex.trim())
+                .map(|key| Some(Self(key)))
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid audit key")),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Loads the key from the platform's keyring, generating and storing it
+    /// if the keyring has no key for the service and account.
+    ///
+    /// The key is stored in the keychain on Apple targets, in the Credential
+    /// Manager on Windows, and using the Secret Service API, e.g., GNOME
+    /// Keyring or KWallet, on Linux, which may prompt the user to unlock the
+    /// keyring. On other targets, this returns [`Error::Unavailable`].
+    ///
+    /// [`Error::Unavailable`]: crate::Error::Unavailable
+    pub fn from_keyring(service: &str, account: &str) -> Result<Self> {
+        if let Some(key) = Self::load_from_keyring(service, account)? {
+            return Ok(key);
+        }
+        let key = Self::generate().map_err(|_| crate::Error::Unknown)?;
+        crate::sys::write_secret(service, account, &key.0)?;
+        Ok(key)
+    }
+
+    /// Loads the key from the platform's keyring, like [`Self::from_keyring`],
+    /// but returns `None` instead of generating it, e.g., to verify a log.
+    pub fn load_from_keyring(service: &str, account: &str) -> Result<Option<Self>> {
+        crate::sys::read_secret(service, account)?
+            .map(|secret| {
+                secret
+                    .try_into()
+                    .map(Self)
+                    .map_err(|_| crate::Error::Unknown)
+            })
+            .transpose()
+    }
+
+    fn mac(&self, data: &[u8]) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.0).expect("HMAC accepts any key length");
+        mac.update(data);
+        mac
+    }
+}
+
+/// A summary of a verified audit log, see [`verify_audit_log`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct AuditLogSummary {
+    /// The number of records.
+    pub records: u64,
+    /// The sequence number of the first retained record.
+    ///
+    /// Earlier records were removed by rotation.
+    pub first: u64,
+    /// The sequence number of the last record, or zero if there are none.
+    pub last: u64,
+}
+
+/// The reason an audit log failed verification, see [`verify_audit_log`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum AuditLogError {
+    /// A file of the log could not be read.
+    Io(io::Error),
+    /// The head file, recording the last record, is missing.
+    MissingHead,
+    /// A line is not a hash-chained record, e.g., because it was partially
+    /// written or edited.
+    Malformed {
+        /// The file containing the line.
+        path: PathBuf,
+        /// The line number, starting at 1.
+        line: usize,
+    },
+    /// A record or the head file was modified, or authenticated with
+    /// another key.
+    Modified {
+        /// The file containing the record.
+        path: PathBuf,
+        /// The line number, starting at 1.
+        line: usize,
+    },
+    /// Records were deleted, inserted or reordered before the given record.
+    Discontinuous {
+        /// The file containing the record.
+        path: PathBuf,
+        /// The line number, starting at 1.
+        line: usize,
+        /// The expected sequence number of the record.
+        expected: u64,
+        /// The sequence number of the record.
+        found: u64,
+    },
+    /// The last records were deleted.
+    Truncated {
+        /// The sequence number of the last record according to the head.
+        expected: u64,
+        /// The sequence number of the last record in the log.
+        found: u64,
+    },
+}
+
+impl fmt::Display for AuditLogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to read the audit log: {e}"),
+            Self::MissingHead => f.write_str("the head of the audit log is missing"),
+            Self::Malformed { path, line } => {
+                write!(f, "{}:{line}: malformed record", path.display())
+            }
+            Self::Modified { path, line } => {
+                write!(f, "{}:{line}: record was modified", path.display())
+            }
+            Self::Discontinuous {
+                path,
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{}:{line}: expected record {expected}, found record {found}",
+                path.display()
+            ),
+            Self::Truncated { expected, found } => write!(
+                f,
+                "the audit log was truncated: the last record is {found} instead of {expected}"
+            ),
+        }
+    }
+}
+
+impl error::Error for AuditLogError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for AuditLogError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Verifies that the hash-chained audit log at `path` was not tampered with,
+/// see [`JsonLinesSink::with_hash_chain`](crate::JsonLinesSink::with_hash_chain).
+///
+/// The files rotated from the log, e.g., `audit.jsonl.1`, are verified too,
+/// from oldest to newest. Verification detects modified, inserted, reordered
+/// and deleted records, including the last ones, and deleted rotated files.
+///
+/// Verification can't detect the log being rolled back as a whole, i.e.,
+/// replaced by a copy of the log and its head file taken earlier.
+pub fn verify_audit_log(
+    path: impl AsRef<Path>,
+    key: &AuditKey,
+) -> std::result::Result<AuditLogSummary, AuditLogError> {
+    let path = path.as_ref();
+    let head = match Head::read(path, key) {
+        Ok(head) => head,
+        Err(HeadError::Missing) => return Err(AuditLogError::MissingHead),
+        Err(HeadError::Modified) => {
+            return Err(AuditLogError::Modified {
+                path: head_path(path),
+                line: 1,
+            })
+        }
+        Err(HeadError::Io(e)) => return Err(AuditLogError::Io(e)),
+    };
+
+    let mut files: Vec<_> = (1..)
+        .map(|index| rotated_path(path, index))
+        .take_while(|path| path.exists())
+        .collect();
+    files.reverse();
+    files.push(path.to_owned());
+
+    let mut expected = head.first;
+    let mut prev = None;
+    let mut records = 0;
+    for file in files {
+        let reader = match File::open(&file) {
+            Ok(file) => BufReader::new(file),
+            // The log itself doesn't exist until the first record.
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line_number = index + 1;
+            let Some(record) = Sealed::parse(&line) else {
+                return Err(AuditLogError::Malformed {
+                    path: file,
+                    line: line_number,
+                });
+            };
+            if !record.verify(key) {
+                return Err(AuditLogError::Modified {
+                    path: file,
+                    line: line_number,
+                });
+            }
+            // The MAC of the record preceding the first retained record is
+            // only known if it is the first record ever.
+            let prev_matches = match prev {
+                Some(prev) => record.prev == prev,
+                None => expected != 1 || record.prev == GENESIS,
+            };
+            if record.seq != expected || !prev_matches {
+                return Err(AuditLogError::Discontinuous {
+                    path: file,
+                    line: line_number,
+                    expected,
+                    found: record.seq,
+                });
+            }
+            if record.seq == head.last && record.mac != head.last_mac {
+                return Err(AuditLogError::Modified {
+                    path: file,
+                    line: line_number,
+                });
+            }
+            prev = Some(record.mac);
+            expected += 1;
+            records += 1;
+        }
+    }
+
+    // Records written after the head was last updated, e.g., because the app
+    // crashed, are authentic, so only missing records are an error.
+    let last = expected - 1;
+    if last < head.last {
+        return Err(AuditLogError::Truncated {
+            expected: head.last,
+            found: last,
+        });
+    }
+    Ok(AuditLogSummary {
+        records,
+        first: head.first,
+        last,
+    })
+}
+
+/// The state of the chain of a [`JsonLinesSink`](crate::JsonLinesSink).
+pub(super) struct Chain {
+    key: AuditKey,
+    head: Head,
+    head_path: PathBuf,
+}
+
+impl Chain {
+    /// Resumes the chain of the log at `path`, or starts a new chain if the
+    /// log is empty.
+    pub(super) fn resume(key: AuditKey, path: &Path) -> io::Result<Self> {
+        let head = match Head::read(path, &key) {
+            Ok(head) => head,
+            Err(HeadError::Missing) => {
+                let empty = fs::metadata(path).map_or(true, |metadata| metadata.len() == 0);
+                if !empty {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "the audit log is not hash-chained",
+                    ));
+                }
+                let head = Head {
+                    first: 1,
+                    last: 0,
+                    last_mac: GENESIS,
+                };
+                // Written now so that deleting all records is detected.
+                head.write(&head_path(path), &key)?;
+                head
+            }
+            Err(HeadError::Modified) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "the head of the audit log was modified",
+                ))
+            }
+            Err(HeadError::Io(e)) => return Err(e),
+        };
+        Ok(Self {
+            key,
+            head,
+            head_path: head_path(path),
+        })
+    }
+
+    /// Appends the record to the file, chained to the previous record.
+    pub(super) fn write(&mut self, file: &mut RotatingFile, json: &str) -> io::Result<()> {
+        let seq = self.head.last + 1;
+        // The record's fields, followed by the chain's.
+        let body = format!(
+            "{},\"seq\":{seq},\"prev\":\"{}\"}}",
+            json.strip_suffix('}').unwrap_or(json),
+            encode(&self.head.last_mac),
+        );
+        let mac = self.key.mac(body.as_bytes()).finalize().into_bytes().into();
+        let line = seal(&body, &mac);
+
+        if file.write(format!("{line}\n").as_bytes())? {
+            self.head.first = match file.max_files {
+                0 => seq,
+                max_files => oldest_seq(&file.path, max_files)?.unwrap_or(seq),
+            };
+        }
+        self.head.last = seq;
+        self.head.last_mac = mac;
+        self.head.write(&self.head_path, &self.key)
+    }
+}
+
+/// The authenticated record of the first and last records of a log.
+struct Head {
+    /// The sequence number of the first retained record.
+    first: u64,
+    /// The sequence number of the last record, or zero if there are none.
+    last: u64,
+    /// The MAC of the last record.
+    last_mac: [u8; LEN],
+}
+
+enum HeadError {
+    Missing,
+    Modified,
+    Io(io::Error),
+}
+
+impl Head {
+    fn read(path: &Path, key: &AuditKey) -> std::result::Result<Self, HeadError> {
+        let line = match fs::read_to_string(head_path(path)) {
+            Ok(line) => line,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(HeadError::Missing),
+            Err(e) => return Err(HeadError::Io(e)),
+        };
+        let (body, mac) = unseal(line.trim_end()).ok_or(HeadError::Modified)?;
+        if key.mac(body.as_bytes()).verify_slice(&mac).is_err() {
+            return Err(HeadError::Modified);
+        }
+        let field = |name: &str| {
+            let start = body.find(&format!("\"{name}\":"))? + name.len() + 3;
+            let end = body[start..].find([',', '}'])? + start;
+            Some(body[start..end].trim_matches('"'))
+        };
+        let parse = || {
+            Some(Self {
+                first: field("first")?.parse().ok()?,
+                last: field("last")?.parse().ok()?,
+                last_mac: decode(field("last_mac")?)?,
+            })
+        };
+        parse().ok_or(HeadError::Modified)
+    }
+
+    /// Replaces the head file atomically.
+    fn write(&self, path: &Path, key: &AuditKey) -> io::Result<()> {
+        let body = format!(
+            "{{\"v\":1,\"first\":{},\"last\":{},\"last_mac\":\"{}\"}}",
+            self.first,
+            self.last,
+            encode(&self.last_mac),
+        );
+        let mac = key.mac(body.as_bytes()).finalize().into_bytes().into();
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        fs::write(&temp, format!("{}\n", seal(&body, &mac)))?;
+        fs::rename(&temp, path)
+    }
+}
+
+/// A parsed hash-chained record.
+struct Sealed {
+    body: String,
+    seq: u64,
+    prev: [u8; LEN],
+    mac: [u8; LEN],
+}
+
+impl Sealed {
+    fn parse(line: &str) -> Option<Self> {
+        let (body, mac) = unseal(line)?;
+        let fields = body.strip_suffix("\"}")?;
+        let (fields, prev) = fields.split_at(fields.len().checked_sub(2 * LEN)?);
+        let fields = fields.strip_suffix(",\"prev\":\"")?;
+        let (_, seq) = fields.rsplit_once(",\"seq\":")?;
+        Some(Self {
+            seq: seq.parse().ok()?,
+            prev: decode(prev)?,
+            mac,
+            body,
+        })
+    }
+
+    fn verify(&self, key: &AuditKey) -> bool {
+        key.mac(self.body.as_bytes())
+            .verify_slice(&self.mac)
+            .is_ok()
+    }
+}
+
+/// Appends the MAC of the JSON object `body` to it as its last member.
+fn seal(body: &str, mac: &[u8; LEN]) -> String {
+    let fields = body.strip_suffix('}').unwrap_or(body);
+    format!("{fields},\"mac\":\"{}\"}}", encode(mac))
+}
+
+/// Splits the line into the JSON object without its MAC, and the MAC.
+fn unseal(line: &str) -> Option<(String, [u8; LEN])> {
+    let fields = line.strip_suffix("\"}")?;
+    let (fields, mac) = fields.split_at(fields.len().checked_sub(2 * LEN)?);
+    let fields = fields.strip_suffix(",\"mac\":\"")?;
+    Some((format!("{fields}}}"), decode(mac)?))
+}
+
+/// Returns the sequence number of the first record of the oldest file rotated
+/// from the log at `path`.
+fn oldest_seq(path: &Path, max_files: usize) -> io::Result<Option<u64>> {
+    let Some(oldest) = (1..=max_files)
+        .rev()
+        .map(|index| rotated_path(path, index))
+        .find(|path| path.exists())
+    else {
+        return Ok(None);
+    };
+    let mut line = String::new();
+    BufReader::new(File::open(oldest)?).read_line(&mut line)?;
+    Ok(Sealed::parse(line.trim_end()).map(|record| record.seq))
+}
+
+/// Returns the path of the head file of the log at `path`.
+fn head_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".head");
+    PathBuf::from(name)
+}
+
+fn encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn decode(hex: &str) -> Option<[u8; LEN]> {
+    if hex.len() != 2 * LEN || !hex.is_ascii() {
+        return None;
+    }
+    let mut bytes = [0; LEN];
+    for (byte, digits) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()?;
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process};
+
+    use super::*;
+    use crate::{AuditEvent, AuditRecord, AuditSink, JsonLinesSink};
+
+    const KEY: AuditKey = AuditKey::from_bytes([7; LEN]);
+
+    /// A log in a temporary directory, removed when dropped.
+    struct Log {
+        dir: PathBuf,
+        path: PathBuf,
+    }
+
+    impl Log {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("robius-audit-chain-{}-{name}", process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            let path = dir.join("audit.jsonl");
+            Self { dir, path }
+        }
+
+        /// Returns a sink appending to the log, keeping `max_files` previous
+        /// files with one record each if given.
+        fn sink(&self, max_files: Option<usize>) -> JsonLinesSink {
+            let mut sink = JsonLinesSink::new(&self.path).unwrap();
+            if let Some(max_files) = max_files {
+                sink = sink.with_rotation(1, max_files);
+            }
+            sink.with_hash_chain(KEY).unwrap()
+        }
+
+        /// Writes `count` records using the sink.
+        fn write(&self, sink: &JsonLinesSink, count: u64) {
+            for request in 1..=count {
+                sink.record(&AuditRecord::new(
+                    request,
+                    AuditEvent::Requested,
+                    Some("export-keys"),
+                ));
+            }
+        }
+
+        fn verify(&self) -> std::result::Result<AuditLogSummary, AuditLogError> {
+            verify_audit_log(&self.path, &KEY)
+        }
+
+        fn rotated(&self, index: usize) -> PathBuf {
+            rotated_path(&self.path, index)
+        }
+
+        fn head(&self) -> PathBuf {
+            head_path(&self.path)
+        }
+
+        fn lines(&self) -> Vec<String> {
+            let log = fs::read_to_string(&self.path).unwrap();
+            log.lines().map(str::to_owned).collect()
+        }
+
+        fn set_lines(&self, lines: &[String]) {
+            fs::write(
+                &self.path,
+                lines
+                    .iter()
+                    .map(|line| format!("{line}\n"))
+                    .collect::<String>(),
+            )
+            .unwrap();
+        }
+    }
+
+    impl Drop for Log {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    /// Returns a log of five records.
+    fn written(name: &str) -> Log {
+        let log = Log::new(name);
+        log.write(&log.sink(None), 5);
+        log
+    }
+
+    fn summary(records: u64, first: u64, last: u64) -> AuditLogSummary {
+        AuditLogSummary {
+            records,
+            first,
+            last,
+        }
+    }
+
+    #[test]
+    fn intact() {
+        let log = written("intact");
+        assert_eq!(log.verify().unwrap(), summary(5, 1, 5));
+
+        // The chain is resumed by a new sink.
+        log.write(&log.sink(None), 2);
+        assert_eq!(log.verify().unwrap(), summary(7, 1, 7));
+
+        let log = Log::new("empty");
+        log.sink(None);
+        assert_eq!(log.verify().unwrap(), summary(0, 1, 0));
+    }
+
+    #[test]
+    fn io_error() {
+        let log = written("io-error");
+        fs::remove_file(log.head()).unwrap();
+        fs::create_dir(log.head()).unwrap();
+        assert!(matches!(log.verify(), Err(AuditLogError::Io(_))));
+    }
+
+    #[test]
+    fn missing_head() {
+        let log = written("missing-head");
+        fs::remove_file(log.head()).unwrap();
+        assert!(matches!(log.verify(), Err(AuditLogError::MissingHead)));
+    }
+
+    #[test]
+    fn key_file() {
+        let log = Log::new("key-file");
+        let path = log.dir.join("audit.key");
+        assert!(AuditKey::load_from_file(&path).unwrap().is_none());
+        assert!(!path.exists());
+
+        let key = AuditKey::from_file(&path).unwrap();
+        assert_eq!(AuditKey::load_from_file(&path).unwrap().unwrap().0, key.0);
+        assert_eq!(AuditKey::from_file(&path).unwrap().0, key.0);
+
+        fs::write(&path, "not a key").unwrap();
+        let error = AuditKey::load_from_file(&path).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn malformed() {
+        let log = written("malformed");
+        let mut lines = log.lines();
+        let half = lines[1].len() / 2;
+        lines[1].truncate(half);
+        log.set_lines(&lines);
+        assert!(matches!(
+            log.verify(),
+            Err(AuditLogError::Malformed { path, line: 2 }) if path == log.path
+        ));
+    }
+
+    #[test]
+    fn modified() {
+        let log = written("modified");
+        let mut lines = log.lines();
+        lines[1] = lines[1].replace("export-keys", "import-keys");
+        log.set_lines(&lines);
+        assert!(matches!(
+            log.verify(),
+            Err(AuditLogError::Modified { path, line: 2 }) if path == log.path
+        ));
+
+        // Authenticated with another key.
+        let log = written("other-key");
+        let other = AuditKey::from_bytes([8; LEN]);
+        assert!(matches!(
+            verify_audit_log(&log.path, &other),
+            Err(AuditLogError::Modified { path, line: 1 }) if path == log.head()
+        ));
+    }
+
+    #[test]
+    fn modified_head() {
+        let log = written("modified-head");
+        let head = fs::read_to_string(log.head()).unwrap();
+        fs::write(log.head(), head.replace("\"last\":5", "\"last\":4")).unwrap();
+        assert!(matches!(
+            log.verify(),
+            Err(AuditLogError::Modified { path, line: 1 }) if path == log.head()
+        ));
+    }
+
+    #[test]
+    fn inserted() {
+        let log = written("inserted");
+        let mut lines = log.lines();
+        lines.insert(2, lines[1].clone());
+        log.set_lines(&lines);
+        assert!(matches!(
+            log.verify(),
+            Err(AuditLogError::Discontinuous {
+                line: 3,
+                expected: 3,
+                found: 2,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn reordered() {
+        let log = written("reordered");
+        let mut lines = log.lines();
+        lines.swap(1, 2);
+        log.set_lines(&lines);
+        assert!(matches!(
+            log.verify(),
+            Err(AuditLogError::Discontinuous {
+                line: 2,
+                expected: 2,
+                found: 3,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn deleted() {
+        let log = written("deleted");
+        let mut lines = log.lines();
+        lines.remove(2);
+        log.set_lines(&lines);
+        assert!(matches!(
+            log.verify(),
+            Err(AuditLogError::Discontinuous {
+                line: 3,
+                expected: 3,
+                found: 4,
+                ..
+            })
+        ));
+
+        let log = written("deleted-first");
+        let mut lines = log.lines();
+        lines.remove(0);
+        log.set_lines(&lines);
+        assert!(matches!(
+            log.verify(),
+            Err(AuditLogError::Discontinuous {
+                line: 1,
+                expected: 1,
+                found: 2,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn truncated() {
+        let log = written("truncated");
+        let lines = log.lines();
+        log.set_lines(&lines[..3]);
+        assert!(matches!(
+            log.verify(),
+            Err(AuditLogError::Truncated {
+                expected: 5,
+                found: 3,
+            })
+        ));
+
+        let log = written("emptied");
+        log.set_lines(&[]);
+        assert!(matches!(
+            log.verify(),
+            Err(AuditLogError::Truncated {
+                expected: 5,
+                found: 0,
+            })
+        ));
+    }
+
+    #[test]
+    fn rotated() {
+        let log = Log::new("rotated");
+        log.write(&log.sink(Some(3)), 5);
+        // The first record was removed by rotation.
+        assert!(log.rotated(3).exists() && !log.rotated(4).exists());
+        assert_eq!(log.verify().unwrap(), summary(4, 2, 5));
+    }
+
+    #[test]
+    fn deleted_rotated_file() {
+        let log = Log::new("deleted-oldest");
+        log.write(&log.sink(Some(3)), 5);
+        fs::remove_file(log.rotated(3)).unwrap();
+        assert!(matches!(
+            log.verify(),
+            Err(AuditLogError::Discontinuous {
+                path,
+                line: 1,
+                expected: 2,
+                found: 3,
+            }) if path == log.rotated(2)
+        ));
+
+        let log = Log::new("deleted-middle");
+        log.write(&log.sink(Some(3)), 5);
+        fs::remove_file(log.rotated(1)).unwrap();
+        assert!(matches!(
+            log.verify(),
+            Err(AuditLogError::Discontinuous {
+                path,
+                line: 1,
+                expected: 2,
+                found: 5,
+            }) if path == log.path
+        ));
+    }
+
+    #[test]
+    fn head_rollback() {
+        // The records written after the head are accepted, as if the app
+        // crashed before updating it, so no record is lost.
+        let log = Log::new("head-rollback");
+        let sink = log.sink(None);
+        log.write(&sink, 3);
+        let head = fs::read(log.head()).unwrap();
+        log.write(&sink, 2);
+        fs::write(log.head(), &head).unwrap();
+        assert_eq!(log.verify().unwrap(), summary(5, 1, 5));
+
+        // The head records the first retained record, so rolling it back
+        // across a rotation is detected.
+        let log = Log::new("head-rollback-rotated");
+        let sink = log.sink(Some(1));
+        log.write(&sink, 2);
+        let head = fs::read(log.head()).unwrap();
+        log.write(&sink, 2);
+        fs::write(log.head(), &head).unwrap();
+        assert!(matches!(
+            log.verify(),
+            Err(AuditLogError::Discontinuous {
+                expected: 1,
+                found: 3,
+                ..
+            })
+        ));
+
+        // Rolling back the log and its head together is not detected, as
+        // documented by `verify_audit_log`.
+        let log = Log::new("rollback");
+        let sink = log.sink(None);
+        log.write(&sink, 3);
+        let (head, lines) = (fs::read(log.head()).unwrap(), log.lines());
+        log.write(&sink, 2);
+        fs::write(log.head(), &head).unwrap();
+        log.set_lines(&lines);
+        assert_eq!(log.verify().unwrap(), summary(3, 1, 3));
+    }
+}
@@ -0,0 +1,76 @@
+//! Verifies that a hash-chained audit log was not tampered with.
+//!
+//! ```text
+//! verify-audit-log <LOG> --key-file <PATH>
+//! verify-audit-log <LOG> --keyring <SERVICE> <ACCOUNT>
+//! ```
+//!
+//! Exits with status 0 if the log is intact, 1 if it was tampered with, and 2
+//! if the arguments are invalid, the key could not be loaded or the log could
+//! not be read.
+
+use std::{env, process::ExitCode};
+
+use robius_authentication::{verify_audit_log, AuditKey, AuditLogError};
+
+const USAGE: &str = "\
+usage: verify-audit-log <LOG> --key-file <PATH>
+       verify-audit-log <LOG> --keyring <SERVICE> <ACCOUNT>";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let (log, key) = match args[..] {
+        [log, "--key-file", path] => (
+            log,
+            match AuditKey::load_from_file(path) {
+                Ok(Some(key)) => Ok(key),
+                Ok(None) => Err(format!("the key file {path} doesn't exist")),
+                Err(e) => Err(e.to_string()),
+            },
+        ),
+        [log, "--keyring", service, account] => (
+            log,
+            match AuditKey::load_from_keyring(service, account) {
+                Ok(Some(key)) => Ok(key),
+                Ok(None) => Err(format!("the keyring has no key for {account} ({service})")),
+                Err(e) => Err(e.to_string()),
+            },
+        ),
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    let key = match key {
+        Ok(key) => key,
+        Err(e) => {
+            eprintln!("error: failed to load the key: {e}");
+            return ExitCode::from(2);
+        }
+    };
+
+    match verify_audit_log(log, &key) {
+        Ok(summary) if summary.records == 0 => {
+            println!("ok: no records");
+            ExitCode::SUCCESS
+        }
+        Ok(summary) => {
+            println!(
+                "ok: {} records, sequence numbers {} to {}",
+                summary.records, summary.first, summary.last
+            );
+            ExitCode::SUCCESS
+        }
+        // The log may be intact, e.g., if it is not readable by the user.
+        Err(e @ AuditLogError::Io(_)) => {
+            eprintln!("error: {e}");
+            ExitCode::from(2)
+        }
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
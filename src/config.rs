@@ -0,0 +1,630 @@
+//! Serialization of policies and prompt text, e.g., from configuration files.
+//!
+//! The serialized forms are defined by private representations, so that the
+//! schemas are independent of the layout of the public types.
+
+use std::{borrow::Cow, fmt, marker::PhantomData, time::Duration};
+
+use serde::{
+    de::{self, MapAccess, Visitor},
+    ser::SerializeMap,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{
+    Action, ActionRegistry, BiometricStrength, Identity, LinuxBackend, LinuxDetail, Policy,
+    PolicyBuilder, TextBuf,
+};
+
+/// The [JSON Schema] of serialized [`Policy`] and [`PolicyBuilder`] values.
+///
+/// A policy is an object with the following fields, all of which are
+/// optional and default to the values of [`PolicyBuilder::new`]:
+/// - `biometrics`: `"strong"`, `"weak"` or `"disabled"`, see
+///   [`PolicyBuilder::biometrics`].
+/// - `password`, `companion` and `wrist_detection`: booleans, see the
+///   corresponding methods of [`PolicyBuilder`].
+/// - `linux_backend`: `{ "polkit": { "action_id": "..." } }` or
+///   `{ "pam": { "service": "..." } }`, see [`LinuxBackend`].
+/// - `identity`: `"current_user"`, `"administrator"` or
+///   `{ "user": "..." }`, see [`Identity`].
+///
+/// Unknown fields are rejected. Deserializing a [`Policy`] validates it like
+/// [`PolicyBuilder::try_build`], failing if it is not valid for the current
+/// target.
+///
+/// ```
+/// use robius_authentication::{Policy, PolicyBuilder};
+///
+/// let policy: Policy = serde_json::from_str(
+///     r#"{
+///         "biometrics": "disabled",
+///         "linux_backend": { "polkit": { "action_id": "com.example.app.unlock" } }
+///     }"#,
+/// )
+/// .unwrap();
+///
+/// // A policy that allows no authentication method is invalid.
+/// let json = r#"{ "biometrics": "disabled", "password": false }"#;
+/// assert!(serde_json::from_str::<Policy>(json).is_err());
+///
+/// let builder: PolicyBuilder =
+///     serde_json::from_str(r#"{ "identity": { "user": "admin" } }"#).unwrap();
+/// let json = serde_json::to_string(&builder).unwrap();
+/// assert_eq!(serde_json::from_str::<PolicyBuilder>(&json).unwrap(), builder);
+/// ```
+///
+/// [JSON Schema]: https://json-schema.org/
+pub const POLICY_JSON_SCHEMA: &str = include_str!("../schema/policy.schema.json");
+
+/// The [JSON Schema] of serialized [`TextBuf`] values.
+///
+/// The text is an object with a field for each platform, mirroring [`Text`]:
+/// - `android`: `{ "title": "...", "subtitle": "...", "description": "..." }`
+/// - `apple`: a string
+/// - `linux`: `{ "message": "...", "icon_name": "...", "gettext_domain": "...",
+///   "details": { "key": "value", ... } }`
+/// - `windows`: `{ "title": "...", "description": "..." }`
+///
+/// All fields are optional; missing strings are empty. Unknown fields are
+/// rejected. The text is not validated, see [`Text::validate`].
+///
+/// ```
+/// use robius_authentication::TextBuf;
+///
+/// let text: TextBuf = serde_json::from_str(
+///     r#"{
+///         "apple": "unlock your vault",
+///         "linux": { "message": "Authenticate to unlock your vault" }
+///     }"#,
+/// )
+/// .unwrap();
+/// let json = serde_json::to_string(&text).unwrap();
+/// assert_eq!(serde_json::from_str::<TextBuf>(&json).unwrap(), text);
+/// ```
+///
+/// [JSON Schema]: https://json-schema.org/
+/// [`Text`]: crate::Text
+/// [`Text::validate`]: crate::Text::validate
+pub const TEXT_JSON_SCHEMA: &str = include_str!("../schema/text.schema.json");
+
+/// The [JSON Schema] of serialized [`ActionRegistry`] values.
+///
+/// The registry is an object mapping the id of each action to an object with
+/// the following fields:
+/// - `text`: the text of the prompt, see [`TEXT_JSON_SCHEMA`].
+/// - `policy`: optional, the policy, see [`POLICY_JSON_SCHEMA`].
+/// - `reuse_window_secs`: optional, the reuse window in whole seconds, see
+///   [`Action::with_reuse_window`].
+/// - `audit_label`: optional, see [`Action::with_audit_label`].
+///
+/// The schema refers to the other schemas relative to its `$id`. Unknown
+/// fields are rejected, and deserializing fails if the policy of an action is
+/// not valid for the current target, see [`ActionRegistry::register`].
+///
+/// ```
+/// use robius_authentication::ActionRegistry;
+///
+/// let actions: ActionRegistry = serde_json::from_str(
+///     r#"{
+///         "export-keys": {
+///             "text": { "linux": { "message": "Authenticate to export your keys" } },
+///             "policy": { "biometrics": "disabled" },
+///             "reuse_window_secs": 60,
+///             "audit_label": "Key export"
+///         }
+///     }"#,
+/// )
+/// .unwrap();
+/// assert_eq!(actions.get("export-keys").unwrap().audit_label(), Some("Key export"));
+/// ```
+///
+/// [JSON Schema]: https://json-schema.org/
+pub const ACTIONS_JSON_SCHEMA: &str = include_str!("../schema/actions.schema.json");
+
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct PolicyRepr {
+    biometrics: BiometricsRepr,
+    password: bool,
+    companion: bool,
+    wrist_detection: bool,
+    linux_backend: LinuxBackend,
+    identity: Identity,
+}
+
+impl Default for PolicyRepr {
+    fn default() -> Self {
+        PolicyBuilder::new().into()
+    }
+}
+
+impl From<PolicyBuilder> for PolicyRepr {
+    fn from(builder: PolicyBuilder) -> Self {
+        Self {
+            biometrics: match builder.biometrics {
+                Some(BiometricStrength::Strong) => BiometricsRepr::Strong,
+                Some(BiometricStrength::Weak) => BiometricsRepr::Weak,
+                None => BiometricsRepr::Disabled,
+            },
+            password: builder.password,
+            companion: builder.companion,
+            wrist_detection: builder.wrist_detection,
+            linux_backend: builder.linux_backend,
+            identity: builder.identity,
+        }
+    }
+}
+
+impl From<PolicyRepr> for PolicyBuilder {
+    fn from(repr: PolicyRepr) -> Self {
+        PolicyBuilder::new()
+            .biometrics(match repr.biometrics {
+                BiometricsRepr::Strong => Some(BiometricStrength::Strong),
+                BiometricsRepr::Weak => Some(BiometricStrength::Weak),
+                BiometricsRepr::Disabled => None,
+            })
+            .password(repr.password)
+            .companion(repr.companion)
+            .wrist_detection(repr.wrist_detection)
+            .linux_backend(repr.linux_backend)
+            .identity(repr.identity)
+    }
+}
+
+/// Unlike `Option<BiometricStrength>`, this can be represented in TOML.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum BiometricsRepr {
+    Strong,
+    Weak,
+    Disabled,
+}
+
+impl Serialize for PolicyBuilder {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        PolicyRepr::from(self.clone()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for PolicyBuilder {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        PolicyRepr::deserialize(deserializer).map(Self::from)
+    }
+}
+
+impl Serialize for Policy {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.builder.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Policy {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        PolicyBuilder::deserialize(deserializer)?
+            .try_build_ref()
+            .map_err(de::Error::custom)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum LinuxBackendRepr {
+    Polkit { action_id: Cow<'static, str> },
+    Pam { service: Cow<'static, str> },
+}
+
+impl Serialize for LinuxBackend {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Polkit { action_id } => LinuxBackendRepr::Polkit {
+                action_id: action_id.clone(),
+            },
+            Self::Pam { service } => LinuxBackendRepr::Pam {
+                service: service.clone(),
+            },
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for LinuxBackend {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match LinuxBackendRepr::deserialize(deserializer)? {
+            LinuxBackendRepr::Polkit { action_id } => Self::Polkit { action_id },
+            LinuxBackendRepr::Pam { service } => Self::Pam { service },
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum IdentityRepr {
+    CurrentUser,
+    Administrator,
+    User(Cow<'static, str>),
+}
+
+impl Serialize for Identity {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::CurrentUser => IdentityRepr::CurrentUser,
+            Self::Administrator => IdentityRepr::Administrator,
+            Self::User(name) => IdentityRepr::User(name.clone()),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Identity {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match IdentityRepr::deserialize(deserializer)? {
+            IdentityRepr::CurrentUser => Self::CurrentUser,
+            IdentityRepr::Administrator => Self::Administrator,
+            IdentityRepr::User(name) => Self::User(name),
+        })
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct TextRepr {
+    android: AndroidTextRepr,
+    apple: String,
+    linux: LinuxTextRepr,
+    windows: WindowsTextRepr,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct AndroidTextRepr {
+    title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    subtitle: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct LinuxTextRepr {
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    icon_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gettext_domain: Option<String>,
+    #[serde(skip_serializing_if = "Details::is_empty")]
+    details: Details,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct WindowsTextRepr {
+    title: String,
+    description: String,
+}
+
+/// The Linux details, represented as a map in their original order.
+#[derive(Default)]
+struct Details(Vec<LinuxDetail<'static>>);
+
+impl Details {
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Serialize for Details {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for detail in &self.0 {
+            map.serialize_entry(&detail.key, &detail.value)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Details {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct DetailsVisitor(PhantomData<Details>);
+
+        impl<'de> Visitor<'de> for DetailsVisitor {
+            type Value = Details;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a map of strings")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Details, A::Error> {
+                let mut details = Vec::with_capacity(map.size_hint().unwrap_or(0));
+                while let Some((key, value)) = map.next_entry::<String, String>()? {
+                    details.push(LinuxDetail {
+                        key: Cow::Owned(key),
+                        value: Cow::Owned(value),
+                    });
+                }
+                Ok(Details(details))
+            }
+        }
+
+        deserializer.deserialize_map(DetailsVisitor(PhantomData))
+    }
+}
+
+impl Serialize for TextBuf {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        TextRepr {
+            android: AndroidTextRepr {
+                title: self.android_title.clone(),
+                subtitle: self.android_subtitle.clone(),
+                description: self.android_description.clone(),
+            },
+            apple: self.apple.clone(),
+            linux: LinuxTextRepr {
+                message: self.linux_message.clone(),
+                icon_name: self.linux_icon_name.clone(),
+                gettext_domain: self.linux_gettext_domain.clone(),
+                details: Details(self.linux_details.clone()),
+            },
+            windows: WindowsTextRepr {
+                title: self.windows_title.clone(),
+                description: self.windows_description.clone(),
+            },
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for TextBuf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let TextRepr {
+            android,
+            apple,
+            linux,
+            windows,
+        } = TextRepr::deserialize(deserializer)?;
+        Ok(Self {
+            android_title: android.title,
+            android_subtitle: android.subtitle,
+            android_description: android.description,
+            apple,
+            linux_message: linux.message,
+            linux_icon_name: linux.icon_name,
+            linux_gettext_domain: linux.gettext_domain,
+            linux_details: linux.details.0,
+            windows_title: windows.title,
+            windows_description: windows.description,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ActionRepr {
+    text: TextBuf,
+    #[serde(default)]
+    policy: PolicyBuilder,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reuse_window_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    audit_label: Option<String>,
+}
+
+impl Serialize for Action {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ActionRepr {
+            text: self.text.clone(),
+            policy: self.policy.clone(),
+            reuse_window_secs: self.reuse_window.map(|window| window.as_secs()),
+            audit_label: self.audit_label.clone(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Action {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let ActionRepr {
+            text,
+            policy,
+            reuse_window_secs,
+            audit_label,
+        } = ActionRepr::deserialize(deserializer)?;
+        Ok(Self {
+            text,
+            policy,
+            reuse_window: reuse_window_secs.map(Duration::from_secs),
+            audit_label,
+        })
+    }
+}
+
+impl Serialize for ActionRegistry {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.iter())
+    }
+}
+
+impl<'de> Deserialize<'de> for ActionRegistry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct RegistryVisitor;
+
+        impl<'de> Visitor<'de> for RegistryVisitor {
+            type Value = ActionRegistry;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a map of actions")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<ActionRegistry, A::Error> {
+                let mut registry = ActionRegistry::new();
+                while let Some((id, action)) = map.next_entry::<String, Action>()? {
+                    if registry.get(&id).is_some() {
+                        return Err(de::Error::custom(format_args!("duplicate action `{id}`")));
+                    }
+                    registry.register(id.as_str(), action).map_err(|e| {
+                        de::Error::custom(format_args!("invalid policy of action `{id}`: {e}"))
+                    })?;
+                }
+                Ok(registry)
+            }
+        }
+
+        deserializer.deserialize_map(RegistryVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonschema::{Resource, Validator};
+    use serde_json::Value;
+
+    use super::*;
+
+    /// Returns a validator for `schema`, resolving the references between
+    /// the published schemas without fetching them.
+    fn validator(schema: &str) -> Validator {
+        let parse = |schema| serde_json::from_str::<Value>(schema).unwrap();
+        let resources = [POLICY_JSON_SCHEMA, TEXT_JSON_SCHEMA].map(|schema| {
+            let schema = parse(schema);
+            let id = schema["$id"].as_str().unwrap().to_owned();
+            (id, Resource::from_contents(schema))
+        });
+        jsonschema::options()
+            .with_resources(resources.into_iter())
+            .build(&parse(schema))
+            .unwrap()
+    }
+
+    /// Serializes `value`, checks that the output is valid according to
+    /// `schema`, and returns it deserialized again.
+    fn round_trip<T: Serialize + for<'de> Deserialize<'de>>(value: &T, schema: &str) -> T {
+        // The output is deserialized from a string, because a `Value` doesn't
+        // keep the order of the Linux details.
+        let json = serde_json::to_string(value).unwrap();
+        let errors: Vec<_> = validator(schema)
+            .iter_errors(&serde_json::from_str(&json).unwrap())
+            .map(|e| e.to_string())
+            .collect();
+        assert!(errors.is_empty(), "{json} is invalid: {errors:?}");
+        serde_json::from_str(&json).unwrap()
+    }
+
+    fn builders() -> Vec<PolicyBuilder> {
+        vec![
+            PolicyBuilder::new(),
+            PolicyBuilder::new()
+                .biometrics(Some(BiometricStrength::Weak))
+                .companion(false)
+                .wrist_detection(false)
+                .linux_backend(LinuxBackend::Polkit {
+                    action_id: "com.example.app.unlock".into(),
+                })
+                .identity(Identity::Administrator),
+            PolicyBuilder::new()
+                .biometrics(None)
+                .linux_backend(LinuxBackend::Pam {
+                    service: "example".into(),
+                })
+                .identity(Identity::User("admin".into())),
+        ]
+    }
+
+    fn texts() -> Vec<TextBuf> {
+        vec![
+            TextBuf::new(),
+            TextBuf::new()
+                .android_title("Unlock")
+                .android_subtitle("Example")
+                .android_description("Unlock the vault")
+                .apple("unlock the vault")
+                .linux_message("Authentication is required to unlock the vault")
+                .linux_icon_name("example")
+                .linux_gettext_domain("example")
+                .linux_detail("vault", "personal")
+                .linux_detail("device", "laptop")
+                .windows("Unlock", "Unlock the vault"),
+        ]
+    }
+
+    #[test]
+    fn policy_builder() {
+        for builder in builders() {
+            assert_eq!(round_trip(&builder, POLICY_JSON_SCHEMA), builder);
+        }
+        let invalid = serde_json::json!({ "identity": { "group": "wheel" } });
+        assert!(!validator(POLICY_JSON_SCHEMA).is_valid(&invalid));
+    }
+
+    #[test]
+    fn policy() {
+        for builder in builders() {
+            let Ok(policy) = builder.try_build() else {
+                continue;
+            };
+            assert_eq!(
+                round_trip(&policy, POLICY_JSON_SCHEMA).builder,
+                policy.builder
+            );
+        }
+    }
+
+    #[test]
+    fn policy_defaults() {
+        let schema: Value = serde_json::from_str(POLICY_JSON_SCHEMA).unwrap();
+        let defaults = serde_json::to_value(PolicyBuilder::new()).unwrap();
+        let properties = schema["properties"].as_object().unwrap();
+        assert_eq!(
+            properties.keys().collect::<Vec<_>>(),
+            defaults.as_object().unwrap().keys().collect::<Vec<_>>(),
+        );
+        for (name, property) in properties {
+            assert_eq!(property["default"], defaults[name], "{name}");
+        }
+    }
+
+    #[test]
+    fn linux_backend() {
+        for backend in builders().into_iter().map(|builder| builder.linux_backend) {
+            let json = serde_json::to_value(&backend).unwrap();
+            let policy = serde_json::json!({ "linux_backend": json });
+            assert!(validator(POLICY_JSON_SCHEMA).is_valid(&policy), "{json}");
+            assert_eq!(
+                serde_json::from_value::<LinuxBackend>(json).unwrap(),
+                backend
+            );
+        }
+    }
+
+    #[test]
+    fn text() {
+        for text in texts() {
+            assert_eq!(round_trip(&text, TEXT_JSON_SCHEMA), text);
+        }
+    }
+
+    #[test]
+    fn text_details_keep_their_order() {
+        let text = TextBuf::new().linux_detail("b", "1").linux_detail("a", "2");
+        let json = serde_json::to_string(&text).unwrap();
+        assert!(json.find(r#""b":"1""#) < json.find(r#""a":"2""#), "{json}");
+        assert_eq!(serde_json::from_str::<TextBuf>(&json).unwrap(), text);
+    }
+
+    #[test]
+    fn actions() {
+        let mut registry = ActionRegistry::new();
+        for (i, (text, builder)) in texts().into_iter().zip(builders()).enumerate() {
+            let action = Action::new(text)
+                .with_policy(builder)
+                .with_reuse_window(Some(Duration::from_secs(60)))
+                .with_audit_label(format!("label-{i}"));
+            registry.register(format!("action-{i}"), action).unwrap();
+        }
+        let round_tripped = round_trip(&registry, ACTIONS_JSON_SCHEMA);
+        assert_eq!(
+            round_tripped.iter().collect::<Vec<_>>(),
+            registry.iter().collect::<Vec<_>>()
+        );
+    }
+}
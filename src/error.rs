@@ -1,8 +1,14 @@
+use std::fmt;
+
 /// The result of an authentication operation.
 pub type Result<T> = std::result::Result<T, Error>;
 
 /// An error produced during authentication.
+///
+/// New variants may be added in minor releases, so matches must include a
+/// wildcard arm.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     // TODO: Reexport jni::errors::Error
     // TODO: Remove target cfg
@@ -19,19 +25,26 @@ pub enum Error {
     Unavailable,
     /// The user canceled authentication.
     UserCanceled,
+    /// No action is registered with the given id, see
+    /// [`Context::authenticate_action`](crate::Context::authenticate_action).
+    UnknownAction,
 
     // Apple-specific errors
     /// The app canceled authentication.
     ///
     /// This error can occur on:
     /// - [Apple]
+    /// - [Linux] (polkit), see `Context::cancel`
     ///
     /// [Apple]: https://developer.apple.com/documentation/localauthentication/laerror/laerrorappcancel
+    /// [Linux]: https://www.freedesktop.org/software/polkit/docs/latest/eggdbus-interface-org.freedesktop.PolicyKit1.Authority.html#eggdbus-method-org.freedesktop.PolicyKit1.Authority.CancelCheckAuthorization
     AppCanceled,
     /// The system canceled authentication.
     ///
     /// This error can occur on:
     /// - [Apple]
+    /// - any target, if an [`AppLock`](crate::AppLock) was locked while
+    ///   prompting to unlock it
     ///
     /// [Apple]: https://developer.apple.com/documentation/localauthentication/laerror/laerrorsystemcancel
     SystemCanceled,
@@ -40,8 +53,10 @@ pub enum Error {
     ///
     /// This error can occur on:
     /// - [Apple]
+    /// - [Linux] (fprintd), if the fingerprint reader was disconnected
     ///
     /// [Apple]: https://developer.apple.com/documentation/localauthentication/laerror/laerrorbiometrydisconnected
+    /// [Linux]: https://fprint.freedesktop.org/fprintd-dev/Device.html
     BiometryDisconnected,
     /// The device supports biometry only using a removable accessory, but no
     /// accessory is paired.
@@ -55,15 +70,22 @@ pub enum Error {
     ///
     /// This error can occur on:
     /// - [Apple]
+    /// - [Linux] (fprintd)
     ///
     /// [Apple]: https://developer.apple.com/documentation/localauthentication/laerror/laerrorbiometrynotenrolled
+    /// [Linux]: https://fprint.freedesktop.org/fprintd-dev/Device.html
     NotEnrolled,
     /// Displaying the required authentication user interface is forbidden.
     ///
     /// This error can occur on:
     /// - [Apple]
+    /// - [Linux], if the session is locked or in the background, or if there
+    ///   is no way to prompt the user, e.g., over SSH without a terminal, or
+    ///   outside of a login session (e.g., in a cron job or a system service)
+    ///   without a conversation handler
     ///
     /// [Apple]: https://developer.apple.com/documentation/localauthentication/laerror/laerrornotinteractive
+    /// [Linux]: https://www.freedesktop.org/software/systemd/man/latest/org.freedesktop.login1.html
     NotInteractive,
     /// An attempt to authenticate with an Apple companion device (e.g., Apple Watch) failed.
     ///
@@ -100,6 +122,13 @@ pub enum Error {
 
     // Android-specific errors
     UpdateRequired,
+    /// The user didn't respond in time.
+    ///
+    /// This error can occur on:
+    /// - Android
+    /// - [Linux] (fprintd), if the fingerprint reader wasn't touched
+    ///
+    /// [Linux]: https://fprint.freedesktop.org/fprintd-dev/Device.html
     Timeout,
 
     // Windows-specific errors
@@ -107,8 +136,12 @@ pub enum Error {
     /// unavailable.
     ///
     /// This error can occur on:
+    /// - [Linux] (fprintd), if another process is using the fingerprint reader
     /// - [Windows]
+    /// - any target, if an [`AppLock`](crate::AppLock) is already prompting to
+    ///   be unlocked
     ///
+    /// [Linux]: https://fprint.freedesktop.org/fprintd-dev/Device.html
     /// [Windows]: https://learn.microsoft.com/en-us/uwp/api/windows.security.credentials.ui.userconsentverificationresult
     Busy,
     /// Group policy has disabled the biometric verifier device.
@@ -126,13 +159,120 @@ pub enum Error {
     /// [Windows]: https://learn.microsoft.com/en-us/uwp/api/windows.security.credentials.ui.userconsentverificationresult
     NotConfigured,
 
+    // Linux-specific errors
+    /// The app is not permitted to make the request.
+    ///
+    /// This error can occur on:
+    /// - [Linux] (polkit), e.g., if the subject whose authorization is
+    ///   checked belongs to another user
+    ///
+    /// [Linux]: https://www.freedesktop.org/software/polkit/docs/latest/eggdbus-interface-org.freedesktop.PolicyKit1.Authority.html#eggdbus-errordomain-org.freedesktop.PolicyKit1.Error
+    PermissionDenied,
+    /// The user is not known to the authentication service.
+    ///
+    /// This error can occur on:
+    /// - [Linux] (PAM)
+    ///
+    /// [Linux]: https://man7.org/linux/man-pages/man3/pam_authenticate.3.html
+    UserUnknown,
+    /// The user's account has expired.
+    ///
+    /// This error can occur on:
+    /// - [Linux] (PAM)
+    ///
+    /// [Linux]: https://man7.org/linux/man-pages/man3/pam_acct_mgmt.3.html
+    AccountExpired,
+    /// The user's password has expired and must be changed before the user
+    /// can authenticate, e.g., using `passwd`.
+    ///
+    /// This error can occur on:
+    /// - [Linux] (PAM)
+    ///
+    /// [Linux]: https://man7.org/linux/man-pages/man3/pam_acct_mgmt.3.html
+    PasswordExpired,
+
     /// An unknown error occurred.
     Unknown,
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            #[cfg(target_os = "android")]
+            Self::Java(e) => return write!(f, "Java error: {e}"),
+            Self::Authentication => "the user failed to provide valid credentials",
+            Self::Exhausted => "too many failed authentication attempts",
+            Self::Unavailable => "the authentication method is unavailable",
+            Self::UserCanceled => "the user canceled authentication",
+            Self::UnknownAction => "no action is registered with the given id",
+            Self::AppCanceled => "the app canceled authentication",
+            Self::SystemCanceled => "the system canceled authentication",
+            Self::BiometryDisconnected => "the biometric accessory is not connected",
+            Self::NotPaired => "no biometric accessory is paired",
+            Self::NotEnrolled => "the user has no enrolled biometric identities",
+            Self::NotInteractive => "the user can't be prompted to authenticate",
+            Self::CompanionNotAvailable => "the companion device is not available",
+            Self::InvalidDimensions => "invalid dimensions",
+            Self::PasscodeNotSet => "no passcode is set on the device",
+            Self::UserFallback => "the user chose the fallback to a password",
+            Self::UpdateRequired => "a security update is required",
+            Self::Timeout => "authentication timed out",
+            Self::Busy => "the biometric verifier device is busy",
+            Self::DisabledByPolicy => "the biometric verifier device is disabled by policy",
+            Self::NotConfigured => "no biometric verifier device is configured",
+            Self::PermissionDenied => "the app is not permitted to make the request",
+            Self::UserUnknown => "the user is not known",
+            Self::AccountExpired => "the user's account has expired",
+            Self::PasswordExpired => "the user's password has expired",
+            Self::Unknown => "an unknown error occurred",
+        })
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            #[cfg(target_os = "android")]
+            Self::Java(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
 #[cfg(target_os = "android")]
 impl From<jni::errors::Error> for Error {
     fn from(value: jni::errors::Error) -> Self {
         Self::Java(value)
     }
 }
+
+/// The reason a [`PolicyBuilder`](crate::PolicyBuilder) could not build a
+/// policy, see [`PolicyBuilder::try_build`](crate::PolicyBuilder::try_build).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PolicyError {
+    /// The combination of authentication methods is not supported on the
+    /// current target.
+    UnsupportedMethods,
+    /// Authenticating as an identity other than the current user is not
+    /// supported on the current target or by the selected backend.
+    UnsupportedIdentity,
+    /// The selected Linux backend is not available, e.g., PAM without the
+    /// `pam` feature.
+    UnsupportedBackend,
+}
+
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::UnsupportedMethods => {
+                "the authentication methods are not supported on this target"
+            }
+            Self::UnsupportedIdentity => {
+                "authenticating as another identity is not supported on this target"
+            }
+            Self::UnsupportedBackend => "the Linux backend is not available",
+        })
+    }
+}
+
+impl std::error::Error for PolicyError {}
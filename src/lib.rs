@@ -6,15 +6,18 @@
 //!   - Requires the `USE_BIOMETRIC` permission in your app's manifest.
 //! - Windows: Windows Hello (face recognition, fingerprint, PIN), plus
 //!   winrt-based fallback for username/password.
-//! - Linux: [`polkit`]-based authentication using the desktop environment's
-//!   prompt.
+//! - Linux: fingerprint authentication using [fprintd], falling back to
+//!   [`polkit`]-based authentication using the desktop environment's prompt,
+//!   or [PAM] for systems without a polkit agent (requires the `pam` feature).
+//!   See [`LinuxBackend`] for more details.
 //!   - **Note: Linux support is currently incomplete.**
 //!
 //! # Example
 //!
 //! ```no_run
 //! use robius_authentication::{
-//!     AndroidText, BiometricStrength, Context, Policy, PolicyBuilder, Text, WindowsText,
+//!     AndroidText, BiometricStrength, Context, LinuxText, Policy, PolicyBuilder, Text,
+//!     WindowsText,
 //! };
 //!
 //! let policy: Policy = PolicyBuilder::new()
@@ -31,13 +34,14 @@
 //!         description: None,
 //!     },
 //!     apple: "authenticate",
+//!     linux: LinuxText::new("Description"),
 //!     windows: WindowsText::new_truncated("Title", "Description"),
 //! };
 //!
 //! let callback = |auth_result| {
 //!     match auth_result {
-//!         Ok(_)  => log::info!("Authentication success!"),
-//!         Err(_) => log::error!(Authentication failed!"),
+//!         Ok(_)  => println!("Authentication success!"),
+//!         Err(_) => eprintln!("Authentication failed!"),
 //!     }
 //! };
 //!
@@ -49,10 +53,8 @@
 //! The `Policy` and `Text` structs can also be constructed at compile-time to
 //! avoid run-time unwraps:
 //! ```
-//! #![feature(const_option)]
-//!
 //! use robius_authentication::{
-//!     AndroidText, BiometricStrength, Policy, PolicyBuilder, Text, WindowsText,
+//!     AndroidText, BiometricStrength, LinuxText, Policy, PolicyBuilder, Text, WindowsText,
 //! };
 //!
 //! const POLICY: Policy = PolicyBuilder::new()
@@ -69,12 +71,30 @@
 //!         description: None,
 //!     },
 //!     apple: "authenticate",
+//!     linux: LinuxText::new("Description"),
 //!     windows: WindowsText::new("Title", "Description").unwrap(),
 //! };
 //! ```
 //!
 //! For more details about the prompt text see the [`Text`] struct
-//! which allows you to customize the prompt for each platform.
+//! which allows you to customize the prompt for each platform,
+//! and its owned counterpart [`TextBuf`] for prompts built at run-time.
+//! Most prompts can simply be derived from a single reason:
+//! ```
+//! use robius_authentication::Text;
+//!
+//! let text = Text::from_reason("unlock your vault").unwrap();
+//! ```
+//! The [`text!`] macro does the same at compile-time, checking the text
+//! against the constraints of every platform on any host:
+//! ```
+//! use robius_authentication::{text, Text};
+//!
+//! const TEXT: Text = text! {
+//!     title: "Unlock vault",
+//!     reason: "unlock your vault",
+//! };
+//! ```
 //!
 //! ## Usage on Android
 //!
@@ -85,14 +105,48 @@
 //! ```
 //!
 //! [`polkit`]: https://www.freedesktop.org/software/polkit/docs/latest/polkit.8.html
+//! [PAM]: https://man7.org/linux/man-pages/man8/pam.8.html
+//! [fprintd]: https://fprint.freedesktop.org/
 
+mod app_lock;
+mod audit;
+#[cfg(feature = "serde")]
+mod config;
 mod error;
+#[cfg(target_os = "linux")]
+pub mod linux;
+mod localization;
+mod registry;
 mod sys;
 mod text;
 
+use std::borrow::Cow;
+
+#[cfg(feature = "audit-chain")]
+pub use crate::audit::{verify_audit_log, AuditKey, AuditLogError, AuditLogSummary};
+#[cfg(feature = "serde")]
+pub use crate::config::{ACTIONS_JSON_SCHEMA, POLICY_JSON_SCHEMA, TEXT_JSON_SCHEMA};
+
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::text::{ConstText, DEFAULT_TITLE};
+}
+
 pub use crate::{
-    error::{Error, Result},
-    text::{AndroidText, Text, WindowsText},
+    app_lock::{
+        AppLock, Clock, LockState, ManualClock, StateReceiver, SystemClock, DEFAULT_IDLE_TIMEOUT,
+    },
+    audit::{AuditEvent, AuditRecord, AuditSink, JsonLinesSink},
+    error::{Error, PolicyError, Result},
+    localization::{
+        system_locale, CatalogError, Localized, Localizer, MissingTranslation, ResolveError,
+        TextKey,
+    },
+    registry::{Action, ActionRegistry},
+    text::{
+        AndroidText, LinuxDetail, LinuxText, Param, Platform, TemplateError, Text, TextBuf,
+        TextBuilder, TextField, TextIssue, TextIssueKind, ValidationError, WindowsText,
+    },
 };
 
 /// A "raw" context that can be used to create a [`Context`].
@@ -104,6 +158,8 @@ pub type RawContext = sys::RawContext;
 #[derive(Debug)]
 pub struct Context {
     inner: sys::Context,
+    actions: ActionRegistry,
+    audit: audit::Auditor,
 }
 
 impl Context {
@@ -112,6 +168,8 @@ impl Context {
     pub fn new(raw: RawContext) -> Self {
         Self {
             inner: sys::Context::new(raw),
+            actions: ActionRegistry::new(),
+            audit: audit::Auditor::default(),
         }
     }
 
@@ -124,7 +182,7 @@ impl Context {
     // #[cfg(feature = "async")]
     // pub async fn authenticate_async(
     //     &self,
-    //     message: Text<'_, '_, '_, '_, '_, '_>,
+    //     message: Text<'_>,
     //     policy: &Policy,
     // ) -> Result<()> {
     //     self.inner.authenticate(message, &policy.inner).await
@@ -144,6 +202,9 @@ impl Context {
     ///
     /// Thus, authentication failed if this function returns an error
     /// **OR** if the `callback` is invoked with `Err(_)`.
+    ///
+    /// If the `validate-text` feature is enabled, debug builds panic if the
+    /// `message` is not valid on every platform, see [`Text::validate`].
     #[inline]
     pub fn authenticate<F>(
         &self,
@@ -154,7 +215,79 @@ impl Context {
     where
         F: Fn(Result<()>) + Send + 'static,
     {
-        self.inner.authenticate(message, &policy.inner, callback)
+        self.authenticate_with_outcome(message, policy, move |result| {
+            callback(result.map(|_| ()))
+        })
+    }
+
+    /// Displays an authentication prompt like [`Self::authenticate`], but
+    /// invokes the `callback` with the [`Outcome`] of a successful
+    /// authentication, e.g., which user approved it.
+    #[inline]
+    pub fn authenticate_with_outcome<F>(
+        &self,
+        message: Text,
+        policy: &Policy,
+        callback: F,
+    ) -> Result<()>
+    where
+        F: Fn(Result<Outcome>) + Send + 'static,
+    {
+        self.authenticate_audited(message, policy, None, callback)
+    }
+
+    /// Authenticates like [`Self::authenticate_with_outcome`], recording the
+    /// request and its outcome for the given action.
+    pub(crate) fn authenticate_audited<F>(
+        &self,
+        message: Text,
+        policy: &Policy,
+        action: Option<&str>,
+        callback: F,
+    ) -> Result<()>
+    where
+        F: Fn(Result<Outcome>) + Send + 'static,
+    {
+        #[cfg(all(feature = "validate-text", debug_assertions))]
+        if let Err(issues) = message.validate() {
+            let issues: Vec<_> = issues.iter().map(ToString::to_string).collect();
+            panic!("invalid authentication prompt text: {}", issues.join("; "));
+        }
+
+        let request = self.audit.requested(action, Some(&policy.builder), Some(&message));
+        let finished = request.clone();
+        self.inner
+            .authenticate(message, policy, move |result| {
+                finished.finished(&result);
+                callback(result)
+            })
+            .inspect_err(|e| request.failed(e))
+    }
+
+    /// Sets the sink receiving a record of every authentication request and
+    /// its outcome, see [`AuditSink`].
+    ///
+    /// By default, nothing is recorded.
+    #[inline]
+    #[must_use]
+    pub fn with_audit_sink(mut self, sink: impl AuditSink + 'static) -> Self {
+        self.audit = audit::Auditor::new(sink);
+        self
+    }
+
+    /// Revokes any authorization retained from previous authentications, so
+    /// that the next authentication prompts the user again.
+    ///
+    /// On Linux, this revokes polkit's temporary authorizations for all actions
+    /// of the session, including those obtained by other processes. On other
+    /// targets, authorization is never retained by the platform.
+    ///
+    /// The reuse windows of the context's actions are also reset, see
+    /// [`ActionRegistry::revoke`].
+    #[inline]
+    pub fn revoke_cached_authorization(&self) -> Result<()> {
+        self.actions.revoke();
+        self.inner.revoke_cached_authorization()
     }
 }
 
@@ -165,12 +298,203 @@ impl Context {
 /// documentation][android-docs] for more details.
 ///
 /// [android-docs]: https://source.android.com/docs/security/features/biometric
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum BiometricStrength {
     Strong,
     Weak,
 }
 
+/// A method used to authenticate, see [`Outcome::method`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+#[non_exhaustive]
+pub enum AuthenticationMethod {
+    /// Biometrics, e.g., a fingerprint, see [`PolicyBuilder::biometrics`].
+    Biometrics,
+    /// A password, PIN or pattern, see [`PolicyBuilder::password`].
+    Password,
+    /// A companion device, e.g., a watch, see [`PolicyBuilder::companion`].
+    Companion,
+}
+
+/// Details about a successful authentication.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct Outcome {
+    /// The name of the user who approved the authentication, if known.
+    ///
+    /// This is only reported on Linux. With polkit, it is only known if the
+    /// identity is the current user, or if the prompt was displayed by the
+    /// agent registered for the current process.
+    pub user: Option<String>,
+    /// The method the user authenticated with, if known.
+    ///
+    /// On Linux, this is known for fingerprints and PAM, and with polkit only
+    /// if the prompt was displayed by the agent registered for the current
+    /// process. On Apple targets, it is only known if the policy allows a
+    /// single method, on Android from API level 30, and on Windows only if
+    /// Windows Hello is unavailable and the user entered their password.
+    pub method: Option<AuthenticationMethod>,
+}
+
+/// The identity that must approve an authentication.
+///
+/// Identities other than [`Self::CurrentUser`] are only supported on Linux by
+/// the polkit and PAM backends, and only when the policy supports passwords.
+/// On other targets, [`PolicyBuilder::try_build`] returns
+/// [`PolicyError::UnsupportedIdentity`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Identity {
+    /// The user running the app.
+    #[default]
+    CurrentUser,
+    /// An administrator, e.g., `root` or a member of the `wheel` group.
+    ///
+    /// With polkit, the administrators are defined by the system's polkit
+    /// rules, and the action must require `auth_admin`, see
+    /// [`LinuxBackend::DEFAULT_ADMIN_ACTION_ID`]. PAM can't authenticate
+    /// administrators, so [`PolicyBuilder::try_build`] returns
+    /// [`PolicyError::UnsupportedIdentity`] with [`LinuxBackend::Pam`].
+    Administrator,
+    /// The user with the given name.
+    ///
+    /// With polkit, the user must be one of the administrators offered for an
+    /// `auth_admin` action; the prompt is not restricted to the user unless
+    /// it is displayed by the agent registered for the current process.
+    User(Cow<'static, str>),
+}
+
+impl Identity {
+    /// Returns the user with the given name, e.g., in a `const` item.
+    #[inline]
+    pub const fn user_static(name: &'static str) -> Self {
+        Self::User(Cow::Borrowed(name))
+    }
+}
+
+/// The service used to authenticate on Linux.
+///
+/// This only has an effect on Linux.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LinuxBackend {
+    /// Authenticate using the desktop environment's [polkit] authentication
+    /// agent.
+    ///
+    /// The action must be declared in a `.policy` file installed in
+    /// `/usr/share/polkit-1/actions`, with `auth_self` (or `auth_self_keep`)
+    /// as the default for active sessions, e.g.:
+    /// ```xml
+    /// <action id="rs.robius.authentication.authenticate">
+    ///   <message>Authentication is required</message>
+    ///   <defaults>
+    ///     <allow_any>auth_self</allow_any>
+    ///     <allow_inactive>auth_self</allow_inactive>
+    ///     <allow_active>auth_self</allow_active>
+    ///   </defaults>
+    ///   <annotate key="org.freedesktop.policykit.owner">unix-user:myapp</annotate>
+    /// </action>
+    /// ```
+    ///
+    /// The optional `org.freedesktop.policykit.owner` annotation lists the
+    /// users, separated by spaces, allowed to pass the [`LinuxText`] to the
+    /// agent, e.g., the user of a system service. Polkit rejects details from
+    /// other users, so for them the agent displays the `<message>` of the
+    /// action instead; processes running as `root` are always allowed.
+    ///
+    /// If the [`Identity`] is not the current user, the action must use
+    /// `auth_admin` (or `auth_admin_keep`) instead. With the default action,
+    /// [`LinuxBackend::DEFAULT_ADMIN_ACTION_ID`] is checked in that case.
+    ///
+    /// If the session has no authentication agent, e.g., over SSH, an agent
+    /// is registered for the current process while authenticating, similar to
+    /// `pkttyagent`. It prompts using the conversation handler set using
+    /// [`Context::with_conversation`], or on the controlling terminal.
+    ///
+    /// [polkit]: https://www.freedesktop.org/software/polkit/docs/latest/polkit.8.html
+    Polkit {
+        /// The id of the polkit action.
+        action_id: Cow<'static, str>,
+    },
+    /// Authenticate the current user by running a [PAM] conversation against
+    /// the given service, e.g., `login` or a dedicated service installed in
+    /// `/etc/pam.d`.
+    ///
+    /// The prompts are displayed by the conversation handler set using
+    /// [`Context::with_conversation`], or on the controlling terminal if no
+    /// handler was set. PAM can be tested without touching the
+    /// system configuration using [`pam_wrapper`] and a test service file.
+    ///
+    /// This requires the `pam` feature; otherwise [`PolicyBuilder::try_build`]
+    /// returns [`PolicyError::UnsupportedBackend`].
+    ///
+    /// [PAM]: https://man7.org/linux/man-pages/man8/pam.8.html
+    /// [`pam_wrapper`]: https://cwrap.org/pam_wrapper.html
+    Pam {
+        /// The name of the PAM service.
+        service: Cow<'static, str>,
+    },
+}
+
+impl LinuxBackend {
+    /// The polkit action id used by default.
+    pub const DEFAULT_ACTION_ID: &'static str = "rs.robius.authentication.authenticate";
+
+    /// The polkit action id used instead of [`Self::DEFAULT_ACTION_ID`] if
+    /// the [`Identity`] is not the current user.
+    ///
+    /// The action must be declared with `auth_admin` as the default.
+    pub const DEFAULT_ADMIN_ACTION_ID: &'static str =
+        "rs.robius.authentication.authenticate-admin";
+
+    /// The default backend, i.e., polkit with [`Self::DEFAULT_ACTION_ID`].
+    pub const DEFAULT: Self = Self::polkit_static(Self::DEFAULT_ACTION_ID);
+
+    /// Returns the polkit backend checking the given action, e.g., in a
+    /// `const` item.
+    #[inline]
+    pub const fn polkit_static(action_id: &'static str) -> Self {
+        Self::Polkit {
+            action_id: Cow::Borrowed(action_id),
+        }
+    }
+
+    /// Returns the PAM backend using the given service, e.g., in a `const`
+    /// item.
+    #[inline]
+    pub const fn pam_static(service: &'static str) -> Self {
+        Self::Pam {
+            service: Cow::Borrowed(service),
+        }
+    }
+
+    /// Returns the polkit action checked for `action_id` if the identity is
+    /// `identity`.
+    pub(crate) fn polkit_action_id<'a>(action_id: &'a str, identity: &Identity) -> &'a str {
+        // Other identities require an `auth_admin` action.
+        if *identity != Identity::CurrentUser && action_id == Self::DEFAULT_ACTION_ID {
+            Self::DEFAULT_ADMIN_ACTION_ID
+        } else {
+            action_id
+        }
+    }
+}
+
+impl Default for LinuxBackend {
+    #[inline]
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
 /// A builder for conveniently defining a policy.
 ///
 /// It is **highly recommended** to use the [`Self::new()`] (default) value
@@ -178,9 +502,14 @@ pub enum BiometricStrength {
 /// when being requested to enable/disable various authentication methods.
 /// Enabling all options is the safest way to ensure that the authentication prompt
 /// will be displayed correctly on all platforms.
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct PolicyBuilder {
-    inner: sys::PolicyBuilder,
+    biometrics: Option<BiometricStrength>,
+    password: bool,
+    companion: bool,
+    wrist_detection: bool,
+    linux_backend: LinuxBackend,
+    identity: Identity,
 }
 
 impl Default for PolicyBuilder {
@@ -195,7 +524,12 @@ impl PolicyBuilder {
     #[inline]
     pub const fn new() -> Self {
         Self {
-            inner: sys::PolicyBuilder::new(),
+            biometrics: Some(BiometricStrength::Strong),
+            password: true,
+            companion: true,
+            wrist_detection: true,
+            linux_backend: LinuxBackend::DEFAULT,
+            identity: Identity::CurrentUser,
         }
     }
 
@@ -205,19 +539,17 @@ impl PolicyBuilder {
     /// for more details.
     #[inline]
     #[must_use]
-    pub const fn biometrics(self, strength: Option<BiometricStrength>) -> Self {
-        Self {
-            inner: self.inner.biometrics(strength),
-        }
+    pub const fn biometrics(mut self, strength: Option<BiometricStrength>) -> Self {
+        self.biometrics = strength;
+        self
     }
 
     /// Sets whether the policy supports passwords.
     #[inline]
     #[must_use]
-    pub const fn password(self, password: bool) -> Self {
-        Self {
-            inner: self.inner.password(password),
-        }
+    pub const fn password(mut self, password: bool) -> Self {
+        self.password = password;
+        self
     }
 
     /// Sets whether the policy supports authentication via a proximity companion device, e.g., Apple Watch.
@@ -225,10 +557,9 @@ impl PolicyBuilder {
     /// This only has an effect on iOS and macOS.
     #[inline]
     #[must_use]
-    pub const fn companion(self, companion: bool) -> Self {
-        Self {
-            inner: self.inner.companion(companion),
-        }
+    pub const fn companion(mut self, companion: bool) -> Self {
+        self.companion = companion;
+        self
     }
 
     /// Sets whether the policy requires the companion device (Apple Watch) to be on the user's wrist.
@@ -236,30 +567,103 @@ impl PolicyBuilder {
     /// This only has an effect on Apple watchOS.
     #[inline]
     #[must_use]
-    pub const fn wrist_detection(self, wrist_detection: bool) -> Self {
-        Self {
-            inner: self.inner.wrist_detection(wrist_detection),
-        }
+    pub const fn wrist_detection(mut self, wrist_detection: bool) -> Self {
+        self.wrist_detection = wrist_detection;
+        self
+    }
+
+    /// Sets the service used to authenticate, see [`LinuxBackend`].
+    ///
+    /// This only has an effect on Linux. In `const` items, use
+    /// [`LinuxBackend::polkit_static`] or [`LinuxBackend::pam_static`]:
+    /// ```
+    /// use robius_authentication::{LinuxBackend, Policy, PolicyBuilder};
+    ///
+    /// const POLICY: Policy = PolicyBuilder::new()
+    ///     .linux_backend(LinuxBackend::polkit_static("org.example.app.unlock"))
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    #[inline]
+    #[must_use]
+    pub const fn linux_backend(mut self, backend: LinuxBackend) -> Self {
+        // A `const fn` can't drop the previous backend, which is only
+        // allocated if it was set at run-time.
+        core::mem::forget(core::mem::replace(&mut self.linux_backend, backend));
+        self
+    }
+
+    /// Sets the identity that must approve the authentication, see
+    /// [`Identity`].
+    #[inline]
+    #[must_use]
+    pub const fn identity(mut self, identity: Identity) -> Self {
+        core::mem::forget(core::mem::replace(&mut self.identity, identity));
+        self
     }
 
     /// Constructs the policy.
     ///
     /// Returns `None` if the specified configuration is not valid for the
-    /// current target.
+    /// current target, see [`Self::try_build`] for the reason.
     #[inline]
     #[must_use]
     pub const fn build(self) -> Option<Policy> {
-        Some(Policy {
-            inner: match self.inner.build() {
-                Some(inner) => inner,
-                None => return None,
-            },
+        // A `const fn` can't drop the result of `Self::try_build`.
+        match self.sys_builder().try_build() {
+            Ok(inner) => Some(Policy {
+                inner,
+                builder: self,
+            }),
+            Err(_) => {
+                core::mem::forget(self);
+                None
+            }
+        }
+    }
+
+    /// Constructs the policy, returning why the specified configuration is
+    /// not valid for the current target.
+    #[inline]
+    pub const fn try_build(self) -> std::result::Result<Policy, PolicyError> {
+        match self.sys_builder().try_build() {
+            Ok(inner) => Ok(Policy {
+                inner,
+                builder: self,
+            }),
+            Err(error) => {
+                // A `const fn` can't drop the builder, which only leaks names
+                // allocated at run-time.
+                core::mem::forget(self);
+                Err(error)
+            }
+        }
+    }
+
+    /// Constructs the policy like [`Self::try_build`] without consuming or
+    /// leaking the builder.
+    pub(crate) fn try_build_ref(&self) -> std::result::Result<Policy, PolicyError> {
+        Ok(Policy {
+            inner: self.sys_builder().try_build()?,
+            builder: self.clone(),
         })
     }
+
+    const fn sys_builder(&self) -> sys::PolicyBuilder {
+        sys::PolicyBuilder::new()
+            .biometrics(self.biometrics)
+            .password(self.password)
+            .companion(self.companion)
+            .wrist_detection(self.wrist_detection)
+            .linux_backend(&self.linux_backend)
+            .identity(&self.identity)
+    }
 }
 
 /// An authentication policy.
 #[derive(Debug)]
 pub struct Policy {
     inner: sys::Policy,
+    /// The configuration the policy was built from, e.g., to serialize it.
+    builder: PolicyBuilder,
 }
@@ -0,0 +1,332 @@
+//! Linux-specific extensions.
+
+mod subject;
+mod terminal;
+
+pub use subject::Subject;
+pub(crate) use subject::SubjectKind;
+pub use terminal::Terminal;
+
+use std::{
+    process::{Command, ExitStatus},
+    time::SystemTime,
+};
+
+use crate::{Context, Error, Result};
+
+/// Displays the prompts of a PAM conversation.
+///
+/// A conversation is only used by the [`Pam`] backend; the polkit backend
+/// uses the desktop environment's authentication agent instead.
+///
+/// [`Pam`]: crate::LinuxBackend::Pam
+pub trait Conversation: Send {
+    /// Called once before the first prompt of an authentication attempt with
+    /// the [`LinuxText::message`](crate::LinuxText::message).
+    fn begin(&mut self, message: &str) {
+        let _ = message;
+    }
+
+    /// Asks the user for a response to the given `prompt`.
+    ///
+    /// If `echo` is false the response is a secret, e.g., a password, and
+    /// must not be displayed. Returning an error aborts authentication with
+    /// that error, e.g., [`Error::UserCanceled`].
+    fn prompt(&mut self, prompt: &str, echo: bool) -> Result<String>;
+
+    /// Displays an informational message.
+    fn info(&mut self, message: &str) {
+        let _ = message;
+    }
+
+    /// Displays an error message.
+    fn error(&mut self, message: &str) {
+        let _ = message;
+    }
+
+    /// Called after an authentication attempt failed with `error`.
+    ///
+    /// Returning true starts another attempt. By default, authentication is
+    /// only attempted once.
+    fn retry(&mut self, error: &Error) -> bool {
+        let _ = error;
+        false
+    }
+}
+
+/// An authorization retained by polkit after authenticating for an action
+/// whose policy is `auth_self_keep` or `auth_admin_keep`.
+///
+/// While it has not expired, the action is authorized without prompting.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TemporaryAuthorization {
+    /// The opaque ID of the authorization.
+    pub id: String,
+    /// The ID of the authorized action.
+    pub action_id: String,
+    /// When the authorization was obtained.
+    pub obtained: SystemTime,
+    /// When the authorization expires.
+    pub expires: SystemTime,
+}
+
+/// The output stream of a helper program, see
+/// [`Context::authorize_and_run`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OutputStream {
+    /// The standard output.
+    Stdout,
+    /// The standard error.
+    Stderr,
+}
+
+/// Why cached authorization was invalidated, see
+/// [`Context::watch_invalidation`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Invalidation {
+    /// The session was locked, i.e., logind's `Lock` signal.
+    Locked,
+    /// The system is about to sleep.
+    Sleep,
+    /// The session was moved to the background, e.g., when switching users.
+    SessionInactive,
+    /// The screen saver was activated.
+    ScreenSaver,
+}
+
+/// Watches for the user walking away, see [`Context::watch_invalidation`].
+///
+/// Watching stops when this is dropped.
+#[derive(Debug)]
+pub struct InvalidationWatch {
+    _inner: crate::sys::InvalidationWatch,
+}
+
+impl Context {
+    /// Sets the handler for the prompts of a PAM conversation.
+    ///
+    /// Without a handler, the [`Pam`] backend prompts on the controlling
+    /// terminal (see [`Terminal`]), failing with [`Error::NotInteractive`] if
+    /// there is none. Outside of a login session, e.g., in a system service,
+    /// only the handler is assumed to reach the user, so authentication fails
+    /// with [`Error::NotInteractive`] without one.
+    ///
+    /// [`Pam`]: crate::LinuxBackend::Pam
+    #[inline]
+    #[must_use]
+    pub fn with_conversation(mut self, conversation: impl Conversation + 'static) -> Self {
+        self.inner.set_conversation(Box::new(conversation));
+        self
+    }
+
+    /// Sets whether the program and command line of the current process are
+    /// passed to the polkit authentication agent, which may display them.
+    ///
+    /// Enabled by default.
+    #[inline]
+    #[must_use]
+    pub fn with_process_details(mut self, enabled: bool) -> Self {
+        self.inner.set_process_details(enabled);
+        self
+    }
+
+    /// Sets a function that redacts the command line of the current process
+    /// before it is passed to the polkit authentication agent.
+    ///
+    /// The function is given the arguments of the command line, including the
+    /// program, and returns the arguments to display. If it returns no
+    /// arguments, the command line is omitted.
+    ///
+    /// ```
+    /// use robius_authentication::Context;
+    ///
+    /// let context = Context::new(()).with_command_line_redaction(|args| {
+    ///     args.into_iter()
+    ///         .map(|arg| match arg.split_once('=') {
+    ///             Some(("--token", _)) => "--token=<redacted>".to_owned(),
+    ///             _ => arg,
+    ///         })
+    ///         .collect()
+    /// });
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn with_command_line_redaction(
+        mut self,
+        redact: impl Fn(Vec<String>) -> Vec<String> + Send + Sync + 'static,
+    ) -> Self {
+        self.inner
+            .set_command_line_redaction(std::sync::Arc::new(redact));
+        self
+    }
+
+    /// Checks the authorization of the given subject instead of the current
+    /// process, e.g., for a privileged helper acting on behalf of its client.
+    ///
+    /// Checking a subject of another user requires the current process to be
+    /// privileged. As the prompt is displayed by the subject's authentication
+    /// agent, no agent is registered if the session has none.
+    ///
+    /// This only has an effect on the polkit backend.
+    #[inline]
+    #[must_use]
+    pub fn for_subject(mut self, subject: Subject) -> Self {
+        self.inner.set_subject(subject);
+        self
+    }
+
+    /// Cancels the polkit authorization checks and fingerprint verifications
+    /// in progress.
+    ///
+    /// The callbacks of the canceled checks are called with
+    /// [`Error::AppCanceled`], without falling back to a password. Checks
+    /// started after this call are not affected.
+    #[inline]
+    pub fn cancel(&self) -> Result<()> {
+        self.inner.cancel()
+    }
+
+    /// Returns the temporary authorizations retained by polkit for the
+    /// session of the subject (see [`Self::for_subject`]).
+    ///
+    /// See [`Context::revoke_cached_authorization`] to revoke them.
+    #[inline]
+    pub fn temporary_authorizations(&self) -> Result<Vec<TemporaryAuthorization>> {
+        self.inner.temporary_authorizations()
+    }
+
+    /// Revokes cached authorization whenever the user walks away, i.e., when
+    /// the session is locked or moved to the background, the screen saver is
+    /// activated or the system is about to sleep, and then calls `callback`.
+    ///
+    /// The reuse windows of the context's actions are also reset, see
+    /// [`ActionRegistry::revoke`](crate::ActionRegistry::revoke). The
+    /// callback should invalidate any other authorization retained by the app
+    /// itself. It is called on a background thread, possibly several times
+    /// for the same event, e.g., if locking the session also activates the
+    /// screen saver. Watching stops when the returned [`InvalidationWatch`] is
+    /// dropped.
+    ///
+    /// The signals of logind's [`Session`] and [`Manager`] are watched on the
+    /// system bus, and [`org.freedesktop.ScreenSaver`] on the session bus if
+    /// there is one. The session is that of the subject (see
+    /// [`Self::for_subject`]), if set. Outside of a login session, only sleep
+    /// is watched.
+    /// Returns [`Error::Unavailable`] if there is no system bus.
+    ///
+    /// ```no_run
+    /// use std::sync::{
+    ///     atomic::{AtomicBool, Ordering},
+    ///     Arc,
+    /// };
+    ///
+    /// use robius_authentication::Context;
+    ///
+    /// let unlocked = Arc::new(AtomicBool::new(true));
+    /// let context = Context::new(());
+    /// let _watch = context.watch_invalidation({
+    ///     let unlocked = unlocked.clone();
+    ///     move |invalidation| {
+    ///         println!("locking the app: {invalidation:?}");
+    ///         unlocked.store(false, Ordering::SeqCst);
+    ///     }
+    /// })?;
+    /// # Ok::<_, robius_authentication::Error>(())
+    /// ```
+    ///
+    /// [`Session`]: https://www.freedesktop.org/software/systemd/man/latest/org.freedesktop.login1.html#Session%20Objects
+    /// [`Manager`]: https://www.freedesktop.org/software/systemd/man/latest/org.freedesktop.login1.html#The%20Manager%20Object
+    /// [`org.freedesktop.ScreenSaver`]: https://specifications.freedesktop.org/idle-inhibit-spec/latest/
+    #[inline]
+    pub fn watch_invalidation(
+        &self,
+        callback: impl Fn(Invalidation) + Send + 'static,
+    ) -> Result<InvalidationWatch> {
+        let authenticated = self.actions.authenticated().clone();
+        let callback = move |invalidation| {
+            authenticated.revoke();
+            callback(invalidation);
+        };
+        Ok(InvalidationWatch {
+            _inner: self.inner.watch_invalidation(Box::new(callback))?,
+        })
+    }
+
+    /// Authorizes the polkit action for the current process and, if
+    /// authorized, runs the program and arguments of `command` as `root`
+    /// using [`pkexec`].
+    ///
+    /// The helper's standard output and error are passed to `output` as they
+    /// are read. Returns the helper's exit status once it has exited, which
+    /// blocks the current thread. Failed authorizations are reported as
+    /// errors, as for [`Context::authenticate`], including if `pkexec` is not
+    /// installed ([`Error::Unavailable`]).
+    ///
+    /// `pkexec` checks the authorization again for the action whose
+    /// `org.freedesktop.policykit.exec.path` annotation is the helper's path,
+    /// which should be the given action. Its default should be
+    /// `auth_admin_keep` so that the user is only prompted once, e.g.:
+    /// ```xml
+    /// <action id="org.example.app.helper">
+    ///   <message>Authentication is required to run the helper</message>
+    ///   <defaults>
+    ///     <allow_any>auth_admin_keep</allow_any>
+    ///     <allow_inactive>auth_admin_keep</allow_inactive>
+    ///     <allow_active>auth_admin_keep</allow_active>
+    ///   </defaults>
+    ///   <annotate key="org.freedesktop.policykit.exec.path">/usr/libexec/example-helper</annotate>
+    /// </action>
+    /// ```
+    ///
+    /// The helper is run with a minimal environment in the home directory of
+    /// `root`, so the environment and working directory of `command` are
+    /// ignored. As `pkexec` exits with 126 if the prompt was dismissed and
+    /// 127 if authorization failed, these are reported as
+    /// [`Error::UserCanceled`] and [`Error::Authentication`] respectively.
+    /// `pkexec` can't be told apart from a helper exiting with the same
+    /// status, so the helper should use other exit statuses; if it exits with
+    /// 126 or 127 itself, the error is returned instead of its exit status.
+    ///
+    /// Once the helper has run, the [`AuditSink`] records a success, whose
+    /// approver and method are only known if the prompt was displayed by the
+    /// agent registered for the current process.
+    ///
+    /// [`AuditSink`]: crate::AuditSink
+    ///
+    /// ```no_run
+    /// use std::{io::Write, process::Command};
+    ///
+    /// use robius_authentication::{linux::OutputStream, Context};
+    ///
+    /// let mut command = Command::new("/usr/libexec/example-helper");
+    /// command.arg("--install");
+    /// let status = Context::new(()).authorize_and_run(
+    ///     "org.example.app.helper",
+    ///     &command,
+    ///     |stream, chunk| match stream {
+    ///         OutputStream::Stdout => std::io::stdout().write_all(chunk).unwrap(),
+    ///         OutputStream::Stderr => std::io::stderr().write_all(chunk).unwrap(),
+    ///     },
+    /// )?;
+    /// println!("helper exited with {status}");
+    /// # Ok::<_, robius_authentication::Error>(())
+    /// ```
+    ///
+    /// [`pkexec`]: https://www.freedesktop.org/software/polkit/docs/latest/pkexec.1.html
+    #[inline]
+    pub fn authorize_and_run(
+        &self,
+        action_id: &str,
+        command: &Command,
+        mut output: impl FnMut(OutputStream, &[u8]),
+    ) -> Result<ExitStatus> {
+        let request = self.audit.requested(Some(action_id), None, None);
+        self.inner
+            .authorize_and_run(action_id, command, &mut output)
+            .map(|(status, outcome)| {
+                request.finished(&Ok(outcome));
+                status
+            })
+            .inspect_err(|e| request.failed(e))
+    }
+}
@@ -0,0 +1,123 @@
+//! The subject of a polkit authorization check.
+
+use std::{
+    fs,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    sync::Arc,
+};
+
+use crate::{Error, Result};
+
+/// The subject whose authorization is checked by polkit.
+///
+/// A process identified only by its PID is not safe to check, because the PID
+/// may be reused by another process before polkit looks it up. Processes are
+/// therefore also identified by their start time and, on Linux 5.3 or newer, by
+/// a pidfd, which polkit 121 or newer uses instead of the PID.
+#[derive(Clone, Debug)]
+pub struct Subject {
+    pub(crate) kind: SubjectKind,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum SubjectKind {
+    Process {
+        pid: u32,
+        start_time: u64,
+        uid: u32,
+        pidfd: Option<Arc<OwnedFd>>,
+    },
+    Session(String),
+    SystemBusName(String),
+}
+
+impl Subject {
+    /// Returns the subject for the current process.
+    pub fn current_process() -> Result<Self> {
+        Self::process(std::process::id())
+    }
+
+    /// Returns the subject for the process with the given PID, e.g., a peer
+    /// connected to a Unix socket as reported by `SO_PEERCRED`.
+    ///
+    /// The process is identified by its start time and pidfd as of this call.
+    /// Returns [`Error::Unknown`] if there is no such process.
+    pub fn process(pid: u32) -> Result<Self> {
+        let pidfd = pidfd_open(pid);
+        let (start_time, uid) = process_info(pid).ok_or(Error::Unknown)?;
+        // Otherwise, the process may have exited and its PID been reused
+        // before its information was read.
+        if let Some(pidfd) = &pidfd {
+            if !is_alive(pidfd) {
+                return Err(Error::Unknown);
+            }
+        }
+
+        Ok(Self {
+            kind: SubjectKind::Process {
+                pid,
+                start_time,
+                uid,
+                pidfd: pidfd.map(Arc::new),
+            },
+        })
+    }
+
+    /// Returns the subject for the login session with the given ID, e.g., the
+    /// value of `XDG_SESSION_ID`.
+    pub fn session(session_id: impl Into<String>) -> Self {
+        Self {
+            kind: SubjectKind::Session(session_id.into()),
+        }
+    }
+
+    /// Returns the subject for the system bus client with the given unique
+    /// name, e.g., the sender of a method call to a D-Bus service.
+    ///
+    /// Unlike a process, a unique name is never reused, so this is the
+    /// preferred subject for D-Bus services.
+    pub fn system_bus_name(name: impl Into<String>) -> Self {
+        Self {
+            kind: SubjectKind::SystemBusName(name.into()),
+        }
+    }
+}
+
+fn pidfd_open(pid: u32) -> Option<OwnedFd> {
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+    // Fails on Linux versions older than 5.3.
+    (fd >= 0).then(|| unsafe { OwnedFd::from_raw_fd(fd as libc::c_int) })
+}
+
+fn is_alive(pidfd: &OwnedFd) -> bool {
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_pidfd_send_signal,
+            pidfd.as_raw_fd(),
+            0,
+            std::ptr::null::<libc::siginfo_t>(),
+            0,
+        )
+    };
+    result == 0
+}
+
+/// Returns the start time and real user ID of a process.
+fn process_info(pid: u32) -> Option<(u64, u32)> {
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    // The second field is the executable name, which may contain spaces and
+    // parentheses, so the remaining fields are split after the last `)`.
+    let start_time = stat
+        .rsplit_once(')')
+        .and_then(|(_, fields)| fields.split_whitespace().nth(19))
+        .and_then(|start_time| start_time.parse().ok())?;
+
+    let status = fs::read_to_string(format!("/proc/{pid}/status")).ok()?;
+    let uid = status
+        .lines()
+        .find_map(|line| line.strip_prefix("Uid:"))
+        .and_then(|uids| uids.split_whitespace().next())
+        .and_then(|uid| uid.parse().ok())?;
+
+    Some((start_time, uid))
+}
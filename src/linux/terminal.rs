@@ -0,0 +1,345 @@
+//! Prompts on a terminal, e.g., for sessions over SSH or on a virtual console.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    mem::MaybeUninit,
+    os::fd::{AsRawFd, RawFd},
+};
+
+use super::Conversation;
+use crate::{Error, Result};
+
+const CTRL_C: u8 = 0x03;
+const CTRL_D: u8 = 0x04;
+const CTRL_U: u8 = 0x15;
+const BACKSPACE: u8 = 0x08;
+const DELETE: u8 = 0x7f;
+
+/// A [`Conversation`] that prompts on a terminal.
+///
+/// Secrets are read with echo disabled. Pressing Ctrl-C, or Ctrl-D on an empty
+/// line, cancels authentication with [`Error::UserCanceled`].
+///
+/// The terminal can be any TTY, e.g., the slave side of a pseudo-terminal to
+/// drive the prompts programmatically.
+#[derive(Debug)]
+pub struct Terminal {
+    tty: File,
+    attempts: u32,
+    failures: u32,
+}
+
+impl Terminal {
+    /// The number of attempts allowed by default.
+    pub const DEFAULT_ATTEMPTS: u32 = 3;
+
+    /// Opens the controlling terminal of the current process.
+    ///
+    /// Returns [`Error::NotInteractive`] if the process has no controlling
+    /// terminal, e.g., because it was started by a service manager.
+    pub fn open() -> Result<Self> {
+        let tty = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/tty")
+            .map_err(|_| Error::NotInteractive)?;
+        Self::from_file(tty)
+    }
+
+    /// Prompts on the given terminal.
+    ///
+    /// Returns [`Error::NotInteractive`] if `tty` is not a terminal.
+    pub fn from_file(tty: File) -> Result<Self> {
+        if unsafe { libc::isatty(tty.as_raw_fd()) } != 1 {
+            return Err(Error::NotInteractive);
+        }
+        Ok(Self {
+            tty,
+            attempts: Self::DEFAULT_ATTEMPTS,
+            failures: 0,
+        })
+    }
+
+    /// Sets the number of times the user may enter their credentials before
+    /// authentication fails.
+    ///
+    /// Defaults to [`Self::DEFAULT_ATTEMPTS`].
+    #[inline]
+    #[must_use]
+    pub fn attempts(self, attempts: u32) -> Self {
+        Self { attempts, ..self }
+    }
+
+    fn write_line(&mut self, line: &str) {
+        // There is nobody to report a failure to.
+        let _ = writeln!(self.tty, "{line}");
+    }
+
+    /// Reads a line with the terminal in raw mode.
+    fn read_line(&mut self, echo: bool) -> Result<String> {
+        let _raw = RawMode::enable(self.tty.as_raw_fd())?;
+
+        let mut line = Vec::new();
+        let result = loop {
+            let mut byte = 0;
+            match self.tty.read(std::slice::from_mut(&mut byte)) {
+                Ok(0) if line.is_empty() => break Err(Error::UserCanceled),
+                Ok(0) => break Ok(()),
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => break Err(Error::Unknown),
+            }
+
+            match byte {
+                CTRL_C => break Err(Error::UserCanceled),
+                CTRL_D if line.is_empty() => break Err(Error::UserCanceled),
+                CTRL_D => {}
+                b'\r' | b'\n' => break Ok(()),
+                BACKSPACE | DELETE => {
+                    // Removes a whole UTF-8 character.
+                    while let Some(byte) = line.pop() {
+                        if byte & 0xc0 != 0x80 {
+                            break;
+                        }
+                    }
+                    if echo {
+                        let _ = self.tty.write_all(b"\x08 \x08");
+                    }
+                }
+                CTRL_U => {
+                    line.fill(0);
+                    line.clear();
+                    if echo {
+                        let _ = self.tty.write_all(b"\r\x1b[K");
+                    }
+                }
+                byte if byte.is_ascii_control() => {}
+                byte => {
+                    line.push(byte);
+                    if echo {
+                        let _ = self.tty.write_all(&[byte]);
+                    }
+                }
+            }
+        };
+        let _ = self.tty.write_all(b"\n");
+
+        let result =
+            result.and_then(|()| String::from_utf8(line.clone()).map_err(|_| Error::Unknown));
+        line.fill(0);
+        result
+    }
+}
+
+impl Conversation for Terminal {
+    fn begin(&mut self, message: &str) {
+        self.failures = 0;
+        self.write_line(message);
+    }
+
+    fn prompt(&mut self, prompt: &str, echo: bool) -> Result<String> {
+        let _ = self.tty.write_all(prompt.as_bytes());
+        self.read_line(echo)
+    }
+
+    fn info(&mut self, message: &str) {
+        self.write_line(message);
+    }
+
+    fn error(&mut self, message: &str) {
+        self.write_line(message);
+    }
+
+    fn retry(&mut self, error: &Error) -> bool {
+        if !matches!(error, Error::Authentication) {
+            return false;
+        }
+        self.failures += 1;
+        if self.failures >= self.attempts {
+            return false;
+        }
+        self.write_line("Sorry, try again.");
+        true
+    }
+}
+
+/// Disables canonical mode, echo and signals until dropped.
+///
+/// Signals are disabled so that Ctrl-C is read as a character instead of
+/// killing the process while echo is disabled.
+struct RawMode {
+    fd: RawFd,
+    original: libc::termios,
+}
+
+impl RawMode {
+    fn enable(fd: RawFd) -> Result<Self> {
+        let mut original = MaybeUninit::uninit();
+        if unsafe { libc::tcgetattr(fd, original.as_mut_ptr()) } != 0 {
+            return Err(Error::NotInteractive);
+        }
+        let original = unsafe { original.assume_init() };
+
+        let mut raw = original;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw) } != 0 {
+            return Err(Error::NotInteractive);
+        }
+        Ok(Self { fd, original })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe { libc::tcsetattr(self.fd, libc::TCSADRAIN, &self.original) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        os::fd::{FromRawFd, OwnedFd},
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+
+    /// A pseudo-terminal, driving a [`Terminal`] on its slave side.
+    struct Pty {
+        master: File,
+        slave: File,
+    }
+
+    impl Pty {
+        fn open() -> Self {
+            let (mut master, mut slave) = (0, 0);
+            let status = unsafe {
+                libc::openpty(
+                    &mut master,
+                    &mut slave,
+                    std::ptr::null_mut(),
+                    std::ptr::null(),
+                    std::ptr::null(),
+                )
+            };
+            assert_eq!(status, 0, "failed to open a pseudo-terminal");
+            let (master, slave) =
+                unsafe { (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave)) };
+            Self {
+                master: master.into(),
+                slave: slave.into(),
+            }
+        }
+
+        /// Prompts on the slave side, typing `input` once the terminal is in
+        /// raw mode, and returns the result and the output of the terminal.
+        fn prompt(self, echo: bool, input: &[u8]) -> (Result<String>, String) {
+            let Self { mut master, slave } = self;
+            let mut terminal = Terminal::from_file(slave).unwrap();
+            let prompt = thread::spawn(move || terminal.prompt("Password: ", echo));
+
+            // Typing before echo is disabled would echo the input.
+            let deadline = Instant::now() + Duration::from_secs(10);
+            while !raw_mode(master.as_raw_fd()) {
+                assert!(Instant::now() < deadline, "the terminal is not in raw mode");
+                thread::sleep(Duration::from_millis(1));
+            }
+            master.write_all(input).unwrap();
+            let result = prompt.join().unwrap();
+
+            (result, read_available(&mut master))
+        }
+    }
+
+    /// Returns whether canonical mode and echo are disabled.
+    fn raw_mode(fd: RawFd) -> bool {
+        let mut termios = MaybeUninit::uninit();
+        assert_eq!(unsafe { libc::tcgetattr(fd, termios.as_mut_ptr()) }, 0);
+        let termios = unsafe { termios.assume_init() };
+        termios.c_lflag & (libc::ICANON | libc::ECHO) == 0
+    }
+
+    /// Reads the output written to the terminal so far.
+    fn read_available(master: &mut File) -> String {
+        let mut output = Vec::new();
+        let mut poll = libc::pollfd {
+            fd: master.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        while unsafe { libc::poll(&mut poll, 1, 100) } == 1 {
+            let mut buf = [0; 256];
+            match master.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(len) => output.extend_from_slice(&buf[..len]),
+            }
+        }
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn secret_is_not_echoed() {
+        let (result, output) = Pty::open().prompt(false, b"hunter2\r");
+        assert_eq!(result.unwrap(), "hunter2");
+        assert!(output.starts_with("Password: "));
+        assert!(!output.contains("hunter2"));
+    }
+
+    #[test]
+    fn response_is_echoed() {
+        let (result, output) = Pty::open().prompt(true, b"alice\r");
+        assert_eq!(result.unwrap(), "alice");
+        assert!(output.contains("alice"));
+    }
+
+    #[test]
+    fn line_editing() {
+        let (result, _) = Pty::open().prompt(false, b"ab\x7fc\r");
+        assert_eq!(result.unwrap(), "ac");
+        let (result, _) = Pty::open().prompt(false, "ü\x7fx\r".as_bytes());
+        assert_eq!(result.unwrap(), "x");
+        let (result, _) = Pty::open().prompt(false, b"wrong\x15right\r");
+        assert_eq!(result.unwrap(), "right");
+    }
+
+    #[test]
+    fn canceled() {
+        let (result, _) = Pty::open().prompt(false, &[b'a', CTRL_C]);
+        assert!(matches!(result, Err(Error::UserCanceled)));
+        let (result, _) = Pty::open().prompt(false, &[CTRL_D]);
+        assert!(matches!(result, Err(Error::UserCanceled)));
+        // Ctrl-D is ignored on a non-empty line.
+        let (result, _) = Pty::open().prompt(false, &[b'a', CTRL_D, b'\r']);
+        assert_eq!(result.unwrap(), "a");
+    }
+
+    #[test]
+    fn retries_are_counted() {
+        let Pty { mut master, slave } = Pty::open();
+        let mut terminal = Terminal::from_file(slave).unwrap().attempts(2);
+
+        terminal.begin("Authenticate");
+        assert!(terminal.retry(&Error::Authentication));
+        assert!(!terminal.retry(&Error::Authentication));
+        // Only failed authentication is retried.
+        terminal.begin("Authenticate");
+        assert!(!terminal.retry(&Error::UserCanceled));
+        assert!(terminal.retry(&Error::Authentication));
+
+        let output = read_available(&mut master);
+        assert_eq!(output.matches("Sorry, try again.").count(), 2);
+    }
+
+    #[test]
+    fn not_a_terminal() {
+        let file = File::open("/dev/null").unwrap();
+        assert!(matches!(
+            Terminal::from_file(file),
+            Err(Error::NotInteractive)
+        ));
+    }
+}
@@ -0,0 +1,414 @@
+//! Run-time localization of prompt text.
+
+mod fluent;
+mod po;
+
+use std::{collections::HashMap, fmt, fs, io, path::Path};
+
+use crate::{TextBuf, TextBuilder, TextIssue};
+
+/// A key identifying a localized prompt text.
+///
+/// A catalog translates a key `id` using the following messages, of which only
+/// the reason is required:
+///
+/// | Message                   | Meaning                                   |
+/// |---------------------------|-------------------------------------------|
+/// | `id`                      | The reason, see [`TextBuilder::reason`]   |
+/// | `id.title`                | See [`TextBuilder::title`]                |
+/// | `id.app-name`             | See [`TextBuilder::app_name`]             |
+/// | `id.android-title`        | See [`TextBuilder::android_title`]        |
+/// | `id.android-subtitle`     | See [`TextBuilder::android_subtitle`]     |
+/// | `id.android-description`  | See [`TextBuilder::android_description`]  |
+/// | `id.apple`                | See [`TextBuilder::apple`]                |
+/// | `id.linux-message`        | See [`TextBuilder::linux_message`]        |
+/// | `id.windows-title`        | See [`TextBuilder::windows_title`]        |
+/// | `id.windows-description`  | See [`TextBuilder::windows_description`]  |
+///
+/// If `id.app-name` is missing, the catalog-wide `app-name` message is used.
+///
+/// In Fluent files, all but the reason are attributes of the `id` message. In
+/// gettext files, they are separate entries whose `msgid` is the message name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextKey<'a>(pub &'a str);
+
+/// The optional messages of a [`TextKey`], in the order they are looked up.
+const ATTRIBUTES: [&str; 9] = [
+    "title",
+    "app-name",
+    "android-title",
+    "android-subtitle",
+    "android-description",
+    "apple",
+    "linux-message",
+    "windows-title",
+    "windows-description",
+];
+
+/// The catalog-wide message used if a key has no `app-name` attribute.
+const APP_NAME: &str = "app-name";
+
+/// A set of message catalogs that resolves [`TextKey`]s to [`TextBuf`]s.
+///
+/// Catalogs are loaded per locale from [Fluent] (`.ftl`) or [gettext] (`.po`)
+/// files. When resolving a key, each message is looked up along the fallback
+/// chain of the requested locale (e.g., `pt-BR`, then `pt`), ending with the
+/// default locale. Messages that had to be taken from the default locale
+/// because neither the requested locale nor its parents have them are reported
+/// as [`MissingTranslation`]s.
+///
+/// Only plain text messages are supported: Fluent placeables and selectors, as
+/// well as gettext plural forms, are not interpreted.
+///
+/// # Example
+///
+/// ```
+/// use robius_authentication::{Localizer, TextKey};
+///
+/// let mut localizer = Localizer::new("en");
+/// localizer
+///     .add_fluent(
+///         "en",
+///         "unlock-vault = unlock your vault\n    .title = Unlock vault\n",
+///     )
+///     .unwrap();
+/// localizer
+///     .add_fluent("pt", "unlock-vault = desbloquear o seu cofre\n")
+///     .unwrap();
+///
+/// let localized = localizer.resolve(TextKey("unlock-vault"), "pt_BR.UTF-8").unwrap();
+/// assert_eq!(localized.text.as_text().apple, "desbloquear o seu cofre");
+///
+/// // The title is only available in English.
+/// assert_eq!(localized.text.as_text().android.title, "Unlock vault");
+/// assert_eq!(localized.missing.len(), 1);
+/// assert_eq!(localized.missing[0].id, "unlock-vault.title");
+/// assert_eq!(localized.missing[0].fallback.as_deref(), Some("en"));
+/// ```
+///
+/// [Fluent]: https://projectfluent.org/
+/// [gettext]: https://www.gnu.org/software/gettext/manual/html_node/PO-Files.html
+#[derive(Clone, Debug)]
+pub struct Localizer {
+    default_locale: String,
+    catalogs: HashMap<String, HashMap<String, String>>,
+}
+
+impl Localizer {
+    /// Creates a new localizer with no catalogs, falling back to the given
+    /// locale.
+    pub fn new(default_locale: &str) -> Self {
+        Self {
+            default_locale: normalize_locale(default_locale),
+            catalogs: HashMap::new(),
+        }
+    }
+
+    /// Adds the messages of a Fluent resource to the catalog of `locale`.
+    ///
+    /// Messages that are already present are replaced.
+    pub fn add_fluent(&mut self, locale: &str, source: &str) -> Result<(), CatalogError> {
+        let messages = fluent::parse(source)?;
+        self.catalog(locale).extend(messages);
+        Ok(())
+    }
+
+    /// Adds the messages of a gettext PO file to the catalog of `locale`.
+    ///
+    /// Messages that are already present are replaced. Untranslated and fuzzy
+    /// entries are skipped.
+    pub fn add_po(&mut self, locale: &str, source: &str) -> Result<(), CatalogError> {
+        let messages = po::parse(source)?;
+        self.catalog(locale).extend(messages);
+        Ok(())
+    }
+
+    /// Adds the messages of the file at `path` to the catalog of `locale`.
+    ///
+    /// The format is determined by the file extension, which must be either
+    /// `ftl` or `po`.
+    pub fn load_file(&mut self, locale: &str, path: impl AsRef<Path>) -> Result<(), CatalogError> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        let add = match extension {
+            Some("ftl") => Self::add_fluent,
+            Some("po") => Self::add_po,
+            _ => return Err(CatalogError::UnsupportedFormat),
+        };
+
+        let source = fs::read_to_string(path).map_err(CatalogError::Io)?;
+        add(self, locale, &source)
+    }
+
+    /// Returns the locales that are searched for a message requested in
+    /// `locale`, in order.
+    ///
+    /// For example, with a default locale of `en`, the chain for `pt_BR.UTF-8`
+    /// is `pt-br`, `pt`, `en`. Locales are normalized to lowercase BCP 47 tags.
+    pub fn fallback_chain(&self, locale: &str) -> Vec<String> {
+        let mut chain = parent_locales(locale);
+        if !chain.contains(&self.default_locale) {
+            chain.push(self.default_locale.clone());
+        }
+
+        chain
+    }
+
+    /// Resolves `key` to a text in the given locale.
+    ///
+    /// Returns an error if the reason of `key` is missing from every locale in
+    /// the fallback chain of `locale`, or if the resolved text is invalid, see
+    /// [`Text::validate`](crate::Text::validate).
+    ///
+    /// ```
+    /// use robius_authentication::{Localizer, ResolveError, TextKey};
+    ///
+    /// let localizer = Localizer::new("en");
+    /// let Err(ResolveError::Missing(missing)) = localizer.resolve(TextKey("unlock-vault"), "pt")
+    /// else {
+    ///     panic!("the key is not in any catalog");
+    /// };
+    /// assert_eq!(missing.id, "unlock-vault");
+    /// assert_eq!(missing.fallback, None);
+    /// ```
+    pub fn resolve(&self, key: TextKey<'_>, locale: &str) -> Result<Localized, ResolveError> {
+        let chain = self.fallback_chain(locale);
+        let requested = &chain[0];
+        let parents = parent_locales(locale);
+        let mut missing = Vec::new();
+
+        let mut lookup = |id: String| {
+            let (found_in, message) = chain.iter().find_map(|locale| {
+                let message = self.catalogs.get(locale)?.get(&id)?;
+                Some((locale, message.clone()))
+            })?;
+            if !parents.contains(found_in) {
+                missing.push(MissingTranslation {
+                    id,
+                    locale: requested.clone(),
+                    fallback: Some(found_in.clone()),
+                });
+            }
+            Some(message)
+        };
+
+        let Some(reason) = lookup(key.0.to_owned()) else {
+            return Err(ResolveError::Missing(MissingTranslation {
+                id: key.0.to_owned(),
+                locale: requested.clone(),
+                fallback: None,
+            }));
+        };
+
+        let mut builder = TextBuilder::new().reason(reason);
+        for attribute in ATTRIBUTES {
+            let mut message = lookup(format!("{}.{attribute}", key.0));
+            if message.is_none() && attribute == APP_NAME {
+                message = lookup(APP_NAME.to_owned());
+            }
+            let Some(message) = message else {
+                continue;
+            };
+
+            builder = match attribute {
+                "title" => builder.title(message),
+                "app-name" => builder.app_name(message),
+                "android-title" => builder.android_title(message),
+                "android-subtitle" => builder.android_subtitle(message),
+                "android-description" => builder.android_description(message),
+                "apple" => builder.apple(message),
+                "linux-message" => builder.linux_message(message),
+                "windows-title" => builder.windows_title(message),
+                "windows-description" => builder.windows_description(message),
+                _ => unreachable!(),
+            };
+        }
+
+        Ok(Localized {
+            text: builder.build_valid().map_err(ResolveError::Invalid)?,
+            missing,
+        })
+    }
+
+    /// Resolves `key` to a text in the current locale, see [`system_locale`].
+    ///
+    /// Falls back to the default locale if the current locale is unknown.
+    pub fn resolve_current(&self, key: TextKey<'_>) -> Result<Localized, ResolveError> {
+        let locale = system_locale().unwrap_or_else(|| self.default_locale.clone());
+        self.resolve(key, &locale)
+    }
+
+    fn catalog(&mut self, locale: &str) -> &mut HashMap<String, String> {
+        self.catalogs.entry(normalize_locale(locale)).or_default()
+    }
+}
+
+/// A text resolved by a [`Localizer`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Localized {
+    /// The resolved text.
+    pub text: TextBuf,
+    /// The messages that were not available in the requested locale.
+    pub missing: Vec<MissingTranslation>,
+}
+
+/// A message that is not translated into the requested locale.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MissingTranslation {
+    /// The name of the message, e.g., `unlock-vault.title`.
+    pub id: String,
+    /// The requested locale.
+    pub locale: String,
+    /// The locale that the message was taken from instead, or `None` if no
+    /// locale in the fallback chain has the message.
+    pub fallback: Option<String>,
+}
+
+impl fmt::Display for MissingTranslation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "message `{}` is not translated into {}", self.id, self.locale)?;
+        match &self.fallback {
+            Some(fallback) => write!(f, ", using {fallback} instead"),
+            None => write!(f, " or any fallback locale"),
+        }
+    }
+}
+
+/// An error produced while resolving a [`TextKey`], see [`Localizer::resolve`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ResolveError {
+    /// The reason of the key is missing from every locale in the fallback
+    /// chain, so its [`MissingTranslation::fallback`] is `None`.
+    Missing(MissingTranslation),
+    /// The resolved text is invalid on some platform, e.g., because a
+    /// translation is empty.
+    Invalid(Vec<TextIssue>),
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing(missing) => missing.fmt(f),
+            Self::Invalid(issues) => {
+                let issues: Vec<_> = issues.iter().map(ToString::to_string).collect();
+                write!(f, "resolved text is invalid: {}", issues.join("; "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
+/// An error produced while loading a message catalog.
+#[derive(Debug)]
+pub enum CatalogError {
+    /// The catalog file could not be read.
+    Io(io::Error),
+    /// The catalog is malformed.
+    Parse { line: usize, message: &'static str },
+    /// The catalog file extension is neither `ftl` nor `po`.
+    UnsupportedFormat,
+}
+
+impl fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to read catalog: {e}"),
+            Self::Parse { line, message } => write!(f, "line {line}: {message}"),
+            Self::UnsupportedFormat => f.write_str("unsupported catalog format"),
+        }
+    }
+}
+
+impl std::error::Error for CatalogError {}
+
+/// Returns the locale of the current user, if known.
+///
+/// The locale is read from the `LC_ALL`, `LC_MESSAGES` and `LANG` environment
+/// variables, in that order, as is done on Linux. The `C` and `POSIX` locales
+/// are ignored. On other targets, the locale should be obtained from the
+/// platform and passed to [`Localizer::resolve`] instead.
+pub fn system_locale() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .filter(|value| {
+            let locale = normalize_locale(value);
+            !locale.is_empty() && locale != "c" && locale != "posix"
+        })
+}
+
+/// Returns the given locale followed by its parents, e.g., `pt-br` and `pt`.
+fn parent_locales(locale: &str) -> Vec<String> {
+    let mut locales = Vec::new();
+
+    let mut locale = normalize_locale(locale);
+    while !locale.is_empty() {
+        locales.push(locale.clone());
+        match locale.rfind('-') {
+            Some(index) => locale.truncate(index),
+            None => break,
+        }
+    }
+
+    locales
+}
+
+/// Converts a POSIX locale (e.g., `pt_BR.UTF-8@euro`) or a BCP 47 tag (e.g.,
+/// `pt-BR`) to a lowercase BCP 47 tag (e.g., `pt-br`).
+fn normalize_locale(locale: &str) -> String {
+    let end = locale.find(['.', '@']).unwrap_or(locale.len());
+    locale[..end].trim().replace('_', "-").to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{TextField, TextIssueKind};
+
+    #[test]
+    fn missing_reason_is_reported() {
+        let mut localizer = Localizer::new("en");
+        localizer.add_fluent("en", "other = do something\n").unwrap();
+        assert_eq!(
+            localizer.resolve(TextKey("unlock-vault"), "pt_BR"),
+            Err(ResolveError::Missing(MissingTranslation {
+                id: "unlock-vault".to_owned(),
+                locale: "pt-br".to_owned(),
+                fallback: None,
+            }))
+        );
+    }
+
+    #[test]
+    fn invalid_text_is_reported() {
+        let mut localizer = Localizer::new("en");
+        localizer
+            .add_fluent("en", "unlock-vault = unlock your vault\n")
+            .unwrap();
+        localizer
+            .add_po("pt", "msgid \"unlock-vault\"\nmsgstr \"desbloquear\\n\u{202e}cofre\"\n")
+            .unwrap();
+
+        let Err(ResolveError::Invalid(issues)) = localizer.resolve(TextKey("unlock-vault"), "pt")
+        else {
+            panic!("the translation is invalid");
+        };
+        assert!(issues.iter().any(|issue| issue.field == TextField::Apple
+            && matches!(issue.kind, TextIssueKind::ControlCharacter { .. })));
+        assert!(issues
+            .iter()
+            .any(|issue| matches!(issue.kind, TextIssueKind::BidiControl { .. })));
+    }
+
+    #[test]
+    fn fallback_chain() {
+        let localizer = Localizer::new("en_US");
+        assert_eq!(
+            localizer.fallback_chain("pt_BR.UTF-8@euro"),
+            ["pt-br", "pt", "en-us"]
+        );
+        assert_eq!(localizer.fallback_chain("en-US"), ["en-us", "en"]);
+    }
+}
@@ -0,0 +1,207 @@
+//! A parser for the subset of [Fluent](https://projectfluent.org/) syntax used
+//! by prompt text catalogs.
+
+use super::CatalogError;
+
+/// Parses a Fluent resource into a list of message names and values.
+///
+/// Attributes are returned as `message.attribute`. Terms are skipped, as they
+/// can only be referenced from placeables.
+pub(super) fn parse(source: &str) -> Result<Vec<(String, String)>, CatalogError> {
+    let mut messages = Vec::new();
+    // The name and value lines of the message or attribute being parsed.
+    let mut current: Option<(String, Vec<&str>)> = None;
+    // The name of the message whose attributes are being parsed.
+    let mut message = None;
+
+    let mut finish = |current: &mut Option<(String, Vec<&str>)>| {
+        if let Some((name, lines)) = current.take() {
+            messages.push((name, join_lines(&lines)));
+        }
+    };
+
+    for (index, line) in source.lines().enumerate() {
+        let error = |message| CatalogError::Parse {
+            line: index + 1,
+            message,
+        };
+
+        if line.trim().is_empty() {
+            // Blank lines are part of a multiline value unless it ends.
+            if let Some((_, lines)) = &mut current {
+                lines.push("");
+            }
+            continue;
+        }
+
+        if !line.starts_with([' ', '\t']) {
+            finish(&mut current);
+            message = None;
+
+            if line.starts_with('#') {
+                continue;
+            }
+            let (name, value) = line.split_once('=').ok_or(error("expected `=`"))?;
+            let name = name.trim_end();
+            if let Some(term) = name.strip_prefix('-') {
+                if !is_identifier(term) {
+                    return Err(error("invalid term name"));
+                }
+                // Terms are skipped, but their attributes must still be parsed.
+                message = Some(None);
+                current = None;
+                continue;
+            }
+            if !is_identifier(name) {
+                return Err(error("invalid message name"));
+            }
+
+            message = Some(Some(name.to_owned()));
+            current = Some((name.to_owned(), vec![value.trim_start()]));
+            continue;
+        }
+
+        let indented = line.trim_start();
+        if let Some(attribute) = indented.strip_prefix('.') {
+            finish(&mut current);
+
+            let Some(parent) = &message else {
+                return Err(error("attribute without a message"));
+            };
+            let (name, value) = attribute
+                .split_once('=')
+                .ok_or(error("expected `=`"))?;
+            let name = name.trim_end();
+            if !is_identifier(name) {
+                return Err(error("invalid attribute name"));
+            }
+
+            if let Some(parent) = parent {
+                current = Some((format!("{parent}.{name}"), vec![value.trim_start()]));
+            }
+            continue;
+        }
+
+        match &mut current {
+            Some((_, lines)) => lines.push(line),
+            None if message == Some(None) => {}
+            None => return Err(error("unexpected indented line")),
+        }
+    }
+    finish(&mut current);
+
+    Ok(messages)
+}
+
+/// Joins the lines of a multiline value, removing their common indentation.
+fn join_lines(lines: &[&str]) -> String {
+    let mut lines = lines.to_vec();
+    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+    // The first line is on the same line as the `=`, so it isn't indented.
+    let first = lines.first().copied().unwrap_or_default();
+    let rest = lines.get(1..).unwrap_or_default();
+
+    let indent = rest
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+
+    let mut value = first.trim_end().to_owned();
+    for line in rest {
+        if !value.is_empty() {
+            value.push('\n');
+        }
+        value.push_str(line.get(indent..).unwrap_or_default().trim_end());
+    }
+    value
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(source: &str) -> Vec<(String, String)> {
+        parse(source).unwrap()
+    }
+
+    fn entry(name: &str, value: &str) -> (String, String) {
+        (name.to_owned(), value.to_owned())
+    }
+
+    #[test]
+    fn messages_and_attributes() {
+        let source = "\
+# A comment.
+unlock-vault = unlock your vault
+    .title = Unlock vault
+    .app-name = Vault
+
+app-name = Vault
+";
+        assert_eq!(
+            messages(source),
+            [
+                entry("unlock-vault", "unlock your vault"),
+                entry("unlock-vault.title", "Unlock vault"),
+                entry("unlock-vault.app-name", "Vault"),
+                entry("app-name", "Vault"),
+            ]
+        );
+    }
+
+    #[test]
+    fn multiline_values() {
+        let source = "\
+long =
+    first line
+    second line
+
+        indented
+    .title = Title
+inline = starts here
+  and continues
+";
+        assert_eq!(
+            messages(source),
+            [
+                entry("long", "first line\nsecond line\n\n    indented"),
+                entry("long.title", "Title"),
+                entry("inline", "starts here\nand continues"),
+            ]
+        );
+    }
+
+    #[test]
+    fn terms_are_skipped() {
+        let source = "\
+-brand = Vault
+    .gender = neuter
+    continued
+unlock = unlock the vault
+";
+        assert_eq!(messages(source), [entry("unlock", "unlock the vault")]);
+    }
+
+    #[test]
+    fn errors() {
+        let line = |source| match parse(source) {
+            Err(CatalogError::Parse { line, .. }) => line,
+            result => panic!("unexpected result: {result:?}"),
+        };
+        assert_eq!(line("ok = fine\nmissing equals\n"), 2);
+        assert_eq!(line("  .title = orphan\n"), 1);
+        assert_eq!(line("1invalid = name\n"), 1);
+        assert_eq!(line("# comment\n  indented\n"), 2);
+        assert_eq!(line("ok = fine\n    .bad name = x\n"), 2);
+    }
+}
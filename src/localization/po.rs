@@ -0,0 +1,199 @@
+//! A parser for [gettext PO files].
+//!
+//! [gettext PO files]: https://www.gnu.org/software/gettext/manual/html_node/PO-Files.html
+
+use super::CatalogError;
+
+/// The keyword of the string currently being parsed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Keyword {
+    Context,
+    Id,
+    IdPlural,
+    Str,
+    OtherPluralStr,
+}
+
+#[derive(Default)]
+struct Entry {
+    context: Option<String>,
+    id: Option<String>,
+    string: Option<String>,
+    fuzzy: bool,
+}
+
+/// Parses a PO file into a list of message IDs and translations.
+///
+/// Entries with a context are keyed as `context\u{4}id`, as gettext does. Only
+/// the first plural form is used.
+pub(super) fn parse(source: &str) -> Result<Vec<(String, String)>, CatalogError> {
+    let mut messages = Vec::new();
+    let mut entry = Entry::default();
+    let mut keyword = None;
+
+    let mut finish = |entry: Entry| {
+        let (Some(id), Some(string)) = (entry.id, entry.string) else {
+            return;
+        };
+        // The header has an empty ID, and untranslated entries have an empty
+        // string.
+        if id.is_empty() || string.is_empty() || entry.fuzzy {
+            return;
+        }
+        let id = match entry.context {
+            Some(context) => format!("{context}\u{4}{id}"),
+            None => id,
+        };
+        messages.push((id, string));
+    };
+
+    for (index, line) in source.lines().enumerate() {
+        let error = |message| CatalogError::Parse {
+            line: index + 1,
+            message,
+        };
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(comment) = line.strip_prefix('#') {
+            if let Some(flags) = comment.strip_prefix(',') {
+                if keyword.is_some() {
+                    finish(std::mem::take(&mut entry));
+                    keyword = None;
+                }
+                entry.fuzzy |= flags.split(',').any(|flag| flag.trim() == "fuzzy");
+            }
+            continue;
+        }
+
+        let (next, string) = if line.starts_with('"') {
+            (keyword.ok_or(error("string without a keyword"))?, line)
+        } else {
+            let (word, string) = line.split_once([' ', '\t']).ok_or(error("expected a string"))?;
+            let next = match word {
+                "msgctxt" => Keyword::Context,
+                "msgid" => Keyword::Id,
+                "msgid_plural" => Keyword::IdPlural,
+                "msgstr" | "msgstr[0]" => Keyword::Str,
+                _ if word.starts_with("msgstr[") => Keyword::OtherPluralStr,
+                _ => return Err(error("unknown keyword")),
+            };
+            // A `msgctxt` or `msgid` starts a new entry.
+            let starts_entry = matches!(next, Keyword::Context)
+                || (next == Keyword::Id && keyword != Some(Keyword::Context));
+            if starts_entry && keyword.is_some() {
+                finish(std::mem::take(&mut entry));
+            }
+            (next, string.trim_start())
+        };
+        keyword = Some(next);
+
+        let string = unescape(string).ok_or(error("malformed string"))?;
+        let target = match next {
+            Keyword::Context => &mut entry.context,
+            Keyword::Id => &mut entry.id,
+            Keyword::Str => &mut entry.string,
+            Keyword::IdPlural | Keyword::OtherPluralStr => continue,
+        };
+        target.get_or_insert_with(String::new).push_str(&string);
+    }
+    finish(entry);
+
+    Ok(messages)
+}
+
+/// Unescapes a quoted C string.
+fn unescape(string: &str) -> Option<String> {
+    let string = string.strip_prefix('"')?.strip_suffix('"')?;
+    let mut unescaped = String::with_capacity(string.len());
+
+    let mut chars = string.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        unescaped.push(match chars.next()? {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '"' => '"',
+            '\\' => '\\',
+            _ => return None,
+        });
+    }
+
+    Some(unescaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, string: &str) -> (String, String) {
+        (id.to_owned(), string.to_owned())
+    }
+
+    #[test]
+    fn entries() {
+        let source = r#"
+msgid ""
+msgstr ""
+"Language: pt\n"
+
+#: src/main.rs:10
+msgid "unlock-vault"
+msgstr ""
+"desbloquear "
+"o seu cofre"
+
+msgctxt "menu"
+msgid "open"
+msgstr "abrir"
+
+#, fuzzy
+msgid "delete"
+msgstr "apagar"
+
+#, c-format, fuzzy
+msgctxt "menu"
+msgid "close"
+msgstr "fechar"
+
+msgid "file"
+msgid_plural "files"
+msgstr[0] "ficheiro"
+msgstr[1] "ficheiros"
+
+msgid "untranslated"
+msgstr ""
+
+msgid "escaped"
+msgstr "a \"b\"\n\tc\\"
+"#;
+        assert_eq!(
+            parse(source).unwrap(),
+            [
+                entry("unlock-vault", "desbloquear o seu cofre"),
+                entry("menu\u{4}open", "abrir"),
+                entry("file", "ficheiro"),
+                entry("escaped", "a \"b\"\n\tc\\"),
+            ]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let line = |source| match parse(source) {
+            Err(CatalogError::Parse { line, .. }) => line,
+            result => panic!("unexpected result: {result:?}"),
+        };
+        assert_eq!(line("\"orphan\"\n"), 1);
+        assert_eq!(line("msgid \"a\"\nmsgtranslation \"b\"\n"), 2);
+        assert_eq!(line("msgid \"a\"\nmsgstr \"unterminated\n"), 2);
+        assert_eq!(line("msgid \"a\"\nmsgstr \"\\q\"\n"), 2);
+        assert_eq!(line("msgid\n"), 1);
+    }
+}
@@ -0,0 +1,502 @@
+//! Named actions with their own prompt text and policy.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    Clock, Context, Error, Identity, LinuxBackend, Policy, PolicyBuilder, PolicyError, Result,
+    SystemClock, TextBuf,
+};
+
+/// A sensitive operation of the app that requires authentication, see
+/// [`ActionRegistry`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Action {
+    pub(crate) text: TextBuf,
+    pub(crate) policy: PolicyBuilder,
+    pub(crate) reuse_window: Option<Duration>,
+    pub(crate) audit_label: Option<String>,
+}
+
+impl Action {
+    /// Returns a new action that prompts with the given text, using the
+    /// default policy.
+    #[inline]
+    pub fn new(text: TextBuf) -> Self {
+        Self {
+            text,
+            policy: PolicyBuilder::new(),
+            reuse_window: None,
+            audit_label: None,
+        }
+    }
+
+    /// Sets the policy used to authenticate.
+    ///
+    /// The policy is validated when the action is registered.
+    #[inline]
+    #[must_use]
+    pub fn with_policy(self, policy: PolicyBuilder) -> Self {
+        Self { policy, ..self }
+    }
+
+    /// Sets how long a successful authentication is reused for the action,
+    /// i.e., how long the user isn't prompted again.
+    ///
+    /// By default, the user is prompted every time.
+    #[inline]
+    #[must_use]
+    pub fn with_reuse_window(self, reuse_window: Option<Duration>) -> Self {
+        Self {
+            reuse_window,
+            ..self
+        }
+    }
+
+    /// Sets the label identifying the action in audit records, instead of its
+    /// id.
+    #[inline]
+    #[must_use]
+    pub fn with_audit_label(self, audit_label: impl Into<String>) -> Self {
+        Self {
+            audit_label: Some(audit_label.into()),
+            ..self
+        }
+    }
+
+    /// Returns the text of the prompt.
+    #[inline]
+    pub fn text(&self) -> &TextBuf {
+        &self.text
+    }
+
+    /// Returns the policy used to authenticate.
+    #[inline]
+    pub fn policy(&self) -> &PolicyBuilder {
+        &self.policy
+    }
+
+    /// Returns how long a successful authentication is reused.
+    #[inline]
+    pub fn reuse_window(&self) -> Option<Duration> {
+        self.reuse_window
+    }
+
+    /// Returns the label identifying the action in audit records, if it was
+    /// set.
+    #[inline]
+    pub fn audit_label(&self) -> Option<&str> {
+        self.audit_label.as_deref()
+    }
+}
+
+/// The actions of an app by id, see [`Context::authenticate_action`].
+///
+/// Instead of scattering [`Text`](crate::Text) and [`Policy`] values across
+/// the app, each sensitive operation is registered once, or loaded from a
+/// configuration file with the `serde` feature. The registry is also the
+/// source of the app's polkit actions on Linux, see [`Self::polkit_policy`].
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use robius_authentication::{Action, ActionRegistry, Context, PolicyBuilder, Text};
+///
+/// let mut actions = ActionRegistry::new();
+/// actions
+///     .register(
+///         "export-keys",
+///         Action::new(Text::from_reason("export your keys").unwrap())
+///             .with_policy(PolicyBuilder::new().biometrics(None))
+///             .with_reuse_window(Some(Duration::from_secs(60))),
+///     )
+///     .unwrap();
+///
+/// let context = Context::new(()).with_actions(actions);
+/// # if false {
+/// context.authenticate_action("export-keys", |result| println!("{result:?}"))?;
+/// # }
+/// # Ok::<_, robius_authentication::Error>(())
+/// ```
+pub struct ActionRegistry {
+    actions: BTreeMap<String, Entry>,
+    authenticated: Authenticated,
+    clock: Arc<dyn Clock>,
+}
+
+/// When each action was last authenticated, if it is reused, shared with the
+/// pending authentications and the invalidation watches.
+#[derive(Clone, Default)]
+pub(crate) struct Authenticated(Arc<Mutex<AuthenticatedInner>>);
+
+#[derive(Default)]
+struct AuthenticatedInner {
+    times: HashMap<String, Instant>,
+    /// Incremented whenever the authentications are revoked, so that
+    /// authentications pending at the time are not recorded.
+    generation: u64,
+}
+
+impl Authenticated {
+    fn lock(&self) -> MutexGuard<'_, AuthenticatedInner> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns the current generation, to be passed to [`Self::record`] once
+    /// the authentication succeeds.
+    pub(crate) fn generation(&self) -> u64 {
+        self.lock().generation
+    }
+
+    /// Records that the action was authenticated at `now`, unless the
+    /// authentications were revoked since `generation` was obtained.
+    pub(crate) fn record(&self, id: &str, generation: u64, now: Instant) {
+        let mut inner = self.lock();
+        if inner.generation == generation {
+            inner.times.insert(id.to_owned(), now);
+        }
+    }
+
+    /// Forgets all authentications, including those still pending.
+    pub(crate) fn revoke(&self) {
+        let mut inner = self.lock();
+        inner.times.clear();
+        inner.generation += 1;
+    }
+}
+
+struct Entry {
+    action: Action,
+    policy: Policy,
+}
+
+impl fmt::Debug for ActionRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.actions.iter().map(|(id, entry)| (id, &entry.action)))
+            .finish()
+    }
+}
+
+impl Default for ActionRegistry {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ActionRegistry {
+    /// Returns a new, empty registry.
+    pub fn new() -> Self {
+        Self {
+            actions: BTreeMap::new(),
+            authenticated: Authenticated::default(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Sets the clock used to measure reuse windows, e.g., a
+    /// [`ManualClock`](crate::ManualClock) in tests.
+    #[must_use]
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Registers the action with the given id, replacing any action
+    /// previously registered with it.
+    ///
+    /// Returns an error if the action's policy is not valid for the current
+    /// target, see [`PolicyBuilder::try_build`].
+    pub fn register(
+        &mut self,
+        id: impl Into<String>,
+        action: Action,
+    ) -> std::result::Result<(), PolicyError> {
+        let id = id.into();
+        let policy = action.policy.try_build_ref()?;
+        self.authenticated.lock().times.remove(&id);
+        self.actions.insert(id, Entry { action, policy });
+        Ok(())
+    }
+
+    /// Returns the action registered with the given id.
+    #[inline]
+    pub fn get(&self, id: &str) -> Option<&Action> {
+        self.actions.get(id).map(|entry| &entry.action)
+    }
+
+    /// Returns the registered actions and their ids, ordered by id.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Action)> {
+        self.actions
+            .iter()
+            .map(|(id, entry)| (id.as_str(), &entry.action))
+    }
+
+    /// Forgets all successful authentications, so that every action prompts
+    /// again, regardless of its reuse window. Authentications that are still
+    /// pending are not reused either.
+    ///
+    /// This is also done by [`Context::revoke_cached_authorization`], when
+    /// the user walks away (see `Context::watch_invalidation` on Linux) and
+    /// when an [`AppLock`](crate::AppLock) is locked.
+    pub fn revoke(&self) {
+        self.authenticated.revoke();
+    }
+
+    /// Returns the authentications of the reused actions.
+    pub(crate) fn authenticated(&self) -> &Authenticated {
+        &self.authenticated
+    }
+
+    /// Returns the contents of a polkit policy file declaring the polkit
+    /// actions used by the registered actions.
+    ///
+    /// The file is installed in `/usr/share/polkit-1/actions`, see
+    /// [`LinuxBackend::Polkit`]. Actions using the PAM backend are skipped.
+    /// Each polkit action is declared once, by the first action using it in
+    /// order of id, so actions should use distinct polkit actions.
+    ///
+    /// The polkit action uses `auth_self`, or `auth_admin` if the identity is
+    /// not the current user. If the action has a reuse window, the `_keep`
+    /// variant is used, so that polkit also retains the authorization; note
+    /// that polkit retains it for a fixed period of about five minutes.
+    ///
+    /// If an `owner` is given, e.g., the user running a system service, the
+    /// actions are annotated with `org.freedesktop.policykit.owner`, so that
+    /// the [`Text`](crate::Text) of the actions is displayed when the owner
+    /// authenticates. Otherwise, the agent displays the message declared in
+    /// the file, see [`LinuxBackend::Polkit`].
+    ///
+    /// ```
+    /// use robius_authentication::{Action, ActionRegistry, Text};
+    ///
+    /// let mut actions = ActionRegistry::new();
+    /// let text = Text::from_reason("unlock the vault").unwrap();
+    /// actions.register("unlock", Action::new(text)).unwrap();
+    ///
+    /// let policy = actions.polkit_policy(Some("vault"));
+    /// assert!(policy.contains(
+    ///     r#"<annotate key="org.freedesktop.policykit.owner">unix-user:vault</annotate>"#
+    /// ));
+    /// ```
+    pub fn polkit_policy(&self, owner: Option<&str>) -> String {
+        let mut policy = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<!DOCTYPE policyconfig PUBLIC\n",
+            " \"-//freedesktop//DTD PolicyKit Policy Configuration 1.0//EN\"\n",
+            " \"http://www.freedesktop.org/standards/PolicyKit/1/policyconfig.dtd\">\n",
+            "<policyconfig>\n",
+        ));
+        let mut declared = Vec::new();
+        for (id, entry) in &self.actions {
+            let Action {
+                text,
+                policy: builder,
+                reuse_window,
+                audit_label,
+            } = &entry.action;
+            let LinuxBackend::Polkit { action_id } = &builder.linux_backend else {
+                continue;
+            };
+            let action_id = LinuxBackend::polkit_action_id(action_id, &builder.identity);
+            if declared.contains(&action_id) {
+                continue;
+            }
+            declared.push(action_id);
+
+            let message = match text.linux_message.as_str() {
+                "" => "Authentication is required",
+                message => message,
+            };
+            let mut defaults = match builder.identity {
+                Identity::CurrentUser => String::from("auth_self"),
+                _ => String::from("auth_admin"),
+            };
+            if reuse_window.is_some() {
+                defaults.push_str("_keep");
+            }
+
+            policy.push_str(&format!("  <action id=\"{}\">\n", escape(action_id)));
+            policy.push_str(&format!(
+                "    <description>{}</description>\n",
+                escape(audit_label.as_deref().unwrap_or(id)),
+            ));
+            policy.push_str(&format!("    <message>{}</message>\n", escape(message)));
+            if let Some(icon_name) = &text.linux_icon_name {
+                policy.push_str(&format!(
+                    "    <icon_name>{}</icon_name>\n",
+                    escape(icon_name)
+                ));
+            }
+            policy.push_str("    <defaults>\n");
+            for allow in ["allow_any", "allow_inactive", "allow_active"] {
+                policy.push_str(&format!("      <{allow}>{defaults}</{allow}>\n"));
+            }
+            policy.push_str("    </defaults>\n");
+            if let Some(owner) = owner {
+                policy.push_str(&format!(
+                    "    <annotate key=\"org.freedesktop.policykit.owner\">unix-user:{}</annotate>\n",
+                    escape(owner)
+                ));
+            }
+            policy.push_str("  </action>\n");
+        }
+        policy.push_str("</policyconfig>\n");
+        policy
+    }
+
+    /// Returns whether the action was authenticated within its reuse window.
+    fn is_reused(&self, id: &str, action: &Action) -> bool {
+        let Some(reuse_window) = action.reuse_window else {
+            return false;
+        };
+        self.authenticated
+            .lock()
+            .times
+            .get(id)
+            .is_some_and(|&authenticated| {
+                self.clock.now().saturating_duration_since(authenticated) < reuse_window
+            })
+    }
+}
+
+/// Escapes the text for use in XML.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl Context {
+    /// Sets the actions that can be authenticated using
+    /// [`Self::authenticate_action`].
+    #[inline]
+    #[must_use]
+    pub fn with_actions(mut self, actions: ActionRegistry) -> Self {
+        self.actions = actions;
+        self
+    }
+
+    /// Returns the actions that can be authenticated using
+    /// [`Self::authenticate_action`].
+    #[inline]
+    pub fn actions(&self) -> &ActionRegistry {
+        &self.actions
+    }
+
+    /// Displays the authentication prompt of the action registered with the
+    /// given id, like [`Self::authenticate`].
+    ///
+    /// If the action was successfully authenticated within its reuse window,
+    /// the `callback` is invoked immediately without prompting.
+    ///
+    /// Returns [`Error::UnknownAction`] if no action is registered with the
+    /// id.
+    pub fn authenticate_action<F>(&self, id: &str, callback: F) -> Result<()>
+    where
+        F: Fn(Result<()>) + Send + 'static,
+    {
+        let Some(entry) = self.actions.actions.get(id) else {
+            return Err(Error::UnknownAction);
+        };
+        let label = entry.action.audit_label.as_deref().unwrap_or(id);
+        if self.actions.is_reused(id, &entry.action) {
+            let text = entry.action.text.as_text();
+            self.audit
+                .requested(Some(label), Some(&entry.action.policy), Some(&text))
+                .reused();
+            callback(Ok(()));
+            return Ok(());
+        }
+
+        let reused = entry.action.reuse_window.is_some().then(|| {
+            let authenticated = self.actions.authenticated.clone();
+            let generation = authenticated.generation();
+            (
+                id.to_owned(),
+                authenticated,
+                generation,
+                self.actions.clock.clone(),
+            )
+        });
+        let text = entry.action.text.as_text();
+        self.authenticate_audited(text, &entry.policy, Some(label), move |result| {
+            if let (Ok(_), Some((id, authenticated, generation, clock))) = (&result, &reused) {
+                authenticated.record(id, *generation, clock.now());
+            }
+            callback(result.map(|_| ()));
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ManualClock;
+
+    fn registry(clock: &ManualClock) -> ActionRegistry {
+        let mut registry = ActionRegistry::new().with_clock(clock.clone());
+        let text = TextBuf::new().linux_message("Unlock the vault");
+        let action = Action::new(text).with_reuse_window(Some(Duration::from_secs(60)));
+        registry.register("unlock", action).unwrap();
+        registry
+    }
+
+    #[test]
+    fn reuse_window() {
+        let clock = ManualClock::new();
+        let registry = registry(&clock);
+        let action = registry.get("unlock").unwrap();
+        assert!(!registry.is_reused("unlock", action));
+
+        let generation = registry.authenticated().generation();
+        registry
+            .authenticated()
+            .record("unlock", generation, clock.now());
+        clock.advance(Duration::from_secs(59));
+        assert!(registry.is_reused("unlock", action));
+        clock.advance(Duration::from_secs(1));
+        assert!(!registry.is_reused("unlock", action));
+    }
+
+    #[test]
+    fn revoke() {
+        let clock = ManualClock::new();
+        let registry = registry(&clock);
+        let action = registry.get("unlock").unwrap();
+        let generation = registry.authenticated().generation();
+        registry
+            .authenticated()
+            .record("unlock", generation, clock.now());
+        registry.revoke();
+        assert!(!registry.is_reused("unlock", action));
+    }
+
+    #[test]
+    fn pending_authentications_are_revoked() {
+        let clock = ManualClock::new();
+        let registry = registry(&clock);
+        let action = registry.get("unlock").unwrap();
+        // Revoked while the user was being prompted.
+        let generation = registry.authenticated().generation();
+        registry.revoke();
+        registry
+            .authenticated()
+            .record("unlock", generation, clock.now());
+        assert!(!registry.is_reused("unlock", action));
+    }
+}
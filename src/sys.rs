@@ -5,9 +5,9 @@ cfg_if::cfg_if! {
     } else if #[cfg(target_vendor = "apple")] {
         mod apple;
         pub(crate) use apple::*;
-    // } else if #[cfg(target_os = "linux")] { // linux is currently unsupported
-    //     mod linux;
-    //     pub(crate) use linux::*;
+    } else if #[cfg(target_os = "linux")] {
+        mod linux;
+        pub(crate) use linux::*;
     } else if #[cfg(target_os = "windows")] {
         mod windows;
         pub(crate) use windows::*;
@@ -3,19 +3,20 @@
 package robius.authentication;
 
 import android.hardware.biometrics.BiometricPrompt;
+import android.os.Build;
 
 public class AuthenticationCallback extends BiometricPrompt.AuthenticationCallback {
   private long pointer;
 
   /* TODO: There are neater ways of doing this */
-  private native void rustCallback(long pointer, int errorCode, int helpCode);
+  private native void rustCallback(long pointer, int errorCode, int helpCode, int authenticationType);
 
   public AuthenticationCallback(long pointer) {
     this.pointer = pointer;
   }
 
   public void onAuthenticationError(int errorCode, CharSequence errString) {
-    rustCallback(pointer, errorCode, 0);
+    rustCallback(pointer, errorCode, 0, 0);
   }
 
   /* This is called when the user presents an incorrect authenticator (e.g. fingerprint or password). However, the
@@ -24,10 +25,15 @@ public class AuthenticationCallback extends BiometricPrompt.AuthenticationCallba
   public void onAuthenticationFailed() {}
 
   public void onAuthenticationHelp(int helpCode, CharSequence helpString) {
-    rustCallback(pointer, 0, helpCode);
+    rustCallback(pointer, 0, helpCode, 0);
   }
 
   public void onAuthenticationSucceeded(BiometricPrompt.AuthenticationResult result) {
-    rustCallback(pointer, 0, 0);
+    /* The type of authentication is only reported from API level 30. */
+    int authenticationType = 0;
+    if (Build.VERSION.SDK_INT >= Build.VERSION_CODES.R) {
+      authenticationType = result.getAuthenticationType();
+    }
+    rustCallback(pointer, 0, 0, authenticationType);
   }
 }
\ No newline at end of file
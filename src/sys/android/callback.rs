@@ -6,14 +6,14 @@ use jni::{
     JNIEnv, NativeMethod,
 };
 
-use crate::{Error, Result};
+use crate::{AuthenticationMethod, Error, Result};
 
 const AUTHENTICATION_CALLBACK_BYTECODE: &[u8] =
     include_bytes!(concat!(env!("OUT_DIR"), "/classes.dex"));
 
 
 // NOTE: This must be kept in sync with the signature of `rust_callback`.
-const RUST_CALLBACK_SIGNATURE: &str = "(JII)V";
+const RUST_CALLBACK_SIGNATURE: &str = "(JIII)V";
 
 // NOTE: The signature of this function must be kept in sync with
 // `RUST_CALLBACK_SIGNATURE`.
@@ -23,10 +23,11 @@ unsafe extern "C" fn rust_callback<'a>(
     callback_ptr_ptr: jlong,
     error_code: jint,
     help_code: jint,
+    authentication_type: jint,
 ) {
     // When we constructed the callback, we double-boxed it.
     let callback_ptr_boxed = unsafe {
-        Box::from_raw(callback_ptr_ptr as *mut Box<dyn Fn(Result<()>)>)
+        Box::from_raw(callback_ptr_ptr as *mut Box<dyn Fn(Result<Option<AuthenticationMethod>>)>)
     };
     let callback = *callback_ptr_boxed;
 
@@ -54,7 +55,11 @@ unsafe extern "C" fn rust_callback<'a>(
         // TODO: consider returning a specific retry-able error here.
         Err(Error::Unknown)
     } else {
-        Ok(())
+        Ok(match authentication_type {
+            AUTHENTICATION_RESULT_TYPE_BIOMETRIC => Some(AuthenticationMethod::Biometrics),
+            AUTHENTICATION_RESULT_TYPE_DEVICE_CREDENTIAL => Some(AuthenticationMethod::Password),
+            _ => None,
+        })
     };
     callback(result);
 }
@@ -136,3 +141,7 @@ const BIOMETRIC_ERROR_VENDOR: i32 = 8;
 // NOTE: I don't think onAuthenticationError is ever actually called with this
 // value.
 const BIOMETRIC_NO_AUTHENTICATION: i32 = -1;
+
+// https://developer.android.com/reference/android/hardware/biometrics/BiometricPrompt#AUTHENTICATION_RESULT_TYPE_BIOMETRIC
+const AUTHENTICATION_RESULT_TYPE_BIOMETRIC: i32 = 2;
+const AUTHENTICATION_RESULT_TYPE_DEVICE_CREDENTIAL: i32 = 1;
@@ -5,7 +5,10 @@ use jni::{
     JNIEnv,
 };
 
-use crate::{BiometricStrength, Error, Result, Text};
+use crate::{
+    AuthenticationMethod, BiometricStrength, Error, Identity, LinuxBackend, Outcome, PolicyError,
+    Result, Text,
+};
 
 pub(crate) type RawContext = ();
 
@@ -24,7 +27,7 @@ impl Context {
     // #[cfg(feature = "async")]
     // pub(crate) async fn authenticate_async(
     //     &self,
-    //     text: Text<'_, '_, '_, '_, '_, '_>,
+    //     text: Text<'_>,
     //     policy: &Policy,
     // ) -> Result<()> {
     //     if let Ok(inner) = self.authenticate_inner(text, policy)?.await {
@@ -34,16 +37,23 @@ impl Context {
     //     }
     // }
 
+    pub(crate) fn revoke_cached_authorization(&self) -> Result<()> {
+        // Authorization is never retained.
+        Ok(())
+    }
+
     pub(crate) fn authenticate<F>(
         &self,
         text: Text,
-        policy: &Policy,
+        policy: &crate::Policy,
         callback: F,
     ) -> Result<()>
     where
-        F: Fn(Result<()>) + Send + 'static,
+        F: Fn(Result<Outcome>) + Send + 'static,
     {
-        self.authenticate_inner(text, policy, callback)
+        self.authenticate_inner(text, &policy.inner, move |result| {
+            callback(result.map(|method| Outcome { user: None, method }))
+        })
     }
 
     fn authenticate_inner<F>(
@@ -53,11 +63,12 @@ impl Context {
         callback: F,
     ) -> Result<()>
     where
-        F: Fn(Result<()>) + Send + 'static,
+        F: Fn(Result<Option<AuthenticationMethod>>) + Send + 'static,
     {
         robius_android_env::with_activity(|env, context| {
             let callback_class = callback::get_callback_class(env)?;
-            let callback_boxed_dyn = Box::new(callback) as Box<dyn Fn(Result<()>)>;
+            let callback_boxed_dyn =
+                Box::new(callback) as Box<dyn Fn(Result<Option<AuthenticationMethod>>)>;
             let callback_boxed_boxed_ptr = Box::into_raw(Box::new(callback_boxed_dyn));
             let callback_instance = construct_callback(
                 env,
@@ -101,6 +112,7 @@ pub(crate) struct Policy {
 pub(crate) struct PolicyBuilder {
     biometrics: Option<BiometricStrength>,
     password: bool,
+    current_user: bool,
 }
 
 impl PolicyBuilder {
@@ -108,6 +120,7 @@ impl PolicyBuilder {
         Self {
             biometrics: Some(BiometricStrength::Strong),
             password: true,
+            current_user: true,
         }
     }
 
@@ -127,14 +140,28 @@ impl PolicyBuilder {
         self
     }
 
-    pub(crate) const fn build(self) -> Option<Policy> {
+    pub(crate) const fn linux_backend(self, _: &LinuxBackend) -> Self {
+        self
+    }
+
+    pub(crate) const fn identity(self, identity: &Identity) -> Self {
+        Self {
+            current_user: matches!(identity, Identity::CurrentUser),
+            ..self
+        }
+    }
+
+    pub(crate) const fn try_build(self) -> std::result::Result<Policy, PolicyError> {
+        if !self.current_user {
+            return Err(PolicyError::UnsupportedIdentity);
+        }
         if let Some(strength) = self.biometrics {
-            return Some(Policy {
+            return Ok(Policy {
                 strength,
                 password: self.password,
             });
         }
-        None
+        Err(PolicyError::UnsupportedMethods)
     }
 }
 
@@ -230,3 +257,14 @@ fn construct_biometric_prompt<'a>(
     .l()
     .map_err(|e| e.into())
 }
+
+#[cfg(feature = "audit-chain")]
+pub(crate) fn read_secret(_: &str, _: &str) -> Result<Option<Vec<u8>>> {
+    // The Android keystore doesn't store arbitrary secrets.
+    Err(Error::Unavailable)
+}
+
+#[cfg(feature = "audit-chain")]
+pub(crate) fn write_secret(_: &str, _: &str, _: &[u8]) -> Result<()> {
+    Err(Error::Unavailable)
+}
@@ -5,7 +5,10 @@ use objc2_local_authentication::{LAContext, LAError, LAPolicy};
 // #[cfg(feature = "async")]
 // use tokio::sync::oneshot as channel_impl;
 
-use crate::{BiometricStrength, Error, Result, Text};
+use crate::{
+    AuthenticationMethod, BiometricStrength, Error, Identity, LinuxBackend, Outcome, PolicyError,
+    Result, Text,
+};
 
 pub(crate) type RawContext = ();
 
@@ -25,28 +28,37 @@ impl Context {
     // #[cfg(feature = "async")]
     // pub(crate) async fn authenticate_async(
     //     &self,
-    //     text: Text<'_, '_, '_, '_, '_, '_>,
+    //     text: Text<'_>,
     //     policy: &Policy,
     // ) -> Result<()> {
     //     // The callback should always execute and hence a message will always be sent.
     //     self.authenticate_inner(text, policy).await.unwrap()
     // }
 
+    pub(crate) fn revoke_cached_authorization(&self) -> Result<()> {
+        // Authorization is never retained.
+        Ok(())
+    }
+
     pub(crate) fn authenticate<F>(
         &self,
         text: Text,
-        policy: &Policy,
+        policy: &crate::Policy,
         callback: F,
     ) -> Result<()>
     where
-        F: Fn(Result<()>) + Send + 'static,
+        F: Fn(Result<Outcome>) + Send + 'static,
     {
-        self.authenticate_inner(text, policy, callback)
+        let policy = &policy.inner;
+        let method = policy.method;
+        self.authenticate_inner(text, policy, move |result| {
+            callback(result.map(|()| Outcome { user: None, method }))
+        })
     }
 
     fn authenticate_inner<F>(
         &self,
-        text: Text<'_, '_, '_, '_, '_, '_>,
+        text: Text<'_>,
         policy: &Policy,
         callback: F,
     ) -> Result<()>
@@ -87,6 +99,8 @@ impl Context {
 #[derive(Debug)]
 pub(crate) struct Policy {
     inner: LAPolicy,
+    /// The only method allowed by the policy, if any.
+    method: Option<AuthenticationMethod>,
 }
 
 #[derive(Debug)]
@@ -95,6 +109,7 @@ pub(crate) struct PolicyBuilder {
     _password: bool,
     _companion: bool,
     _wrist_detection: bool,
+    _current_user: bool,
 }
 
 impl PolicyBuilder {
@@ -104,6 +119,7 @@ impl PolicyBuilder {
             _password: true,
             _companion: true,
             _wrist_detection: true,
+            _current_user: true,
         }
     }
 
@@ -135,9 +151,28 @@ impl PolicyBuilder {
         }
     }
 
-    pub(crate) const fn build(self) -> Option<Policy> {
+    pub(crate) const fn linux_backend(self, _: &LinuxBackend) -> Self {
+        self
+    }
+
+    pub(crate) const fn identity(self, identity: &Identity) -> Self {
+        Self {
+            _current_user: matches!(identity, Identity::CurrentUser),
+            ..self
+        }
+    }
+
+    pub(crate) const fn try_build(self) -> std::result::Result<Policy, PolicyError> {
+        if !self._current_user {
+            return Err(PolicyError::UnsupportedIdentity);
+        }
+
         // TODO: Test watchos
 
+        // Only known if the policy allows a single method.
+        #[cfg_attr(target_os = "watchos", allow(unused_mut))]
+        let mut method = None;
+
         #[cfg(target_os = "watchos")]
         let policy = match self {
             Self {
@@ -150,7 +185,7 @@ impl PolicyBuilder {
                 _wrist_detection: false,
                 ..
             } => LAPolicy::DeviceOwnerAuthentication,
-            _ => return None,
+            _ => return Err(PolicyError::UnsupportedMethods),
         };
 
         #[cfg(not(target_os = "watchos"))]
@@ -182,6 +217,7 @@ impl PolicyBuilder {
                 _companion: false,
                 ..
             } => {
+                method = Some(AuthenticationMethod::Biometrics);
                 LAPolicy::DeviceOwnerAuthenticationWithBiometrics
             },
             Self {
@@ -192,15 +228,19 @@ impl PolicyBuilder {
             } => {
                 // This crashes the app on iOS (at least on the simulator).
                 #[cfg(not(target_os = "ios"))] {
+                    method = Some(AuthenticationMethod::Companion);
                     LAPolicy::DeviceOwnerAuthenticationWithCompanion
                 }
                 #[cfg(target_os = "ios")] {
                     LAPolicy::DeviceOwnerAuthentication
                 }
             },
-            _ => return None,
+            _ => return Err(PolicyError::UnsupportedMethods),
         };
-        Some(Policy { inner: policy })
+        Ok(Policy {
+            inner: policy,
+            method,
+        })
     }
 }
 
@@ -228,3 +268,27 @@ impl From<LAError> for Error {
         }
     }
 }
+
+/// The status of keychain operations if the item doesn't exist, i.e.,
+/// `errSecItemNotFound`.
+#[cfg(feature = "audit-chain")]
+const ITEM_NOT_FOUND: i32 = -25300;
+
+/// Returns the secret stored in the keychain for the service and account, if
+/// any.
+#[cfg(feature = "audit-chain")]
+pub(crate) fn read_secret(service: &str, account: &str) -> Result<Option<Vec<u8>>> {
+    match security_framework::passwords::get_generic_password(service, account) {
+        Ok(secret) => Ok(Some(secret)),
+        Err(e) if e.code() == ITEM_NOT_FOUND => Ok(None),
+        Err(_) => Err(Error::Unavailable),
+    }
+}
+
+/// Stores the secret in the keychain for the service and account, replacing
+/// any secret stored for them.
+#[cfg(feature = "audit-chain")]
+pub(crate) fn write_secret(service: &str, account: &str, secret: &[u8]) -> Result<()> {
+    security_framework::passwords::set_generic_password(service, account, secret)
+        .map_err(|_| Error::Unavailable)
+}
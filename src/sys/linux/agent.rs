@@ -0,0 +1,578 @@
+//! An in-process polkit authentication agent, similar to `pkttyagent`.
+//!
+//! Sessions without a desktop environment, e.g., over SSH or in containers,
+//! usually have no authentication agent, in which case polkit fails any check
+//! requiring authentication. The agent is only registered for the current
+//! process and only if no other agent exists for the session.
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    process::{Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use zbus::{
+    blocking::Connection,
+    zvariant::{ObjectPath, Value},
+};
+
+use super::{
+    authority::{self, AuthorityProxy, DbusSubject},
+    SharedConversation,
+};
+use crate::{
+    linux::{Conversation, Subject, Terminal},
+    Error, Identity, Result,
+};
+
+const OBJECT_PATH: &str = "/rs/robius/authentication/AuthenticationAgent";
+
+/// The setuid helper shipped with polkit which authenticates an identity and
+/// reports the result to the authority.
+const HELPER_PATHS: &[&str] = &[
+    "/usr/lib/polkit-1/polkit-agent-helper-1",
+    "/usr/libexec/polkit-agent-helper-1",
+    "/usr/libexec/polkit-1/polkit-agent-helper-1",
+    "/usr/lib/policykit-1/polkit-agent-helper-1",
+];
+
+/// A registered authentication agent, unregistered when dropped.
+pub(super) struct Agent {
+    connection: Connection,
+    object_path: ObjectPath<'static>,
+    subject: DbusSubject,
+    approver: Approver,
+}
+
+/// The name of the user who last authenticated using the agent.
+type Approver = Arc<Mutex<Option<String>>>;
+
+impl Agent {
+    /// Registers a fallback agent for the current process on the given
+    /// connection to the system bus.
+    ///
+    /// Polkit only uses the agent if the session has no other agent.
+    /// Without a conversation, the agent prompts on the controlling terminal.
+    /// Returns `None` if no agent was registered, e.g., because there is no
+    /// way to prompt the user or polkit's helper is not installed.
+    pub(super) fn register(
+        connection: &Connection,
+        conversation: Option<SharedConversation>,
+        identity: Identity,
+    ) -> Option<Self> {
+        let helper = HELPER_PATHS
+            .iter()
+            .map(Path::new)
+            .find(|path| path.exists())?;
+        Self::register_with_helper(connection, conversation, identity, helper)
+    }
+
+    fn register_with_helper(
+        connection: &Connection,
+        conversation: Option<SharedConversation>,
+        identity: Identity,
+        helper: &Path,
+    ) -> Option<Self> {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+        let conversation = match conversation {
+            Some(conversation) => conversation,
+            None => {
+                let terminal: Box<dyn Conversation> = Box::new(Terminal::open().ok()?);
+                Arc::new(Mutex::new(terminal))
+            }
+        };
+        let subject = authority::subject(&Subject::current_process().ok()?).ok()?;
+        let user = super::current_user().ok()?;
+
+        // The connection may be shared by several agents.
+        let object_path = format!("{OBJECT_PATH}/{}", NEXT_ID.fetch_add(1, Ordering::Relaxed));
+        let object_path = ObjectPath::try_from(object_path).ok()?;
+        let approver = Approver::default();
+        let listener = Listener {
+            conversation,
+            helper: helper.to_owned(),
+            uid: unsafe { libc::getuid() },
+            user,
+            identity,
+            approver: approver.clone(),
+            attempts: Default::default(),
+        };
+        connection.object_server().at(&object_path, listener).ok()?;
+        let agent = Self {
+            connection: connection.clone(),
+            object_path,
+            subject,
+            approver,
+        };
+
+        let locale = crate::system_locale().unwrap_or_default();
+        // Other agents registered for the session take precedence over a
+        // fallback agent.
+        let options = HashMap::from([("fallback", Value::from(true))]);
+        AuthorityProxy::new(&agent.connection)
+            .ok()?
+            .register_authentication_agent_with_options(
+                &agent.subject,
+                &locale,
+                agent.object_path.as_ref(),
+                options,
+            )
+            .ok()?;
+
+        Some(agent)
+    }
+
+    /// Returns the name of the user who authenticated using the agent, if
+    /// any.
+    pub(super) fn approver(&self) -> Option<String> {
+        self.approver
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+impl Drop for Agent {
+    fn drop(&mut self) {
+        if let Ok(authority) = AuthorityProxy::new(&self.connection) {
+            let _ =
+                authority.unregister_authentication_agent(&self.subject, self.object_path.as_ref());
+        }
+        let _ = self
+            .connection
+            .object_server()
+            .remove::<Listener, _>(&self.object_path);
+    }
+}
+
+#[derive(Debug, zbus::DBusError)]
+#[zbus(prefix = "org.freedesktop.PolicyKit1.Error")]
+enum AgentError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    /// Reported to the client as a dismissed authorization check.
+    Cancelled(String),
+    Failed(String),
+}
+
+impl From<Error> for AgentError {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::UserCanceled => Self::Cancelled("authentication was canceled".to_owned()),
+            error => Self::Failed(format!("authentication failed: {error:?}")),
+        }
+    }
+}
+
+struct Listener {
+    conversation: SharedConversation,
+    helper: std::path::PathBuf,
+    uid: u32,
+    user: String,
+    identity: Identity,
+    approver: Approver,
+    /// The authentications in progress, by cookie.
+    attempts: Arc<Mutex<HashMap<String, Arc<Attempt>>>>,
+}
+
+#[zbus::interface(name = "org.freedesktop.PolicyKit1.AuthenticationAgent")]
+impl Listener {
+    async fn begin_authentication(
+        &self,
+        _action_id: &str,
+        message: &str,
+        _icon_name: &str,
+        _details: HashMap<String, String>,
+        cookie: &str,
+        identities: Vec<DbusSubject>,
+    ) -> std::result::Result<(), AgentError> {
+        let user = self.select_user(&identities)?;
+        let attempt = Arc::new(Attempt::default());
+        self.attempts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(cookie.to_owned(), attempt.clone());
+
+        // The conversation blocks until the user responds, so it runs on
+        // another thread to keep handling `CancelAuthentication`.
+        let conversation = self.conversation.clone();
+        let helper = self.helper.clone();
+        let message = message.to_owned();
+        let owned_cookie = cookie.to_owned();
+        let result = blocking::unblock(move || {
+            let mut conversation = conversation.lock().unwrap_or_else(|e| e.into_inner());
+            conversation.begin(&message);
+            loop {
+                match authenticate(&helper, &user, &owned_cookie, &mut **conversation, &attempt) {
+                    Err(error) if !attempt.is_canceled() && conversation.retry(&error) => continue,
+                    Err(_) if attempt.is_canceled() => return Err(Error::UserCanceled),
+                    result => return result.map(|()| user),
+                }
+            }
+        })
+        .await;
+
+        self.attempts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(cookie);
+        let user = result?;
+        *self.approver.lock().unwrap_or_else(|e| e.into_inner()) = Some(user);
+        Ok(())
+    }
+
+    fn cancel_authentication(&self, cookie: &str) {
+        let attempts = self.attempts.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(attempt) = attempts.get(cookie) {
+            attempt.cancel();
+        }
+    }
+}
+
+/// An authentication in progress, which can be canceled by the authority.
+#[derive(Default)]
+struct Attempt {
+    canceled: AtomicBool,
+    /// The process ID of the helper while it runs.
+    helper: Mutex<Option<u32>>,
+}
+
+impl Attempt {
+    fn cancel(&self) {
+        self.canceled.store(true, Ordering::SeqCst);
+        // The ID is removed before the helper is waited for, so it can't have
+        // been reused by another process.
+        let helper = self.helper.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(pid) = *helper {
+            unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) };
+        }
+    }
+
+    fn is_canceled(&self) -> bool {
+        self.canceled.load(Ordering::SeqCst)
+    }
+
+    fn set_helper(&self, pid: Option<u32>) {
+        *self.helper.lock().unwrap_or_else(|e| e.into_inner()) = pid;
+    }
+}
+
+impl Listener {
+    /// Returns the name of the user to authenticate as.
+    ///
+    /// Unless a specific user is required, the current user is preferred over
+    /// other administrators.
+    fn select_user(&self, identities: &[DbusSubject]) -> Result<String> {
+        let uids = identities
+            .iter()
+            .filter(|(kind, _)| kind == "unix-user")
+            .filter_map(|(_, details)| u32::try_from(details.get("uid")?).ok());
+        if let Identity::User(name) = &self.identity {
+            return uids
+                .filter_map(|uid| super::user_name(uid).ok())
+                .find(|user| *user == *name)
+                .ok_or(Error::UserUnknown);
+        }
+        let mut first = None;
+        for uid in uids {
+            if uid == self.uid {
+                return Ok(self.user.clone());
+            }
+            first.get_or_insert(uid);
+        }
+        super::user_name(first.ok_or(Error::UserUnknown)?)
+    }
+}
+
+/// Authenticates `user` using the polkit helper, which responds to the
+/// authority on success.
+fn authenticate(
+    helper: &Path,
+    user: &str,
+    cookie: &str,
+    conversation: &mut dyn Conversation,
+    attempt: &Attempt,
+) -> Result<()> {
+    if attempt.is_canceled() {
+        return Err(Error::UserCanceled);
+    }
+    let mut child = Command::new(helper)
+        .arg(user)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|_| Error::Unavailable)?;
+    attempt.set_helper(Some(child.id()));
+    // Canceled while the helper was starting.
+    if attempt.is_canceled() {
+        let _ = child.kill();
+    }
+    let (Some(mut stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+        attempt.set_helper(None);
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err(Error::Unknown);
+    };
+
+    let result = (|| {
+        writeln!(stdin, "{cookie}").map_err(|_| Error::Unknown)?;
+        for line in BufReader::new(stdout).lines() {
+            let line = line.map_err(|_| Error::Unknown)?;
+            let (kind, message) = line.split_once(' ').unwrap_or((&line, ""));
+            let response = match kind {
+                "PAM_PROMPT_ECHO_OFF" => conversation.prompt(message, false)?,
+                "PAM_PROMPT_ECHO_ON" => conversation.prompt(message, true)?,
+                "PAM_ERROR_MSG" => {
+                    conversation.error(message);
+                    continue;
+                }
+                "PAM_TEXT_INFO" => {
+                    conversation.info(message);
+                    continue;
+                }
+                "SUCCESS" => return Ok(()),
+                "FAILURE" => return Err(Error::Authentication),
+                _ => continue,
+            };
+            let mut response = response.into_bytes();
+            // The helper reads the response up to the first newline.
+            let written = if response.contains(&b'\n') {
+                Err(Error::Authentication)
+            } else {
+                stdin
+                    .write_all(&response)
+                    .and_then(|()| stdin.write_all(b"\n"))
+                    .map_err(|_| Error::Unknown)
+            };
+            response.fill(0);
+            written?;
+        }
+        Err(Error::Unknown)
+    })();
+
+    attempt.set_helper(None);
+    if result.is_err() {
+        let _ = child.kill();
+    }
+    let _ = child.wait();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        os::unix::fs::PermissionsExt,
+        path::PathBuf,
+        sync::mpsc::{self, Receiver, Sender},
+        thread,
+        time::Duration,
+    };
+
+    use zbus::zvariant::{OwnedObjectPath, OwnedValue};
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Stands in for `polkit-agent-helper-1`, accepting the password `secret`.
+    const HELPER: &str = "#!/bin/sh
+read -r cookie
+echo 'PAM_PROMPT_ECHO_OFF Password:'
+read -r password
+if [ \"$password\" = secret ]; then echo SUCCESS; else echo FAILURE; fi
+";
+
+    // Peer-to-peer connections ignore the destination.
+    #[zbus::proxy(
+        interface = "org.freedesktop.PolicyKit1.AuthenticationAgent",
+        default_service = "rs.robius.authentication.Test",
+        gen_async = false,
+        blocking_name = "AgentProxy"
+    )]
+    trait AuthenticationAgent {
+        fn begin_authentication(
+            &self,
+            action_id: &str,
+            message: &str,
+            icon_name: &str,
+            details: HashMap<&str, &str>,
+            cookie: &str,
+            identities: &[DbusSubject],
+        ) -> zbus::Result<()>;
+
+        fn cancel_authentication(&self, cookie: &str) -> zbus::Result<()>;
+    }
+
+    /// Records the agents registered with the authority.
+    struct MockAuthority {
+        registered: Mutex<Sender<(OwnedObjectPath, bool)>>,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.PolicyKit1.Authority")]
+    impl MockAuthority {
+        fn register_authentication_agent_with_options(
+            &self,
+            _subject: DbusSubject,
+            _locale: &str,
+            object_path: OwnedObjectPath,
+            options: HashMap<String, OwnedValue>,
+        ) {
+            let fallback = options
+                .get("fallback")
+                .is_some_and(|value| bool::try_from(value).unwrap_or(false));
+            let registered = self.registered.lock().unwrap();
+            registered.send((object_path, fallback)).unwrap();
+        }
+
+        fn unregister_authentication_agent(
+            &self,
+            _subject: DbusSubject,
+            _object_path: OwnedObjectPath,
+        ) {
+        }
+    }
+
+    /// A conversation that reports each prompt and waits for the test to
+    /// respond.
+    struct Gated {
+        prompted: Sender<()>,
+        responses: Receiver<String>,
+    }
+
+    impl Conversation for Gated {
+        fn prompt(&mut self, _: &str, _: bool) -> Result<String> {
+            self.prompted.send(()).unwrap();
+            self.responses.recv().map_err(|_| Error::Unknown)
+        }
+    }
+
+    struct Setup {
+        agent: Agent,
+        /// The connection of the authority, used to call the agent.
+        authority: Connection,
+        object_path: OwnedObjectPath,
+        prompted: Receiver<()>,
+        responses: Sender<String>,
+        helper: PathBuf,
+    }
+
+    impl Drop for Setup {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.helper);
+        }
+    }
+
+    /// Registers an agent with a mock authority on a peer-to-peer
+    /// connection.
+    fn setup(name: &str) -> Setup {
+        let helper =
+            std::env::temp_dir().join(format!("robius-agent-helper-{}-{name}", std::process::id()));
+        fs::write(&helper, HELPER).unwrap();
+        fs::set_permissions(&helper, fs::Permissions::from_mode(0o700)).unwrap();
+
+        let (registered, registrations) = mpsc::channel();
+        let (connection, authority) = super::super::mock_service(|builder| {
+            builder.serve_at(
+                "/org/freedesktop/PolicyKit1/Authority",
+                MockAuthority {
+                    registered: Mutex::new(registered),
+                },
+            )
+        });
+
+        let (prompted, prompts) = mpsc::channel();
+        let (responses, gate) = mpsc::channel();
+        let conversation: Box<dyn Conversation> = Box::new(Gated {
+            prompted,
+            responses: gate,
+        });
+        let agent = Agent::register_with_helper(
+            &connection,
+            Some(Arc::new(Mutex::new(conversation))),
+            Identity::CurrentUser,
+            &helper,
+        )
+        .unwrap();
+
+        let (object_path, fallback) = registrations.recv_timeout(TIMEOUT).unwrap();
+        assert!(fallback, "the agent must be registered as a fallback");
+        Setup {
+            agent,
+            authority,
+            object_path,
+            prompted: prompts,
+            responses,
+            helper,
+        }
+    }
+
+    /// Calls `BeginAuthentication` for the current user on another thread.
+    fn begin(setup: &Setup, cookie: &'static str) -> thread::JoinHandle<zbus::Result<()>> {
+        let authority = setup.authority.clone();
+        let object_path = setup.object_path.clone();
+        thread::spawn(move || {
+            let identity = (
+                "unix-user".to_owned(),
+                HashMap::from([(
+                    "uid".to_owned(),
+                    OwnedValue::from(unsafe { libc::getuid() }),
+                )]),
+            );
+            AgentProxy::builder(&authority)
+                .path(object_path)?
+                .build()?
+                .begin_authentication(
+                    "rs.robius.authentication.authenticate",
+                    "Authenticate",
+                    "",
+                    HashMap::new(),
+                    cookie,
+                    &[identity],
+                )
+        })
+    }
+
+    #[test]
+    fn authenticates_using_the_helper() {
+        let setup = setup("success");
+        let begin = begin(&setup, "cookie");
+        setup.prompted.recv_timeout(TIMEOUT).unwrap();
+        setup.responses.send("secret".to_owned()).unwrap();
+
+        begin.join().unwrap().unwrap();
+        assert_eq!(setup.agent.approver(), super::super::current_user().ok());
+    }
+
+    #[test]
+    fn cancel_while_prompting() {
+        let setup = setup("cancel");
+        let begin = begin(&setup, "cookie");
+        setup.prompted.recv_timeout(TIMEOUT).unwrap();
+
+        // Handled while `BeginAuthentication` waits for the conversation.
+        AgentProxy::builder(&setup.authority)
+            .path(setup.object_path.clone())
+            .unwrap()
+            .build()
+            .unwrap()
+            .cancel_authentication("cookie")
+            .unwrap();
+        setup.responses.send("secret".to_owned()).unwrap();
+
+        let error = begin.join().unwrap().unwrap_err();
+        assert!(
+            matches!(&error, zbus::Error::MethodError(name, ..)
+                if name.as_str() == "org.freedesktop.PolicyKit1.Error.Cancelled"),
+            "{error:?}"
+        );
+        assert_eq!(setup.agent.approver(), None);
+    }
+}
@@ -0,0 +1,124 @@
+//! Bindings to the D-Bus interface of the polkit authority.
+//!
+//! See the [polkit D-Bus API] for more details.
+//!
+//! [polkit D-Bus API]: https://www.freedesktop.org/software/polkit/docs/latest/eggdbus-interface-org.freedesktop.PolicyKit1.Authority.html
+
+use std::collections::HashMap;
+
+use zbus::zvariant::{Fd, ObjectPath, OwnedValue, Value};
+
+use crate::{
+    linux::{Subject, SubjectKind},
+    Error, Result,
+};
+
+/// A polkit subject or identity, i.e., its kind and its details.
+pub(super) type DbusSubject = (String, HashMap<String, OwnedValue>);
+
+/// A temporary authorization, i.e., its ID, action ID, subject, and the times
+/// it was obtained and expires in seconds since the Unix epoch.
+pub(super) type TemporaryAuthorization = (String, String, DbusSubject, u64, u64);
+
+/// Whether the subject is authorized, whether it could be authorized through
+/// authentication, and the details of the result.
+pub(super) type AuthorizationResult = (bool, bool, HashMap<String, String>);
+
+/// A declared action, i.e., its ID, description, message, vendor name,
+/// vendor URL, icon name, implicit authorizations for any, inactive and active
+/// sessions, and its annotations.
+pub(super) type ActionDescription = (
+    String,
+    String,
+    String,
+    String,
+    String,
+    String,
+    u32,
+    u32,
+    u32,
+    HashMap<String, String>,
+);
+
+#[zbus::proxy(
+    interface = "org.freedesktop.PolicyKit1.Authority",
+    default_service = "org.freedesktop.PolicyKit1",
+    default_path = "/org/freedesktop/PolicyKit1/Authority",
+    gen_async = false,
+    blocking_name = "AuthorityProxy"
+)]
+pub(super) trait Authority {
+    fn enumerate_actions(&self, locale: &str) -> zbus::Result<Vec<ActionDescription>>;
+
+    fn check_authorization(
+        &self,
+        subject: &DbusSubject,
+        action_id: &str,
+        details: HashMap<&str, &str>,
+        flags: u32,
+        cancellation_id: &str,
+    ) -> zbus::Result<AuthorizationResult>;
+
+    fn cancel_check_authorization(&self, cancellation_id: &str) -> zbus::Result<()>;
+
+    fn enumerate_temporary_authorizations(
+        &self,
+        subject: &DbusSubject,
+    ) -> zbus::Result<Vec<TemporaryAuthorization>>;
+
+    fn revoke_temporary_authorizations(&self, subject: &DbusSubject) -> zbus::Result<()>;
+
+    fn register_authentication_agent_with_options(
+        &self,
+        subject: &DbusSubject,
+        locale: &str,
+        object_path: ObjectPath<'_>,
+        options: HashMap<&str, Value<'_>>,
+    ) -> zbus::Result<()>;
+
+    fn unregister_authentication_agent(
+        &self,
+        subject: &DbusSubject,
+        object_path: ObjectPath<'_>,
+    ) -> zbus::Result<()>;
+}
+
+/// Converts a subject to its D-Bus representation.
+pub(super) fn subject(subject: &Subject) -> Result<DbusSubject> {
+    let (kind, details) = match &subject.kind {
+        SubjectKind::Process {
+            pid,
+            start_time,
+            uid,
+            pidfd,
+        } => {
+            let mut details = HashMap::from([
+                ("pid".to_owned(), OwnedValue::from(*pid)),
+                ("start-time".to_owned(), OwnedValue::from(*start_time)),
+                ("uid".to_owned(), OwnedValue::from(*uid as i32)),
+            ]);
+            if let Some(pidfd) = pidfd {
+                let pidfd = Value::from(Fd::from(&**pidfd))
+                    .try_to_owned()
+                    .map_err(|_| Error::Unknown)?;
+                details.insert("pidfd".to_owned(), pidfd);
+            }
+            ("unix-process", details)
+        }
+        SubjectKind::Session(session_id) => {
+            let details = HashMap::from([(
+                "session-id".to_owned(),
+                OwnedValue::from(zbus::zvariant::Str::from(session_id.clone())),
+            )]);
+            ("unix-session", details)
+        }
+        SubjectKind::SystemBusName(name) => {
+            let details = HashMap::from([(
+                "name".to_owned(),
+                OwnedValue::from(zbus::zvariant::Str::from(name.clone())),
+            )]);
+            ("system-bus-name", details)
+        }
+    };
+    Ok((kind.to_owned(), details))
+}
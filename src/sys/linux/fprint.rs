@@ -0,0 +1,454 @@
+//! Fingerprint authentication using [fprintd].
+//!
+//! [fprintd]: https://fprint.freedesktop.org/fprintd-dev/
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use async_channel::{Receiver, Sender};
+use async_io::Timer;
+use futures_lite::{future, StreamExt};
+use zbus::{blocking::Connection, zvariant::OwnedObjectPath};
+
+use super::SharedConversation;
+use crate::{
+    linux::{Conversation, Terminal},
+    Error, Result,
+};
+
+/// How long to wait for the user to touch the reader, or to touch it again
+/// after a failed scan.
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[zbus::proxy(
+    interface = "net.reactivated.Fprint.Manager",
+    default_service = "net.reactivated.Fprint",
+    default_path = "/net/reactivated/Fprint/Manager",
+    gen_async = false,
+    blocking_name = "ManagerProxy"
+)]
+trait Manager {
+    fn get_default_device(&self) -> zbus::Result<OwnedObjectPath>;
+}
+
+#[zbus::proxy(
+    interface = "net.reactivated.Fprint.Device",
+    default_service = "net.reactivated.Fprint",
+    gen_async = false,
+    blocking_name = "DeviceProxy"
+)]
+trait Device {
+    fn claim(&self, username: &str) -> zbus::Result<()>;
+
+    fn release(&self) -> zbus::Result<()>;
+
+    fn verify_start(&self, finger_name: &str) -> zbus::Result<()>;
+
+    fn verify_stop(&self) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn verify_status(&self, result: &str, done: bool) -> zbus::Result<()>;
+
+    #[zbus(property, name = "scan-type")]
+    fn scan_type(&self) -> zbus::Result<String>;
+}
+
+/// The fingerprint verifications in progress.
+#[derive(Debug)]
+pub(super) struct PendingVerifications {
+    /// Closed to cancel the verification, by ID.
+    verifications: Mutex<HashMap<u64, Sender<()>>>,
+    /// How long to wait for each verification status.
+    timeout: Duration,
+}
+
+impl Default for PendingVerifications {
+    fn default() -> Self {
+        Self {
+            verifications: Default::default(),
+            timeout: VERIFY_TIMEOUT,
+        }
+    }
+}
+
+impl PendingVerifications {
+    /// Cancels all verifications in progress, which then fail with
+    /// [`Error::AppCanceled`].
+    pub(super) fn cancel(&self) {
+        let mut verifications = self.verifications.lock().unwrap_or_else(|e| e.into_inner());
+        for (_, canceled) in verifications.drain() {
+            canceled.close();
+        }
+    }
+
+    /// Returns the ID of a new verification and the receiver closed if it is
+    /// canceled.
+    fn insert(&self) -> (u64, Receiver<()>) {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = async_channel::bounded(1);
+        let mut verifications = self.verifications.lock().unwrap_or_else(|e| e.into_inner());
+        verifications.insert(id, sender);
+        (id, receiver)
+    }
+
+    fn remove(&self, id: u64) {
+        let mut verifications = self.verifications.lock().unwrap_or_else(|e| e.into_inner());
+        verifications.remove(&id);
+    }
+}
+
+/// Verifies any enrolled finger of the current user using the default
+/// fingerprint reader of the fprintd service on the given connection to the
+/// system bus.
+///
+/// Instructions are displayed using the `conversation`, or on the controlling
+/// terminal if there is one. Fails with [`Error::Timeout`] if the user doesn't
+/// touch the reader in time, and with [`Error::AppCanceled`] if canceled.
+pub(super) fn authenticate(
+    connection: &Connection,
+    message: &str,
+    conversation: Option<&SharedConversation>,
+    pending: &PendingVerifications,
+) -> Result<()> {
+    let mut guard;
+    let mut terminal;
+    let conversation: &mut dyn Conversation = match conversation {
+        Some(conversation) => {
+            guard = conversation.lock().unwrap_or_else(|e| e.into_inner());
+            &mut **guard
+        }
+        None => match Terminal::open() {
+            Ok(opened) => {
+                terminal = opened;
+                &mut terminal
+            }
+            Err(_) => &mut Silent,
+        },
+    };
+
+    let user = super::current_user()?;
+    let path = ManagerProxy::new(connection)
+        .and_then(|manager| manager.get_default_device())
+        .map_err(convert)?;
+    let device = DeviceProxy::builder(connection)
+        .path(path)
+        .and_then(|builder| builder.build())
+        .map_err(convert)?;
+
+    device.claim(&user).map_err(convert)?;
+    let (id, canceled) = pending.insert();
+    let result = (|| {
+        let instruction = match device.scan_type().as_deref() {
+            Ok("swipe") => "Swipe your finger across the fingerprint reader",
+            _ => "Place your finger on the fingerprint reader",
+        };
+        conversation.begin(message);
+        loop {
+            conversation.info(instruction);
+            match verify(&device, conversation, &canceled, pending.timeout) {
+                // The user is gone, or the app no longer wants an answer.
+                result @ Err(Error::Timeout | Error::AppCanceled) => return result,
+                Err(error) if conversation.retry(&error) => {}
+                result => return result,
+            }
+        }
+    })();
+    pending.remove(id);
+    let _ = device.release();
+    result
+}
+
+/// An event while waiting for a verification status.
+enum Event {
+    Status(Option<zbus::Message>),
+    Canceled,
+    TimedOut,
+}
+
+/// Runs a single verification, displaying the status updates until it is
+/// done, canceled or times out.
+fn verify(
+    device: &DeviceProxy,
+    conversation: &mut dyn Conversation,
+    canceled: &Receiver<()>,
+    timeout: Duration,
+) -> Result<()> {
+    // Subscribes before starting so that no status is missed.
+    let mut statuses = async_io::block_on(device.inner().inner().receive_signal("VerifyStatus"))
+        .map_err(convert)?;
+    device.verify_start("any").map_err(convert)?;
+
+    let result = loop {
+        let event = async_io::block_on(future::or(
+            async { Event::Status(statuses.next().await) },
+            future::or(
+                async {
+                    let _ = canceled.recv().await;
+                    Event::Canceled
+                },
+                async {
+                    Timer::after(timeout).await;
+                    Event::TimedOut
+                },
+            ),
+        ));
+        let status = match event {
+            Event::Status(Some(status)) => status,
+            // The connection was closed.
+            Event::Status(None) => break Err(Error::Unavailable),
+            Event::Canceled => break Err(Error::AppCanceled),
+            Event::TimedOut => break Err(Error::Timeout),
+        };
+        let body = status.body();
+        let Ok((result, done)) = body.deserialize::<(&str, bool)>() else {
+            continue;
+        };
+        if done {
+            break match result {
+                "verify-match" => Ok(()),
+                "verify-no-match" => Err(Error::Authentication),
+                "verify-disconnected" => Err(Error::BiometryDisconnected),
+                _ => Err(Error::Unknown),
+            };
+        }
+
+        let message = match result {
+            "verify-retry-scan" => "Place your finger on the reader again",
+            "verify-swipe-too-short" => "Swipe was too short, try again",
+            "verify-finger-not-centered" => "Your finger was not centered, try again",
+            "verify-remove-and-retry" => "Remove your finger, and try again",
+            _ => continue,
+        };
+        conversation.error(message);
+    };
+
+    let _ = device.verify_stop();
+    result
+}
+
+/// Discards the instructions if there is nowhere to display them.
+struct Silent;
+
+impl Conversation for Silent {
+    fn prompt(&mut self, _: &str, _: bool) -> Result<String> {
+        Err(Error::NotInteractive)
+    }
+}
+
+fn convert(error: zbus::Error) -> Error {
+    let zbus::Error::MethodError(name, ..) = &error else {
+        return Error::Unavailable;
+    };
+    match name.as_str() {
+        "net.reactivated.Fprint.Error.NoEnrolledPrints" => Error::NotEnrolled,
+        "net.reactivated.Fprint.Error.AlreadyInUse" => Error::Busy,
+        "net.reactivated.Fprint.Error.Internal" => Error::Unknown,
+        // E.g., no reader is connected or fprintd is not installed.
+        _ => Error::Unavailable,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use zbus::{fdo, object_server::SignalEmitter};
+
+    use super::*;
+
+    const DEVICE_PATH: &str = "/net/reactivated/Fprint/Device/0";
+
+    struct MockManager;
+
+    #[zbus::interface(name = "net.reactivated.Fprint.Manager")]
+    impl MockManager {
+        fn get_default_device(&self) -> OwnedObjectPath {
+            OwnedObjectPath::try_from(DEVICE_PATH).unwrap()
+        }
+    }
+
+    #[derive(Debug, zbus::DBusError)]
+    #[zbus(prefix = "net.reactivated.Fprint.Error")]
+    enum FprintError {
+        #[zbus(error)]
+        ZBus(zbus::Error),
+        AlreadyInUse(String),
+    }
+
+    /// A fingerprint reader reporting the given statuses for each
+    /// verification.
+    struct MockDevice {
+        in_use: bool,
+        statuses: &'static [(&'static str, bool)],
+        claimed: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[zbus::interface(name = "net.reactivated.Fprint.Device")]
+    impl MockDevice {
+        fn claim(&self, username: &str) -> std::result::Result<(), FprintError> {
+            if self.in_use {
+                return Err(FprintError::AlreadyInUse(
+                    "Device was already claimed".to_owned(),
+                ));
+            }
+            self.claimed.lock().unwrap().push(username.to_owned());
+            Ok(())
+        }
+
+        fn release(&self) {}
+
+        async fn verify_start(
+            &self,
+            _finger_name: &str,
+            #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        ) -> fdo::Result<()> {
+            for &(result, done) in self.statuses {
+                Self::verify_status(&emitter, result, done).await?;
+            }
+            Ok(())
+        }
+
+        fn verify_stop(&self) {}
+
+        #[zbus(signal)]
+        async fn verify_status(
+            emitter: &SignalEmitter<'_>,
+            result: &str,
+            done: bool,
+        ) -> zbus::Result<()>;
+
+        #[zbus(property, name = "scan-type")]
+        fn scan_type(&self) -> &str {
+            "press"
+        }
+    }
+
+    /// Records the messages displayed to the user.
+    #[derive(Clone, Default)]
+    struct Recorder {
+        messages: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Conversation for Recorder {
+        fn prompt(&mut self, _: &str, _: bool) -> Result<String> {
+            Err(Error::NotInteractive)
+        }
+
+        fn info(&mut self, message: &str) {
+            self.messages.lock().unwrap().push(message.to_owned());
+        }
+
+        fn error(&mut self, message: &str) {
+            self.messages.lock().unwrap().push(message.to_owned());
+        }
+    }
+
+    /// Verifies a fingerprint with a mock fprintd, returning the result, the
+    /// users who claimed the device and the displayed messages.
+    fn verify(
+        in_use: bool,
+        statuses: &'static [(&'static str, bool)],
+    ) -> (Result<()>, Vec<String>, Vec<String>) {
+        verify_pending(in_use, statuses, &PendingVerifications::default())
+    }
+
+    fn verify_pending(
+        in_use: bool,
+        statuses: &'static [(&'static str, bool)],
+        pending: &PendingVerifications,
+    ) -> (Result<()>, Vec<String>, Vec<String>) {
+        let claimed = Arc::new(Mutex::new(Vec::new()));
+        let device = MockDevice {
+            in_use,
+            statuses,
+            claimed: claimed.clone(),
+        };
+        let (connection, _fprintd) = super::super::mock_service(|builder| {
+            builder
+                .serve_at("/net/reactivated/Fprint/Manager", MockManager)?
+                .serve_at(DEVICE_PATH, device)
+        });
+
+        let recorder = Recorder::default();
+        let conversation: SharedConversation = Arc::new(Mutex::new(Box::new(recorder.clone())));
+        let result = authenticate(&connection, "Authenticate", Some(&conversation), pending);
+
+        let claimed = claimed.lock().unwrap().clone();
+        let messages = recorder.messages.lock().unwrap().clone();
+        (result, claimed, messages)
+    }
+
+    #[test]
+    fn matched() {
+        let (result, claimed, messages) = verify(
+            false,
+            &[("verify-retry-scan", false), ("verify-match", true)],
+        );
+        assert!(result.is_ok());
+        assert_eq!(claimed, [super::super::current_user().unwrap()]);
+        assert_eq!(
+            messages,
+            [
+                "Place your finger on the fingerprint reader",
+                "Place your finger on the reader again",
+            ]
+        );
+    }
+
+    #[test]
+    fn not_matched() {
+        let (result, ..) = verify(false, &[("verify-no-match", true)]);
+        assert!(matches!(result, Err(Error::Authentication)));
+    }
+
+    #[test]
+    fn disconnected() {
+        let (result, ..) = verify(false, &[("verify-disconnected", true)]);
+        assert!(matches!(result, Err(Error::BiometryDisconnected)));
+    }
+
+    #[test]
+    fn already_in_use() {
+        let (result, claimed, _) = verify(true, &[("verify-match", true)]);
+        assert!(matches!(result, Err(Error::Busy)));
+        assert!(claimed.is_empty());
+    }
+
+    #[test]
+    fn times_out() {
+        let pending = PendingVerifications {
+            timeout: Duration::from_millis(100),
+            ..Default::default()
+        };
+        // The user never touches the reader.
+        let (result, ..) = verify_pending(false, &[], &pending);
+        assert!(matches!(result, Err(Error::Timeout)));
+    }
+
+    #[test]
+    fn canceled() {
+        let pending = Arc::new(PendingVerifications::default());
+        let canceler = thread::spawn({
+            let pending = pending.clone();
+            move || {
+                // Waits for the verification to start.
+                while pending.verifications.lock().unwrap().is_empty() {
+                    thread::sleep(Duration::from_millis(10));
+                }
+                pending.cancel();
+            }
+        });
+        let (result, ..) = verify_pending(false, &[], &pending);
+        canceler.join().unwrap();
+        assert!(matches!(result, Err(Error::AppCanceled)));
+        assert!(pending.verifications.lock().unwrap().is_empty());
+    }
+}
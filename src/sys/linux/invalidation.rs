@@ -0,0 +1,474 @@
+//! Invalidating cached authorization when the user walks away.
+//!
+//! The signals are received on dedicated connections, which are closed when
+//! the watch is dropped, ending the threads listening on them.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use zbus::{
+    blocking::{fdo::DBusProxy, Connection, MessageIterator},
+    message::Type,
+    names::BusName,
+    zvariant::OwnedValue,
+    MatchRule, Message, OwnedMatchRule,
+};
+
+use super::{logind, polkit};
+use crate::{
+    linux::{Invalidation, Subject},
+    Error, Result,
+};
+
+const LOGIND_NAME: &str = "org.freedesktop.login1";
+const SCREEN_SAVER_NAME: &str = "org.freedesktop.ScreenSaver";
+
+const SESSION_INTERFACE: &str = "org.freedesktop.login1.Session";
+const MANAGER_INTERFACE: &str = "org.freedesktop.login1.Manager";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+const SCREEN_SAVER_INTERFACE: &str = "org.freedesktop.ScreenSaver";
+
+type Callback = Arc<Mutex<Box<dyn Fn(Invalidation) + Send>>>;
+
+/// Watches for the user walking away until dropped.
+pub(crate) struct InvalidationWatch {
+    connections: Vec<Connection>,
+}
+
+impl fmt::Debug for InvalidationWatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InvalidationWatch")
+            .field("connections", &self.connections.len())
+            .finish()
+    }
+}
+
+impl InvalidationWatch {
+    /// Starts watching, revoking temporary authorizations using the given
+    /// connection to the system bus.
+    pub(super) fn new(
+        system_bus: &Connection,
+        subject: Option<Subject>,
+        callback: Box<dyn Fn(Invalidation) + Send>,
+    ) -> Result<Self> {
+        let system = Connection::system().map_err(|_| Error::Unavailable)?;
+        Self::listen(
+            system,
+            Connection::session().ok(),
+            system_bus,
+            subject,
+            callback,
+        )
+    }
+
+    /// Starts watching for signals on the given dedicated connections to the
+    /// system bus and, in desktop sessions, the session bus.
+    fn listen(
+        system: Connection,
+        session: Option<Connection>,
+        system_bus: &Connection,
+        subject: Option<Subject>,
+        callback: Box<dyn Fn(Invalidation) + Send>,
+    ) -> Result<Self> {
+        let callback = Arc::new(Mutex::new(callback));
+
+        // Only the system bus allows a single service to own the name of
+        // logind, so its signals are matched by name.
+        let mut rules = vec![rule(
+            LOGIND_NAME,
+            MANAGER_INTERFACE,
+            "PrepareForSleep",
+            None,
+            None,
+        )?];
+        // Outside of a login session, e.g., in a system service, only sleep
+        // is watched.
+        if let Ok(session) = logind::session(&system, subject.as_ref()) {
+            let path = session.inner().path().as_str();
+            rules.push(rule(
+                LOGIND_NAME,
+                SESSION_INTERFACE,
+                "Lock",
+                Some(path),
+                None,
+            )?);
+            rules.push(rule(
+                LOGIND_NAME,
+                PROPERTIES_INTERFACE,
+                "PropertiesChanged",
+                Some(path),
+                Some(SESSION_INTERFACE),
+            )?);
+        }
+        let mut connections = vec![listen(
+            system,
+            LOGIND_NAME,
+            rules,
+            system_bus,
+            &subject,
+            &callback,
+        )?];
+
+        // The screen saver is only available in desktop sessions. Any client
+        // of the session bus may claim its name once it is released, so only
+        // the current owner's signals are matched.
+        let screen_saver = session.and_then(|connection| {
+            let owner = name_owner(&DBusProxy::new(&connection).ok()?, SCREEN_SAVER_NAME)?;
+            let rules =
+                vec![rule(&owner, SCREEN_SAVER_INTERFACE, "ActiveChanged", None, None).ok()?];
+            listen(
+                connection,
+                SCREEN_SAVER_NAME,
+                rules,
+                system_bus,
+                &subject,
+                &callback,
+            )
+            .ok()
+        });
+        connections.extend(screen_saver);
+
+        Ok(Self { connections })
+    }
+}
+
+impl Drop for InvalidationWatch {
+    fn drop(&mut self) {
+        for connection in self.connections.drain(..) {
+            let _ = connection.close();
+        }
+    }
+}
+
+/// Returns a match rule for the signal sent by `sender`.
+fn rule(
+    sender: &str,
+    interface: &'static str,
+    member: &'static str,
+    path: Option<&str>,
+    arg0: Option<&'static str>,
+) -> Result<OwnedMatchRule> {
+    let mut builder = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .sender(sender.to_owned())
+        .and_then(|builder| builder.interface(interface))
+        .and_then(|builder| builder.member(member));
+    if let Some(path) = path {
+        builder = builder.and_then(|builder| builder.path(path.to_owned()));
+    }
+    if let Some(arg0) = arg0 {
+        builder = builder.and_then(|builder| builder.arg(0, arg0));
+    }
+    builder
+        .map(|builder| builder.build().to_owned().into())
+        .map_err(|_| Error::Unknown)
+}
+
+/// Returns the unique name of the owner of `name`, if it has one.
+fn name_owner(dbus: &DBusProxy<'_>, name: &'static str) -> Option<String> {
+    let owner = dbus.get_name_owner(BusName::from_static_str(name).ok()?);
+    owner.ok().map(|owner| owner.to_string())
+}
+
+/// Subscribes to the signals and handles them on a new thread until the
+/// connection is closed.
+///
+/// Signals are only handled if they were sent by the current owner of
+/// `name`, as signals addressed to the connection are delivered regardless
+/// of the rules.
+fn listen(
+    connection: Connection,
+    name: &'static str,
+    rules: Vec<OwnedMatchRule>,
+    system_bus: &Connection,
+    subject: &Option<Subject>,
+    callback: &Callback,
+) -> Result<Connection> {
+    // Created first so that no signal is missed.
+    let messages = MessageIterator::from(&connection);
+    let dbus = DBusProxy::new(&connection).map_err(|_| Error::Unavailable)?;
+    for rule in rules {
+        dbus.add_match_rule(rule.into())
+            .map_err(|_| Error::Unavailable)?;
+    }
+
+    let system_bus = system_bus.clone();
+    let subject = subject.clone();
+    let callback = callback.clone();
+    thread::Builder::new()
+        .name("robius-authentication-invalidation".to_owned())
+        .spawn(move || {
+            for message in messages {
+                let Ok(message) = message else {
+                    break;
+                };
+                let Some(invalidation) = invalidation(&message) else {
+                    continue;
+                };
+                let sender = message.header().sender().map(|sender| sender.to_string());
+                if sender.is_none() || sender != name_owner(&dbus, name) {
+                    continue;
+                }
+                // The app is notified even if nothing was revoked, e.g.,
+                // without polkit, as it may retain authorization itself.
+                let _ = polkit::revoke_temporary_authorizations(&system_bus, subject.as_ref());
+                (callback.lock().unwrap_or_else(|e| e.into_inner()))(invalidation);
+            }
+        })
+        .map_err(|_| Error::Unknown)?;
+    Ok(connection)
+}
+
+/// Returns why the signal invalidates cached authorization, if it does.
+fn invalidation(message: &Message) -> Option<Invalidation> {
+    let header = message.header();
+    if header.message_type() != Type::Signal {
+        return None;
+    }
+    let body = message.body();
+    match (header.interface()?.as_str(), header.member()?.as_str()) {
+        (SESSION_INTERFACE, "Lock") => Some(Invalidation::Locked),
+        // Also sent with `false` after resuming.
+        (MANAGER_INTERFACE, "PrepareForSleep") => body
+            .deserialize::<bool>()
+            .ok()?
+            .then_some(Invalidation::Sleep),
+        (PROPERTIES_INTERFACE, "PropertiesChanged") => {
+            let (_, changed, _): (String, HashMap<String, OwnedValue>, Vec<String>) =
+                body.deserialize().ok()?;
+            let active = bool::try_from(changed.get("Active")?).ok()?;
+            (!active).then_some(Invalidation::SessionInactive)
+        }
+        (SCREEN_SAVER_INTERFACE, "ActiveChanged") => body
+            .deserialize::<bool>()
+            .ok()?
+            .then_some(Invalidation::ScreenSaver),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader},
+        process::{Child, Command, Stdio},
+        sync::mpsc,
+        time::Duration,
+    };
+
+    use zbus::{
+        blocking::connection::Builder,
+        zvariant::{OwnedObjectPath, Str, Value},
+    };
+
+    use super::*;
+    use crate::sys::linux::authority::DbusSubject;
+
+    const SESSION_PATH: &str = "/org/freedesktop/login1/session/c1";
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// A private message bus, which is stopped when dropped.
+    struct Bus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl Bus {
+        /// Starts a bus, or returns `None` if `dbus-daemon` is not installed.
+        fn start() -> Option<Self> {
+            let Ok(mut daemon) = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+            else {
+                eprintln!("skipped: dbus-daemon is not installed");
+                return None;
+            };
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take().unwrap())
+                .read_line(&mut address)
+                .unwrap();
+            Some(Self {
+                daemon,
+                address: address.trim().to_owned(),
+            })
+        }
+
+        fn connect(&self) -> Connection {
+            Builder::address(&*self.address).unwrap().build().unwrap()
+        }
+    }
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    struct MockManager;
+
+    #[zbus::interface(name = "org.freedesktop.login1.Manager")]
+    impl MockManager {
+        fn get_session(&self, _session_id: &str) -> OwnedObjectPath {
+            OwnedObjectPath::try_from(SESSION_PATH).unwrap()
+        }
+    }
+
+    /// Records the sessions whose temporary authorizations were revoked.
+    struct MockAuthority {
+        revoked: Mutex<mpsc::Sender<String>>,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.PolicyKit1.Authority")]
+    impl MockAuthority {
+        fn revoke_temporary_authorizations(&self, subject: DbusSubject) {
+            let session_id = subject.1["session-id"].downcast_ref::<Str>().unwrap();
+            let revoked = self.revoked.lock().unwrap();
+            revoked.send(session_id.to_string()).unwrap();
+        }
+    }
+
+    #[test]
+    fn signals() {
+        let Some(bus) = Bus::start() else {
+            return;
+        };
+        let (revoked, revocations) = mpsc::channel();
+        let services = Builder::address(&*bus.address)
+            .unwrap()
+            .name(LOGIND_NAME)
+            .unwrap()
+            .name(SCREEN_SAVER_NAME)
+            .unwrap()
+            .name("org.freedesktop.PolicyKit1")
+            .unwrap()
+            .serve_at("/org/freedesktop/login1", MockManager)
+            .unwrap()
+            .serve_at(
+                "/org/freedesktop/PolicyKit1/Authority",
+                MockAuthority {
+                    revoked: Mutex::new(revoked),
+                },
+            )
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let (invalidated, invalidations) = mpsc::channel();
+        let invalidated = Mutex::new(invalidated);
+        let watch = InvalidationWatch::listen(
+            bus.connect(),
+            Some(bus.connect()),
+            &bus.connect(),
+            Some(Subject::session("c1")),
+            Box::new(move |invalidation| invalidated.lock().unwrap().send(invalidation).unwrap()),
+        )
+        .unwrap();
+
+        let emitter = services;
+        let manager = "/org/freedesktop/login1";
+        let screen_saver = "/org/freedesktop/ScreenSaver";
+
+        // Signals from other clients are ignored, even if they are addressed
+        // to the watching connections.
+        let spoofer = bus.connect();
+        for connection in &watch.connections {
+            let destination = connection.unique_name().unwrap();
+            spoofer
+                .emit_signal(
+                    Some(destination),
+                    SESSION_PATH,
+                    SESSION_INTERFACE,
+                    "Lock",
+                    &(),
+                )
+                .unwrap();
+            spoofer
+                .emit_signal(
+                    Some(destination),
+                    screen_saver,
+                    SCREEN_SAVER_INTERFACE,
+                    "ActiveChanged",
+                    &true,
+                )
+                .unwrap();
+        }
+        spoofer
+            .emit_signal(
+                None::<()>,
+                manager,
+                MANAGER_INTERFACE,
+                "PrepareForSleep",
+                &true,
+            )
+            .unwrap();
+
+        // Resuming from sleep and deactivating the screen saver are ignored.
+        emitter
+            .emit_signal(
+                None::<()>,
+                manager,
+                MANAGER_INTERFACE,
+                "PrepareForSleep",
+                &false,
+            )
+            .unwrap();
+        emitter
+            .emit_signal(
+                None::<()>,
+                screen_saver,
+                SCREEN_SAVER_INTERFACE,
+                "ActiveChanged",
+                &false,
+            )
+            .unwrap();
+
+        let invalidated = |expected| {
+            assert_eq!(invalidations.recv_timeout(TIMEOUT).unwrap(), expected);
+            // Revoked before the callback is called.
+            assert_eq!(revocations.try_recv().unwrap(), "c1");
+        };
+        emitter
+            .emit_signal(None::<()>, SESSION_PATH, SESSION_INTERFACE, "Lock", &())
+            .unwrap();
+        invalidated(Invalidation::Locked);
+        emitter
+            .emit_signal(
+                None::<()>,
+                manager,
+                MANAGER_INTERFACE,
+                "PrepareForSleep",
+                &true,
+            )
+            .unwrap();
+        invalidated(Invalidation::Sleep);
+        let changed = HashMap::from([("Active", Value::from(false))]);
+        emitter
+            .emit_signal(
+                None::<()>,
+                SESSION_PATH,
+                PROPERTIES_INTERFACE,
+                "PropertiesChanged",
+                &(SESSION_INTERFACE, changed, Vec::<String>::new()),
+            )
+            .unwrap();
+        invalidated(Invalidation::SessionInactive);
+        emitter
+            .emit_signal(
+                None::<()>,
+                screen_saver,
+                SCREEN_SAVER_INTERFACE,
+                "ActiveChanged",
+                &true,
+            )
+            .unwrap();
+        invalidated(Invalidation::ScreenSaver);
+        assert!(invalidations.try_recv().is_err());
+    }
+}
@@ -0,0 +1,192 @@
+//! Bindings to the D-Bus interface of systemd-logind.
+//!
+//! See [org.freedesktop.login1(5)] for more details.
+//!
+//! [org.freedesktop.login1(5)]: https://www.freedesktop.org/software/systemd/man/latest/org.freedesktop.login1.html
+
+use std::env;
+
+use zbus::{blocking::Connection, names::BusName, zvariant::OwnedObjectPath};
+
+use crate::{
+    linux::{Subject, SubjectKind},
+    Error, Result,
+};
+
+#[zbus::proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1",
+    gen_async = false,
+    blocking_name = "ManagerProxy"
+)]
+pub(super) trait Manager {
+    fn get_session(&self, session_id: &str) -> zbus::Result<OwnedObjectPath>;
+
+    #[zbus(name = "GetSessionByPID")]
+    fn get_session_by_pid(&self, pid: u32) -> zbus::Result<OwnedObjectPath>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.login1.Session",
+    default_service = "org.freedesktop.login1",
+    gen_async = false,
+    blocking_name = "SessionProxy"
+)]
+pub(super) trait Session {
+    #[zbus(property)]
+    fn id(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn user(&self) -> zbus::Result<(u32, OwnedObjectPath)>;
+
+    #[zbus(property, name = "Type")]
+    fn kind(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn active(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn remote(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn locked_hint(&self) -> zbus::Result<bool>;
+}
+
+/// Returns the login session of the subject, or of the current process.
+pub(super) fn session(
+    connection: &Connection,
+    subject: Option<&Subject>,
+) -> Result<SessionProxy<'static>> {
+    let manager = ManagerProxy::new(connection).map_err(|_| Error::Unavailable)?;
+    let path = match subject.map(|subject| &subject.kind) {
+        Some(SubjectKind::Session(session_id)) => manager.get_session(session_id),
+        Some(SubjectKind::Process { pid, .. }) => manager.get_session_by_pid(*pid),
+        Some(SubjectKind::SystemBusName(name)) => {
+            manager.get_session_by_pid(bus_name_pid(connection, name)?)
+        }
+        None => current_session(&manager, env::var("XDG_SESSION_ID").ok()),
+    }
+    .map_err(|_| Error::Unavailable)?;
+    SessionProxy::builder(connection)
+        .path(path)
+        .and_then(|builder| builder.build())
+        .map_err(|_| Error::Unavailable)
+}
+
+/// Returns the ID of the login session of the process with the given PID.
+pub(super) fn session_id(connection: &Connection, pid: u32) -> Result<String> {
+    let manager = ManagerProxy::new(connection).map_err(|_| Error::Unavailable)?;
+    let path = if pid == std::process::id() {
+        current_session(&manager, env::var("XDG_SESSION_ID").ok())
+    } else {
+        manager.get_session_by_pid(pid)
+    };
+    path.and_then(|path| SessionProxy::builder(connection).path(path)?.build())
+        .and_then(|session| session.id())
+        .map_err(|_| Error::Unavailable)
+}
+
+/// Returns the path of the login session of the current process.
+///
+/// The session with the ID `fallback`, i.e., `XDG_SESSION_ID`, is only used if
+/// logind doesn't know the session of the current process, e.g., in a user
+/// service. As the variable is inherited, it may name another session, e.g.,
+/// if the app was started from a terminal of another user.
+fn current_session(
+    manager: &ManagerProxy<'_>,
+    fallback: Option<String>,
+) -> zbus::Result<OwnedObjectPath> {
+    manager
+        .get_session_by_pid(std::process::id())
+        .or_else(|e| match fallback {
+            Some(session_id) => manager.get_session(&session_id),
+            None => Err(e),
+        })
+}
+
+/// Returns the PID of the client of the bus with the given unique name.
+pub(super) fn bus_name_pid(connection: &Connection, name: &str) -> Result<u32> {
+    let name = BusName::try_from(name).map_err(|_| Error::Unknown)?;
+    zbus::blocking::fdo::DBusProxy::new(connection)
+        .and_then(|bus| Ok(bus.get_connection_unix_process_id(name)?))
+        .map_err(|_| Error::Unavailable)
+}
+
+/// Returns the user ID of the client of the bus with the given unique name.
+pub(super) fn bus_name_uid(connection: &Connection, name: &str) -> Result<u32> {
+    let name = BusName::try_from(name).map_err(|_| Error::Unknown)?;
+    zbus::blocking::fdo::DBusProxy::new(connection)
+        .and_then(|bus| Ok(bus.get_connection_unix_user(name)?))
+        .map_err(|_| Error::Unavailable)
+}
+
+#[cfg(test)]
+mod tests {
+    use zbus::fdo;
+
+    use super::*;
+
+    /// Knows the sessions `c1` and `c2`, and the session of the current
+    /// process if `by_pid` is set.
+    struct MockManager {
+        by_pid: bool,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.login1.Manager")]
+    impl MockManager {
+        fn get_session(&self, session_id: &str) -> fdo::Result<OwnedObjectPath> {
+            match session_id {
+                "c1" | "c2" => Ok(session_path(session_id)),
+                _ => Err(fdo::Error::Failed("No such session".to_owned())),
+            }
+        }
+
+        #[zbus(name = "GetSessionByPID")]
+        fn get_session_by_pid(&self, pid: u32) -> fdo::Result<OwnedObjectPath> {
+            match self.by_pid && pid == std::process::id() {
+                true => Ok(session_path("c1")),
+                false => Err(fdo::Error::Failed("No session".to_owned())),
+            }
+        }
+    }
+
+    struct MockSession {
+        id: &'static str,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.login1.Session")]
+    impl MockSession {
+        #[zbus(property)]
+        fn id(&self) -> &str {
+            self.id
+        }
+    }
+
+    fn session_path(session_id: &str) -> OwnedObjectPath {
+        OwnedObjectPath::try_from(format!("/org/freedesktop/login1/session/{session_id}")).unwrap()
+    }
+
+    /// Returns the ID of the session of the current process.
+    fn current_session_id(by_pid: bool, fallback: Option<&str>) -> zbus::Result<String> {
+        let (connection, _logind) = super::super::mock_service(move |builder| {
+            builder
+                .serve_at("/org/freedesktop/login1", MockManager { by_pid })?
+                .serve_at(session_path("c1"), MockSession { id: "c1" })?
+                .serve_at(session_path("c2"), MockSession { id: "c2" })
+        });
+        let manager = ManagerProxy::new(&connection)?;
+        let path = current_session(&manager, fallback.map(str::to_owned))?;
+        SessionProxy::builder(&connection).path(path)?.build()?.id()
+    }
+
+    #[test]
+    fn session_of_the_current_process() {
+        assert_eq!(current_session_id(true, None).unwrap(), "c1");
+        // `XDG_SESSION_ID` may name another session.
+        assert_eq!(current_session_id(true, Some("c2")).unwrap(), "c1");
+        assert_eq!(current_session_id(false, Some("c2")).unwrap(), "c2");
+        assert!(current_session_id(false, None).is_err());
+        assert!(current_session_id(false, Some("c3")).is_err());
+    }
+}
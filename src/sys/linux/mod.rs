@@ -0,0 +1,560 @@
+mod agent;
+mod authority;
+mod fprint;
+mod invalidation;
+mod logind;
+#[cfg(feature = "pam")]
+mod pam;
+mod pkexec;
+mod polkit;
+mod process;
+#[cfg(feature = "audit-chain")]
+mod secret;
+mod session;
+
+use std::{
+    ffi::CStr,
+    fmt,
+    mem::MaybeUninit,
+    process::{Command, ExitStatus},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use zbus::blocking::Connection;
+
+pub(crate) use invalidation::InvalidationWatch;
+#[cfg(feature = "audit-chain")]
+pub(crate) use secret::{read as read_secret, write as write_secret};
+
+use crate::{
+    linux::{Conversation, Invalidation, OutputStream, Subject, TemporaryAuthorization},
+    AuthenticationMethod, BiometricStrength, Error, Identity, LinuxBackend, Outcome, PolicyError,
+    Result, Text, TextBuf,
+};
+
+pub(crate) type RawContext = ();
+
+type SharedConversation = Arc<Mutex<Box<dyn Conversation>>>;
+
+/// The connection to the system bus shared by the backends, which is opened
+/// when first needed.
+#[derive(Clone, Default)]
+struct SystemBus(Arc<Mutex<Option<Connection>>>);
+
+impl SystemBus {
+    fn get(&self) -> Result<Connection> {
+        let mut connection = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(connection) = &*connection {
+            return Ok(connection.clone());
+        }
+        let opened = Connection::system().map_err(|_| Error::Unavailable)?;
+        *connection = Some(opened.clone());
+        Ok(opened)
+    }
+}
+
+#[cfg(test)]
+impl From<Connection> for SystemBus {
+    fn from(connection: Connection) -> Self {
+        Self(Arc::new(Mutex::new(Some(connection))))
+    }
+}
+
+pub(crate) struct Context {
+    bus: SystemBus,
+    conversation: Option<SharedConversation>,
+    pending: Arc<polkit::PendingChecks>,
+    verifications: Arc<fprint::PendingVerifications>,
+    process: process::ProcessDetails,
+    subject: Option<Subject>,
+}
+
+impl fmt::Debug for Context {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Context")
+            .field("conversation", &self.conversation.is_some())
+            .field("pending", &self.pending)
+            .field("verifications", &self.verifications)
+            .field("process", &self.process)
+            .field("subject", &self.subject)
+            .finish()
+    }
+}
+
+impl Context {
+    pub(crate) fn new(_: RawContext) -> Self {
+        Self {
+            bus: Default::default(),
+            conversation: None,
+            pending: Default::default(),
+            verifications: Default::default(),
+            process: Default::default(),
+            subject: None,
+        }
+    }
+
+    pub(crate) fn set_conversation(&mut self, conversation: Box<dyn Conversation>) {
+        self.conversation = Some(Arc::new(Mutex::new(conversation)));
+    }
+
+    pub(crate) fn set_process_details(&mut self, enabled: bool) {
+        self.process.disabled = !enabled;
+    }
+
+    pub(crate) fn set_command_line_redaction(&mut self, redaction: process::Redaction) {
+        self.process.redaction = Some(redaction);
+    }
+
+    pub(crate) fn set_subject(&mut self, subject: Subject) {
+        self.subject = Some(subject);
+    }
+
+    pub(crate) fn cancel(&self) -> Result<()> {
+        self.verifications.cancel();
+        self.pending.cancel()
+    }
+
+    pub(crate) fn temporary_authorizations(&self) -> Result<Vec<TemporaryAuthorization>> {
+        polkit::temporary_authorizations(&self.bus.get()?, self.subject.as_ref())
+    }
+
+    pub(crate) fn revoke_cached_authorization(&self) -> Result<()> {
+        polkit::revoke_temporary_authorizations(&self.bus.get()?, self.subject.as_ref())
+    }
+
+    pub(crate) fn watch_invalidation(
+        &self,
+        callback: Box<dyn Fn(Invalidation) + Send>,
+    ) -> Result<InvalidationWatch> {
+        InvalidationWatch::new(&self.bus.get()?, self.subject.clone(), callback)
+    }
+
+    pub(crate) fn authorize_and_run(
+        &self,
+        action_id: &str,
+        command: &Command,
+        output: &mut dyn FnMut(OutputStream, &[u8]),
+    ) -> Result<(ExitStatus, Outcome)> {
+        // `pkexec` checks the authorization of its parent, i.e., the current
+        // process, so the subject is ignored. The agent is kept registered in
+        // case `pkexec` prompts again.
+        self.check_interactive(false, true, Some(action_id), None)?;
+        let bus = self.bus.get()?;
+        let agent =
+            agent::Agent::register(&bus, self.conversation.clone(), Identity::Administrator);
+        polkit::authenticate(&bus, None, action_id, &self.pending, &self.process, None)?;
+        let status = pkexec::run(command, output)?;
+        // The agent of the session may have used any method, or none if the
+        // authorization was retained.
+        let outcome = match agent.and_then(|agent| agent.approver()) {
+            Some(user) => Outcome {
+                user: Some(user),
+                method: Some(AuthenticationMethod::Password),
+            },
+            None => Outcome::default(),
+        };
+        Ok((status, outcome))
+    }
+
+    // TODO: fix the async authenticate function
+    //
+    // #[cfg(feature = "async")]
+    // pub(crate) async fn authenticate_async(
+    //     &self,
+    //     _: Text<'_>,
+    //     _: &Policy,
+    // ) -> Result<()> {
+    //     Err(Error::Unknown)
+    // }
+
+    pub(crate) fn authenticate<F>(
+        &self,
+        message: Text,
+        policy: &crate::Policy,
+        callback: F,
+    ) -> Result<()>
+    where
+        F: Fn(Result<Outcome>) + Send + 'static,
+    {
+        let crate::PolicyBuilder {
+            linux_backend: backend,
+            identity,
+            ..
+        } = &policy.builder;
+        let Policy {
+            biometrics,
+            password,
+        } = policy.inner;
+        let action_id = match backend {
+            LinuxBackend::Polkit { action_id } => {
+                Some(LinuxBackend::polkit_action_id(action_id, identity))
+            }
+            LinuxBackend::Pam { .. } => None,
+        };
+        // Fingerprints can only be verified for the current user, and only
+        // polkit prompts in the session of the subject.
+        let biometrics = self.check_interactive(
+            biometrics && *identity == Identity::CurrentUser,
+            password,
+            action_id,
+            action_id.and(self.subject.as_ref()),
+        )?;
+        // All backends block until the user has responded to the prompt.
+        let message = TextBuf::from(message);
+        let backend = backend.clone();
+        let identity = identity.clone();
+        let bus = self.bus.clone();
+        let conversation = self.conversation.clone();
+        let pending = self.pending.clone();
+        let verifications = self.verifications.clone();
+        let process = self.process.clone();
+        let subject = self.subject.clone();
+
+        thread::Builder::new()
+            .name("robius-authentication".to_owned())
+            .spawn(move || {
+                let text = message.as_text();
+                let mut result = Err(Error::Unavailable);
+                if biometrics {
+                    result = bus
+                        .get()
+                        .and_then(|connection| {
+                            fprint::authenticate(
+                                &connection,
+                                text.linux.message,
+                                conversation.as_ref(),
+                                &verifications,
+                            )
+                        })
+                        .map(|()| Outcome {
+                            user: current_user().ok(),
+                            method: Some(AuthenticationMethod::Biometrics),
+                        });
+                }
+                // Like on other platforms, the password is a fallback for
+                // failed or unavailable biometrics.
+                if password
+                    && !matches!(
+                        result,
+                        Ok(_) | Err(Error::UserCanceled | Error::AppCanceled)
+                    )
+                {
+                    result = match backend {
+                        LinuxBackend::Polkit { action_id } => {
+                            let action_id = LinuxBackend::polkit_action_id(&action_id, &identity);
+                            // Only registered if the session has no agent and
+                            // the subject is the current process.
+                            let connection = bus.get();
+                            let agent = match (&subject, &connection) {
+                                (None, Ok(connection)) => agent::Agent::register(
+                                    connection,
+                                    conversation,
+                                    identity.clone(),
+                                ),
+                                _ => None,
+                            };
+                            connection
+                                .and_then(|connection| {
+                                    polkit::authenticate(
+                                        &connection,
+                                        Some(&text.linux),
+                                        action_id,
+                                        &pending,
+                                        &process,
+                                        subject.as_ref(),
+                                    )
+                                })
+                                .map(|()| {
+                                    match agent.and_then(|agent| agent.approver()) {
+                                        Some(user) => Outcome {
+                                            user: Some(user),
+                                            method: Some(AuthenticationMethod::Password),
+                                        },
+                                        // Only the current user can approve `auth_self`
+                                        // actions for the current process. The agent of
+                                        // the session may have used any method, or none
+                                        // if the authorization was retained.
+                                        None if identity == Identity::CurrentUser
+                                            && subject.is_none() =>
+                                        {
+                                            Outcome {
+                                                user: current_user().ok(),
+                                                method: None,
+                                            }
+                                        }
+                                        None => Outcome::default(),
+                                    }
+                                })
+                        }
+                        #[cfg(feature = "pam")]
+                        LinuxBackend::Pam { service } => {
+                            authenticate_pam(&service, identity, text.linux.message, conversation)
+                                .map(|user| Outcome {
+                                    user: Some(user),
+                                    method: Some(AuthenticationMethod::Password),
+                                })
+                        }
+                        #[cfg(not(feature = "pam"))]
+                        LinuxBackend::Pam { .. } => {
+                            let _ = conversation;
+                            Err(Error::Unavailable)
+                        }
+                    };
+                }
+                callback(result);
+            })
+            .map_err(|_| Error::Unknown)?;
+        Ok(())
+    }
+}
+
+impl Context {
+    /// Returns [`Error::NotInteractive`] if the user can't respond to a prompt,
+    /// e.g., in a cron job or a locked session, and otherwise whether to use
+    /// biometrics.
+    ///
+    /// `action_id` is the polkit action checked if `password` is set, and
+    /// `subject` the subject whose session displays its prompt.
+    fn check_interactive(
+        &self,
+        biometrics: bool,
+        password: bool,
+        action_id: Option<&str>,
+        subject: Option<&Subject>,
+    ) -> Result<bool> {
+        let session = self
+            .bus
+            .get()
+            .ok()
+            .and_then(|bus| session::Session::of(&bus, subject));
+        let (biometrics, prompt) = match session {
+            Some(session) if session.locked || !session.active => {
+                return Err(Error::NotInteractive)
+            }
+            // The prompt is displayed by the agent of the subject's session,
+            // and fprintd can only verify the user of the current process.
+            Some(_) if subject.is_some() => (false, password && action_id.is_some()),
+            // Without a conversation, instructions and prompts are displayed
+            // on the terminal, or by the session's polkit agent in graphical
+            // sessions.
+            Some(session) => {
+                let conversation = self.conversation.is_some() || session::has_terminal();
+                (
+                    // The user can't touch the fingerprint reader of a remote
+                    // machine.
+                    biometrics && !session.remote && (conversation || session.graphical),
+                    password && (conversation || session.graphical && action_id.is_some()),
+                )
+            }
+            // Outside of a login session, e.g., in a cron job or a system
+            // service, only a conversation set by the app may reach the user.
+            None => (
+                false,
+                password && subject.is_none() && self.conversation.is_some(),
+            ),
+        };
+
+        if biometrics || prompt {
+            return Ok(biometrics);
+        }
+        // Actions may be authorized without prompting, e.g., by polkit rules
+        // for system services or by a temporary authorization.
+        match action_id {
+            Some(action_id)
+                if password
+                    && self
+                        .bus
+                        .get()
+                        .is_ok_and(|bus| polkit::is_authorized(&bus, action_id, subject)) =>
+            {
+                Ok(false)
+            }
+            _ => Err(Error::NotInteractive),
+        }
+    }
+}
+
+/// Runs PAM conversations until authentication succeeds or the conversation
+/// gives up retrying, returning the name of the authenticated user.
+///
+/// Falls back to the controlling terminal if no conversation was set.
+#[cfg(feature = "pam")]
+fn authenticate_pam(
+    service: &str,
+    identity: Identity,
+    message: &str,
+    conversation: Option<SharedConversation>,
+) -> Result<String> {
+    let mut guard;
+    let mut terminal;
+    let conversation: &mut dyn Conversation = match &conversation {
+        Some(conversation) => {
+            guard = conversation.lock().unwrap_or_else(|e| e.into_inner());
+            &mut **guard
+        }
+        None => {
+            terminal = crate::linux::Terminal::open()?;
+            &mut terminal
+        }
+    };
+    let user = match identity {
+        Identity::CurrentUser => current_user()?,
+        // Rejected by `PolicyBuilder::try_build`.
+        Identity::Administrator => return Err(Error::Unavailable),
+        Identity::User(name) => name.into_owned(),
+    };
+
+    conversation.begin(message);
+    loop {
+        match pam::authenticate(service, &user, conversation) {
+            Err(error) if conversation.retry(&error) => continue,
+            result => return result,
+        }
+    }
+}
+
+/// The backend and identity are read from the
+/// [`PolicyBuilder`](crate::PolicyBuilder) of the policy when authenticating.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Policy {
+    biometrics: bool,
+    password: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct PolicyBuilder {
+    pam: bool,
+    biometrics: bool,
+    password: bool,
+    current_user: bool,
+    administrator: bool,
+}
+
+impl PolicyBuilder {
+    pub(crate) const fn new() -> Self {
+        Self {
+            pam: false,
+            biometrics: true,
+            password: true,
+            current_user: true,
+            administrator: false,
+        }
+    }
+
+    pub(crate) const fn biometrics(mut self, strength: Option<BiometricStrength>) -> Self {
+        self.biometrics = strength.is_some();
+        self
+    }
+
+    pub(crate) const fn password(mut self, password: bool) -> Self {
+        self.password = password;
+        self
+    }
+
+    pub(crate) const fn companion(self, _: bool) -> Self {
+        self
+    }
+
+    pub(crate) const fn wrist_detection(self, _: bool) -> Self {
+        self
+    }
+
+    pub(crate) const fn linux_backend(mut self, backend: &LinuxBackend) -> Self {
+        self.pam = matches!(backend, LinuxBackend::Pam { .. });
+        self
+    }
+
+    pub(crate) const fn identity(mut self, identity: &Identity) -> Self {
+        self.current_user = matches!(identity, Identity::CurrentUser);
+        self.administrator = matches!(identity, Identity::Administrator);
+        self
+    }
+
+    pub(crate) const fn try_build(self) -> std::result::Result<Policy, PolicyError> {
+        if !self.biometrics && !self.password {
+            return Err(PolicyError::UnsupportedMethods);
+        }
+        // Other identities can only authenticate using a password.
+        if !self.current_user && !self.password {
+            return Err(PolicyError::UnsupportedIdentity);
+        }
+        if self.pam {
+            if !cfg!(feature = "pam") {
+                return Err(PolicyError::UnsupportedBackend);
+            }
+            // Authenticating as `root` always fails for unprivileged
+            // processes, and many systems have no `root` password.
+            if self.administrator {
+                return Err(PolicyError::UnsupportedIdentity);
+            }
+        }
+        Ok(Policy {
+            biometrics: self.biometrics,
+            password: self.password,
+        })
+    }
+}
+
+/// Returns the name of the user running the current process.
+fn current_user() -> Result<String> {
+    user_name(unsafe { libc::getuid() })
+}
+
+/// Returns the name of the user with the given ID.
+fn user_name(uid: libc::uid_t) -> Result<String> {
+    let mut passwd = MaybeUninit::<libc::passwd>::uninit();
+    let mut buf = vec![0 as libc::c_char; 1024];
+    let mut result = std::ptr::null_mut();
+
+    loop {
+        let status = unsafe {
+            libc::getpwuid_r(
+                uid,
+                passwd.as_mut_ptr(),
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut result,
+            )
+        };
+        match status {
+            libc::ERANGE if buf.len() < 1 << 20 => buf.resize(buf.len() * 2, 0),
+            0 if !result.is_null() => break,
+            _ => return Err(Error::UserUnknown),
+        }
+    }
+
+    let passwd = unsafe { passwd.assume_init() };
+    let name = unsafe { CStr::from_ptr(passwd.pw_name) };
+    name.to_str()
+        .map(str::to_owned)
+        .map_err(|_| Error::UserUnknown)
+}
+
+/// Connects to a mock D-Bus service on a peer-to-peer connection.
+///
+/// Returns the connection of the client and that of the service, which is
+/// set up by `serve`.
+#[cfg(test)]
+fn mock_service<F>(serve: F) -> (Connection, Connection)
+where
+    F: FnOnce(
+            zbus::blocking::connection::Builder<'static>,
+        ) -> zbus::Result<zbus::blocking::connection::Builder<'static>>
+        + Send
+        + 'static,
+{
+    use zbus::blocking::connection::Builder;
+
+    let (service, client) = std::os::unix::net::UnixStream::pair().unwrap();
+    let service = thread::spawn(move || {
+        let builder = Builder::async_io_unix_stream(service)
+            .server(zbus::Guid::generate())
+            .unwrap()
+            .p2p()
+            .method_timeout(std::time::Duration::from_secs(10));
+        serve(builder).and_then(|builder| builder.build()).unwrap()
+    });
+    let client = Builder::async_io_unix_stream(client).p2p().build().unwrap();
+    (client, service.join().unwrap())
+}
@@ -0,0 +1,265 @@
+//! Authentication using a PAM conversation.
+
+use std::{
+    ffi::{CStr, CString},
+    mem,
+    os::raw::{c_char, c_int, c_void},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+use crate::{linux::Conversation, Error, Result};
+
+// Definitions from Linux-PAM's `security/_pam_types.h`.
+const PAM_SUCCESS: c_int = 0;
+const PAM_BUF_ERR: c_int = 5;
+const PAM_PERM_DENIED: c_int = 6;
+const PAM_AUTH_ERR: c_int = 7;
+const PAM_CRED_INSUFFICIENT: c_int = 8;
+const PAM_AUTHINFO_UNAVAIL: c_int = 9;
+const PAM_USER_UNKNOWN: c_int = 10;
+const PAM_MAXTRIES: c_int = 11;
+const PAM_NEW_AUTHTOK_REQD: c_int = 12;
+const PAM_ACCT_EXPIRED: c_int = 13;
+const PAM_CONV_ERR: c_int = 19;
+
+const PAM_DISALLOW_NULL_AUTHTOK: c_int = 0x1;
+
+const PAM_USER: c_int = 2;
+
+const PAM_PROMPT_ECHO_OFF: c_int = 1;
+const PAM_PROMPT_ECHO_ON: c_int = 2;
+const PAM_ERROR_MSG: c_int = 3;
+const PAM_TEXT_INFO: c_int = 4;
+
+const PAM_MAX_NUM_MSG: c_int = 32;
+
+#[repr(C)]
+struct PamHandle {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct PamMessage {
+    msg_style: c_int,
+    msg: *const c_char,
+}
+
+#[repr(C)]
+struct PamResponse {
+    resp: *mut c_char,
+    resp_retcode: c_int,
+}
+
+#[repr(C)]
+struct PamConv {
+    conv: unsafe extern "C" fn(
+        c_int,
+        *mut *const PamMessage,
+        *mut *mut PamResponse,
+        *mut c_void,
+    ) -> c_int,
+    appdata_ptr: *mut c_void,
+}
+
+#[link(name = "pam")]
+extern "C" {
+    fn pam_start(
+        service_name: *const c_char,
+        user: *const c_char,
+        pam_conversation: *const PamConv,
+        pamh: *mut *mut PamHandle,
+    ) -> c_int;
+    fn pam_authenticate(pamh: *mut PamHandle, flags: c_int) -> c_int;
+    fn pam_acct_mgmt(pamh: *mut PamHandle, flags: c_int) -> c_int;
+    fn pam_get_item(pamh: *const PamHandle, item_type: c_int, item: *mut *const c_void) -> c_int;
+    fn pam_end(pamh: *mut PamHandle, pam_status: c_int) -> c_int;
+}
+
+/// The state shared with [`converse`].
+struct AppData<'a> {
+    conversation: &'a mut dyn Conversation,
+    /// The error returned by the conversation, if any.
+    error: Option<Error>,
+}
+
+/// Authenticates `user` against the PAM `service`, displaying prompts using
+/// the given `conversation`.
+///
+/// Returns the name of the authenticated user.
+pub(super) fn authenticate(
+    service: &str,
+    user: &str,
+    conversation: &mut dyn Conversation,
+) -> Result<String> {
+    let service = CString::new(service).map_err(|_| Error::Unknown)?;
+    let user = CString::new(user).map_err(|_| Error::Unknown)?;
+
+    let mut app_data = AppData {
+        conversation,
+        error: None,
+    };
+    let conv = PamConv {
+        conv: converse,
+        appdata_ptr: &mut app_data as *mut AppData as *mut c_void,
+    };
+
+    let mut handle = ptr::null_mut();
+    let status = unsafe { pam_start(service.as_ptr(), user.as_ptr(), &conv, &mut handle) };
+    if status != PAM_SUCCESS {
+        return Err(Error::Unavailable);
+    }
+
+    let mut status = unsafe { pam_authenticate(handle, PAM_DISALLOW_NULL_AUTHTOK) };
+    if status == PAM_SUCCESS {
+        // Checks that the account is valid, e.g., that it hasn't expired.
+        status = unsafe { pam_acct_mgmt(handle, PAM_DISALLOW_NULL_AUTHTOK) };
+    }
+    // Modules may map the requested user to another one.
+    let authenticated = if status == PAM_SUCCESS {
+        authenticated_user(handle)
+    } else {
+        None
+    };
+    unsafe { pam_end(handle, status) };
+
+    match (status, app_data.error) {
+        (PAM_SUCCESS, _) => {
+            Ok(authenticated.unwrap_or_else(|| user.to_string_lossy().into_owned()))
+        }
+        // Prefer the error returned by the conversation, e.g., if the user
+        // canceled the prompt.
+        (_, Some(error)) => Err(error),
+        (status, None) => Err(convert(status)),
+    }
+}
+
+/// Returns the user of the PAM transaction.
+fn authenticated_user(handle: *mut PamHandle) -> Option<String> {
+    let mut item = ptr::null();
+    let status = unsafe { pam_get_item(handle, PAM_USER, &mut item) };
+    if status != PAM_SUCCESS || item.is_null() {
+        return None;
+    }
+    let user = unsafe { CStr::from_ptr(item as *const c_char) };
+    user.to_str().ok().map(str::to_owned)
+}
+
+/// The conversation function called by PAM.
+///
+/// # Safety
+///
+/// `appdata_ptr` must point to a valid [`AppData`].
+unsafe extern "C" fn converse(
+    num_msg: c_int,
+    msg: *mut *const PamMessage,
+    resp: *mut *mut PamResponse,
+    appdata_ptr: *mut c_void,
+) -> c_int {
+    let app_data = unsafe { &mut *(appdata_ptr as *mut AppData) };
+    if num_msg <= 0 || num_msg > PAM_MAX_NUM_MSG {
+        return PAM_CONV_ERR;
+    }
+    let num_msg = num_msg as usize;
+
+    // PAM frees the responses, so they must be allocated using `malloc`.
+    let responses =
+        unsafe { libc::calloc(num_msg, mem::size_of::<PamResponse>()) } as *mut PamResponse;
+    if responses.is_null() {
+        return PAM_BUF_ERR;
+    }
+
+    for i in 0..num_msg {
+        // Linux-PAM passes `msg` as a pointer to an array of pointers.
+        let message = unsafe { &**msg.add(i) };
+        let text = unsafe { CStr::from_ptr(message.msg) }.to_string_lossy();
+
+        // Unwinding into PAM is undefined behavior, so a panicking
+        // conversation fails the conversation instead.
+        let conversation = &mut *app_data.conversation;
+        let result = panic::catch_unwind(AssertUnwindSafe(|| match message.msg_style {
+            PAM_PROMPT_ECHO_OFF => conversation.prompt(&text, false).map(Some),
+            PAM_PROMPT_ECHO_ON => conversation.prompt(&text, true).map(Some),
+            PAM_ERROR_MSG => {
+                conversation.error(&text);
+                Ok(None)
+            }
+            PAM_TEXT_INFO => {
+                conversation.info(&text);
+                Ok(None)
+            }
+            _ => Err(Error::Unknown),
+        }))
+        .unwrap_or(Err(Error::Unknown));
+
+        let response = match result {
+            Ok(Some(response)) => match to_c_string(response) {
+                Some(response) => response,
+                None => {
+                    unsafe { free_responses(responses, i) };
+                    return PAM_BUF_ERR;
+                }
+            },
+            Ok(None) => continue,
+            Err(error) => {
+                unsafe { free_responses(responses, i) };
+                app_data.error = Some(error);
+                return PAM_CONV_ERR;
+            }
+        };
+        unsafe { (*responses.add(i)).resp = response };
+    }
+
+    unsafe { *resp = responses };
+    PAM_SUCCESS
+}
+
+/// Copies `response` into a buffer allocated using `malloc`, zeroing the
+/// original.
+fn to_c_string(response: String) -> Option<*mut c_char> {
+    let mut bytes = response.into_bytes();
+    let copy = if bytes.contains(&0) {
+        ptr::null_mut()
+    } else {
+        let copy = unsafe { libc::calloc(bytes.len() + 1, 1) } as *mut c_char;
+        if !copy.is_null() {
+            unsafe { ptr::copy_nonoverlapping(bytes.as_ptr(), copy as *mut u8, bytes.len()) };
+        }
+        copy
+    };
+    bytes.fill(0);
+
+    (!copy.is_null()).then_some(copy)
+}
+
+/// Zeroes and frees the first `len` responses, and then the array itself.
+///
+/// # Safety
+///
+/// `responses` must have been allocated by `calloc` with at least `len`
+/// elements.
+unsafe fn free_responses(responses: *mut PamResponse, len: usize) {
+    for i in 0..len {
+        let response = unsafe { (*responses.add(i)).resp };
+        if !response.is_null() {
+            unsafe {
+                let len = libc::strlen(response);
+                ptr::write_bytes(response, 0, len);
+                libc::free(response as *mut c_void);
+            }
+        }
+    }
+    unsafe { libc::free(responses as *mut c_void) };
+}
+
+fn convert(status: c_int) -> Error {
+    match status {
+        PAM_AUTH_ERR | PAM_CRED_INSUFFICIENT | PAM_PERM_DENIED => Error::Authentication,
+        PAM_MAXTRIES => Error::Exhausted,
+        PAM_USER_UNKNOWN => Error::UserUnknown,
+        PAM_ACCT_EXPIRED => Error::AccountExpired,
+        PAM_NEW_AUTHTOK_REQD => Error::PasswordExpired,
+        PAM_AUTHINFO_UNAVAIL => Error::Unavailable,
+        _ => Error::Unknown,
+    }
+}
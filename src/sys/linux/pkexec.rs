@@ -0,0 +1,176 @@
+//! Running helper programs as an administrator using `pkexec`.
+
+use std::{
+    io::Read,
+    path::Path,
+    process::{Command, ExitStatus, Stdio},
+    sync::mpsc,
+    thread,
+};
+
+use crate::{linux::OutputStream, Error, Result};
+
+/// The exit status of `pkexec` if the user dismissed the authentication
+/// dialog.
+const DISMISSED: i32 = 126;
+/// The exit status of `pkexec` if the caller is not authorized.
+const NOT_AUTHORIZED: i32 = 127;
+
+/// Runs the program and arguments of `command` using `pkexec`, passing its
+/// output to `output` as it is read.
+pub(super) fn run(
+    command: &Command,
+    output: &mut dyn FnMut(OutputStream, &[u8]),
+) -> Result<ExitStatus> {
+    run_with(Path::new("pkexec"), command, output)
+}
+
+/// Runs `command` using the given `pkexec` executable.
+fn run_with(
+    pkexec: &Path,
+    command: &Command,
+    output: &mut dyn FnMut(OutputStream, &[u8]),
+) -> Result<ExitStatus> {
+    // Prompts are displayed by the session's agent or the agent registered
+    // for the current process, never on the terminal.
+    let mut child = Command::new(pkexec)
+        .arg("--disable-internal-agent")
+        .arg(command.get_program())
+        .args(command.get_args())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|_| Error::Unavailable)?;
+
+    let (sender, receiver) = mpsc::channel();
+    let mut readers = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        readers.push(forward(OutputStream::Stdout, stdout, sender.clone()));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        readers.push(forward(OutputStream::Stderr, stderr, sender.clone()));
+    }
+    drop(sender);
+
+    // Ends once both pipes are closed.
+    for (stream, chunk) in receiver {
+        output(stream, &chunk);
+    }
+    for reader in readers {
+        let _ = reader.join();
+    }
+
+    let status = child.wait().map_err(|_| Error::Unknown)?;
+    // Also the statuses of a helper exiting with them, as documented.
+    match status.code() {
+        Some(DISMISSED) => Err(Error::UserCanceled),
+        Some(NOT_AUTHORIZED) => Err(Error::Authentication),
+        _ => Ok(status),
+    }
+}
+
+/// Sends the chunks read from `reader` until it is closed.
+fn forward(
+    stream: OutputStream,
+    mut reader: impl Read + Send + 'static,
+    sender: mpsc::Sender<(OutputStream, Vec<u8>)>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut buf = [0; 4096];
+        while let Ok(len @ 1..) = reader.read(&mut buf) {
+            if sender.send((stream, buf[..len].to_vec())).is_err() {
+                break;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, os::unix::fs::PermissionsExt, path::PathBuf, process};
+
+    use super::*;
+
+    /// A stand-in for `pkexec`, removed when dropped.
+    struct FakePkexec(PathBuf);
+
+    impl FakePkexec {
+        /// Writes a `pkexec` running the given shell script with the
+        /// arguments passed to `pkexec`.
+        fn new(name: &str, script: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("robius-pkexec-{}-{name}", process::id()));
+            fs::write(&path, format!("#!/bin/sh\n{script}\n")).unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o700)).unwrap();
+            Self(path)
+        }
+
+        fn run(&self, output: &mut dyn FnMut(OutputStream, &[u8])) -> Result<ExitStatus> {
+            let mut command = Command::new("/usr/libexec/helper");
+            command.arg("--install");
+            run_with(&self.0, &command, output)
+        }
+    }
+
+    impl Drop for FakePkexec {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn output_is_streamed() {
+        let pkexec = FakePkexec::new(
+            "output",
+            "echo \"$@\"; echo error >&2; printf partial; sleep 0.1; echo ' line'; exit 3",
+        );
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let status = pkexec
+            .run(&mut |stream, chunk| match stream {
+                OutputStream::Stdout => stdout.extend_from_slice(chunk),
+                OutputStream::Stderr => stderr.extend_from_slice(chunk),
+            })
+            .unwrap();
+        assert_eq!(status.code(), Some(3));
+        assert_eq!(
+            String::from_utf8(stdout).unwrap(),
+            "--disable-internal-agent /usr/libexec/helper --install\npartial line\n"
+        );
+        assert_eq!(stderr, b"error\n");
+    }
+
+    #[test]
+    fn output_is_passed_before_exit() {
+        let pkexec = FakePkexec::new("early", "echo ready; sleep 1");
+        let (sender, receiver) = mpsc::channel();
+        let run =
+            thread::spawn(move || pkexec.run(&mut |_, chunk| sender.send(chunk.to_vec()).unwrap()));
+        let chunk = receiver
+            .recv_timeout(std::time::Duration::from_millis(500))
+            .unwrap();
+        assert_eq!(chunk, b"ready\n");
+        assert!(!run.is_finished());
+        assert!(run.join().unwrap().unwrap().success());
+    }
+
+    #[test]
+    fn statuses() {
+        let run = |status: i32| {
+            FakePkexec::new(&status.to_string(), &format!("exit {status}")).run(&mut |_, _| {})
+        };
+        assert!(matches!(run(DISMISSED), Err(Error::UserCanceled)));
+        assert!(matches!(run(NOT_AUTHORIZED), Err(Error::Authentication)));
+        assert_eq!(run(0).unwrap().code(), Some(0));
+        assert_eq!(run(1).unwrap().code(), Some(1));
+    }
+
+    #[test]
+    fn missing_pkexec() {
+        let pkexec = FakePkexec(std::env::temp_dir().join("robius-pkexec-missing"));
+        assert!(matches!(
+            pkexec.run(&mut |_, _| {}),
+            Err(Error::Unavailable)
+        ));
+    }
+}
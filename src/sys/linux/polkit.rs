@@ -0,0 +1,791 @@
+//! Authentication using the desktop environment's polkit authentication agent.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, SystemTime},
+};
+
+use zbus::blocking::Connection;
+
+use super::{
+    authority::{self, AuthorityProxy, DbusSubject},
+    logind,
+    process::{self, ProcessDetails},
+};
+use crate::{
+    linux::{Subject, SubjectKind, TemporaryAuthorization},
+    text::LinuxText,
+    Error, Result,
+};
+
+const ALLOW_USER_INTERACTION: u32 = 0x1;
+
+/// The annotation listing the identities allowed to pass details when
+/// checking an action.
+const OWNER_ANNOTATION: &str = "org.freedesktop.policykit.owner";
+
+/// The authorization checks in progress, which can be canceled.
+#[derive(Debug, Default)]
+pub(super) struct PendingChecks {
+    /// The connection each check was made on, by cancellation ID.
+    ///
+    /// A check can only be canceled by the connection that made it.
+    checks: Mutex<HashMap<String, Connection>>,
+}
+
+impl PendingChecks {
+    /// Cancels all checks in progress, which then fail with
+    /// [`Error::AppCanceled`].
+    pub(super) fn cancel(&self) -> Result<()> {
+        let checks = self.checks.lock().unwrap_or_else(|e| e.into_inner());
+        for (id, connection) in checks.iter() {
+            AuthorityProxy::new(connection)
+                .and_then(|authority| authority.cancel_check_authorization(id))
+                .map_err(convert)?;
+        }
+        Ok(())
+    }
+
+    fn insert(&self, id: &str, connection: &Connection) {
+        let mut checks = self.checks.lock().unwrap_or_else(|e| e.into_inner());
+        checks.insert(id.to_owned(), connection.clone());
+    }
+
+    fn remove(&self, id: &str) {
+        let mut checks = self.checks.lock().unwrap_or_else(|e| e.into_inner());
+        checks.remove(id);
+    }
+}
+
+/// Checks the authorization of the subject for the action, allowing user
+/// interaction.
+///
+/// Without `text`, the agent displays the message and icon of the action.
+pub(super) fn authenticate(
+    connection: &Connection,
+    text: Option<&LinuxText<'_>>,
+    action_id: &str,
+    pending: &PendingChecks,
+    process: &ProcessDetails,
+    subject: Option<&Subject>,
+) -> Result<()> {
+    static NEXT_CANCELLATION_ID: AtomicU64 = AtomicU64::new(0);
+
+    let authority = AuthorityProxy::new(connection).map_err(convert)?;
+    let current_user = super::current_user()?;
+    let user = match subject {
+        Some(subject) => subject_user(connection, subject),
+        None => Some(current_user.clone()),
+    };
+    let subject = match subject {
+        Some(subject) => authority::subject(subject)?,
+        None => authority::subject(&Subject::current_process()?)?,
+    };
+    let process_details = process.details();
+    let icon_name = text
+        .and_then(|text| text.icon_name)
+        .map(str::to_owned)
+        .or_else(process::icon_name);
+
+    let details = details(
+        &authority,
+        action_id,
+        (unsafe { libc::getuid() }, &current_user),
+        user.as_deref(),
+        text,
+        &process_details,
+        icon_name.as_deref(),
+    );
+
+    let cancellation_id = format!(
+        "robius-authentication-{}",
+        NEXT_CANCELLATION_ID.fetch_add(1, Ordering::Relaxed)
+    );
+    pending.insert(&cancellation_id, connection);
+    let result = authority.check_authorization(
+        &subject,
+        action_id,
+        details,
+        ALLOW_USER_INTERACTION,
+        &cancellation_id,
+    );
+    pending.remove(&cancellation_id);
+
+    let (is_authorized, _, details) = result.map_err(convert)?;
+    if is_authorized {
+        Ok(())
+    } else if details
+        .get("polkit.dismissed")
+        .is_some_and(|value| value == "true")
+    {
+        Err(Error::UserCanceled)
+    } else {
+        Err(Error::Authentication)
+    }
+}
+
+/// Returns the details passed to the agent by the caller, a process of the
+/// given user ID and name, when checking the action for a subject of `user`.
+///
+/// Polkit fails the check if an untrusted caller passes details, in which
+/// case none are returned and the agent displays the message and icon of the
+/// action.
+fn details<'a>(
+    authority: &AuthorityProxy,
+    action_id: &str,
+    (uid, caller): (u32, &str),
+    user: Option<&'a str>,
+    text: Option<&'a LinuxText<'a>>,
+    process_details: &'a [(&'static str, String)],
+    icon_name: Option<&'a str>,
+) -> HashMap<&'a str, &'a str> {
+    let mut details = HashMap::new();
+    if !is_trusted(authority, action_id, uid, caller) {
+        return details;
+    }
+    // Inserted first so that they can't override the details set below.
+    details.extend(
+        text.into_iter()
+            .flat_map(|text| text.details)
+            .map(|detail| (&*detail.key, &*detail.value)),
+    );
+    if let Some(user) = user {
+        details.insert("user", user);
+        details.insert("user.display", user);
+    }
+    for (key, value) in process_details {
+        details.insert(key, value);
+    }
+    if let Some(text) = text {
+        details.insert("polkit.message", text.message);
+        if let Some(gettext_domain) = text.gettext_domain {
+            details.insert("polkit.gettext_domain", gettext_domain);
+        }
+    }
+    if let Some(icon_name) = icon_name {
+        details.insert("polkit.icon_name", icon_name);
+    }
+    details
+}
+
+/// Returns the name of the subject's user, if it can be resolved.
+fn subject_user(connection: &Connection, subject: &Subject) -> Option<String> {
+    let uid = match &subject.kind {
+        SubjectKind::Process { uid, .. } => *uid,
+        SubjectKind::Session(_) => {
+            logind::session(connection, Some(subject))
+                .and_then(|session| session.user().map_err(|_| Error::Unavailable))
+                .ok()?
+                .0
+        }
+        SubjectKind::SystemBusName(name) => logind::bus_name_uid(connection, name).ok()?,
+    };
+    super::user_name(uid).ok()
+}
+
+/// Returns whether polkit accepts details from a process of the given user
+/// when checking the action, i.e., whether the user is `root` or an owner of
+/// the action.
+///
+/// The owners are listed in the `org.freedesktop.policykit.owner` annotation
+/// of the action.
+fn is_trusted(authority: &AuthorityProxy, action_id: &str, uid: u32, user: &str) -> bool {
+    if uid == 0 {
+        return true;
+    }
+    let Ok(actions) = authority.enumerate_actions("") else {
+        return false;
+    };
+    let Some((.., annotations)) = actions.iter().find(|action| action.0 == action_id) else {
+        return false;
+    };
+    annotations
+        .get(OWNER_ANNOTATION)
+        .into_iter()
+        .flat_map(|owners| owners.split_whitespace())
+        .filter_map(|owner| owner.strip_prefix("unix-user:"))
+        .any(|owner| owner == user || owner == uid.to_string())
+}
+
+/// Returns whether the subject, or the current process, is authorized for the
+/// action without prompting.
+pub(super) fn is_authorized(
+    connection: &Connection,
+    action_id: &str,
+    subject: Option<&Subject>,
+) -> bool {
+    let result = (|| {
+        let subject = match subject {
+            Some(subject) => authority::subject(subject)?,
+            None => authority::subject(&Subject::current_process()?)?,
+        };
+        AuthorityProxy::new(connection)
+            .and_then(|authority| {
+                authority.check_authorization(&subject, action_id, HashMap::new(), 0, "")
+            })
+            .map_err(convert)
+    })();
+    matches!(result, Ok((true, _, _)))
+}
+
+/// Returns the temporary authorizations of the subject's session.
+pub(super) fn temporary_authorizations(
+    connection: &Connection,
+    subject: Option<&Subject>,
+) -> Result<Vec<TemporaryAuthorization>> {
+    let session = session_subject(connection, subject)?;
+    let authorizations = AuthorityProxy::new(connection)
+        .and_then(|authority| authority.enumerate_temporary_authorizations(&session))
+        .map_err(convert)?;
+
+    let time = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+    Ok(authorizations
+        .into_iter()
+        .map(
+            |(id, action_id, _, obtained, expires)| TemporaryAuthorization {
+                id,
+                action_id,
+                obtained: time(obtained),
+                expires: time(expires),
+            },
+        )
+        .collect())
+}
+
+/// Revokes the temporary authorizations of the subject's session.
+pub(super) fn revoke_temporary_authorizations(
+    connection: &Connection,
+    subject: Option<&Subject>,
+) -> Result<()> {
+    let session = session_subject(connection, subject)?;
+    AuthorityProxy::new(connection)
+        .and_then(|authority| authority.revoke_temporary_authorizations(&session))
+        .map_err(convert)
+}
+
+/// Returns the session of the subject, or of the current process.
+///
+/// Polkit only manages temporary authorizations by session.
+fn session_subject(connection: &Connection, subject: Option<&Subject>) -> Result<DbusSubject> {
+    let pid = match subject.map(|subject| &subject.kind) {
+        Some(SubjectKind::Session(_)) => return authority::subject(subject.unwrap()),
+        Some(SubjectKind::Process { pid, .. }) => *pid,
+        Some(SubjectKind::SystemBusName(name)) => logind::bus_name_pid(connection, name)?,
+        None => std::process::id(),
+    };
+    let session_id = logind::session_id(connection, pid)?;
+    authority::subject(&Subject::session(session_id))
+}
+
+fn convert(error: zbus::Error) -> Error {
+    let zbus::Error::MethodError(name, ..) = &error else {
+        return Error::Unavailable;
+    };
+    match name.as_str() {
+        "org.freedesktop.PolicyKit1.Error.Cancelled" => Error::AppCanceled,
+        // E.g., checking a subject of another user.
+        "org.freedesktop.PolicyKit1.Error.NotAuthorized" => Error::PermissionDenied,
+        // E.g., the action is not declared.
+        "org.freedesktop.PolicyKit1.Error.Failed" => Error::Unavailable,
+        "org.freedesktop.DBus.Error.ServiceUnknown" => Error::Unavailable,
+        _ => Error::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        sync::{Arc, Condvar},
+        thread,
+    };
+
+    use zbus::zvariant::Str;
+
+    use super::*;
+    use crate::sys::linux::Context;
+
+    /// Checks of this action wait until they are canceled.
+    const BLOCKING_ACTION: &str = "rs.robius.authentication.test.blocking";
+
+    #[derive(Debug, zbus::DBusError)]
+    #[zbus(prefix = "org.freedesktop.PolicyKit1.Error")]
+    enum MockError {
+        #[zbus(error)]
+        ZBus(zbus::Error),
+        Cancelled(String),
+        NotAuthorized(String),
+        Failed(String),
+    }
+
+    #[derive(Default)]
+    struct State {
+        /// The action ID, details and flags of each check.
+        checks: Vec<(String, HashMap<String, String>, u32)>,
+        /// The subject of each check.
+        subjects: Vec<DbusSubject>,
+        canceled: HashSet<String>,
+        /// The session IDs whose temporary authorizations were revoked.
+        revoked: Vec<String>,
+    }
+
+    /// A stand-in for the polkit authority, returning the same result for
+    /// every check.
+    #[derive(Clone)]
+    struct MockAuthority {
+        authorized: bool,
+        dismissed: bool,
+        /// The owner annotation of the declared actions.
+        owner: Option<String>,
+        /// The name of the error returned by every check, if any.
+        error: Option<&'static str>,
+        state: Arc<(Mutex<State>, Condvar)>,
+    }
+
+    impl MockAuthority {
+        fn new(authorized: bool, dismissed: bool) -> Self {
+            Self {
+                authorized,
+                dismissed,
+                owner: None,
+                error: None,
+                state: Default::default(),
+            }
+        }
+
+        fn state(&self) -> std::sync::MutexGuard<'_, State> {
+            self.state.0.lock().unwrap()
+        }
+    }
+
+    #[zbus::interface(name = "org.freedesktop.PolicyKit1.Authority")]
+    impl MockAuthority {
+        fn enumerate_actions(&self, _locale: &str) -> Vec<authority::ActionDescription> {
+            let annotations = self
+                .owner
+                .iter()
+                .map(|owner| (OWNER_ANNOTATION.to_owned(), owner.clone()))
+                .collect();
+            vec![(
+                "rs.robius.authentication.authenticate".to_owned(),
+                String::new(),
+                "Authentication is required".to_owned(),
+                String::new(),
+                String::new(),
+                String::new(),
+                2,
+                2,
+                2,
+                annotations,
+            )]
+        }
+
+        async fn check_authorization(
+            &self,
+            subject: DbusSubject,
+            action_id: String,
+            details: HashMap<String, String>,
+            flags: u32,
+            cancellation_id: String,
+        ) -> std::result::Result<(bool, bool, HashMap<String, String>), MockError> {
+            let blocking = action_id == BLOCKING_ACTION;
+            let state = self.state.clone();
+            let canceled = blocking::unblock(move || {
+                let (lock, condvar) = &*state;
+                let mut state = lock.lock().unwrap();
+                state.checks.push((action_id, details, flags));
+                state.subjects.push(subject);
+                condvar.notify_all();
+                while blocking && !state.canceled.contains(&cancellation_id) {
+                    state = condvar.wait(state).unwrap();
+                }
+                blocking
+            })
+            .await;
+            if canceled {
+                return Err(MockError::Cancelled("the check was canceled".to_owned()));
+            }
+            if self.error == Some("NotAuthorized") {
+                return Err(MockError::NotAuthorized("not authorized".to_owned()));
+            }
+            if self.error == Some("Failed") {
+                return Err(MockError::Failed("the action is not registered".to_owned()));
+            }
+
+            let details =
+                HashMap::from([("polkit.dismissed".to_owned(), self.dismissed.to_string())]);
+            Ok((self.authorized, !self.authorized, details))
+        }
+
+        fn cancel_check_authorization(&self, cancellation_id: String) {
+            self.state().canceled.insert(cancellation_id);
+            self.state.1.notify_all();
+        }
+
+        fn enumerate_temporary_authorizations(
+            &self,
+            subject: DbusSubject,
+        ) -> Vec<authority::TemporaryAuthorization> {
+            vec![(
+                "tmpauthz1".to_owned(),
+                "rs.robius.authentication.authenticate".to_owned(),
+                subject,
+                1_000,
+                1_300,
+            )]
+        }
+
+        fn revoke_temporary_authorizations(&self, subject: DbusSubject) {
+            let session_id = subject.1["session-id"].downcast_ref::<Str>().unwrap();
+            self.state().revoked.push(session_id.to_string());
+        }
+    }
+
+    /// Returns a context using a mock authority for polkit.
+    fn mock_context(authority: &MockAuthority) -> (Context, zbus::blocking::Connection) {
+        let (connection, service) = super::super::mock_service({
+            let authority = authority.clone();
+            |builder| builder.serve_at("/org/freedesktop/PolicyKit1/Authority", authority)
+        });
+        let mut context = Context::new(());
+        context.bus = connection.into();
+        (context, service)
+    }
+
+    fn authenticate(context: &Context, action_id: &str) -> Result<()> {
+        authenticate_subject(context, action_id, None)
+    }
+
+    fn authenticate_subject(
+        context: &Context,
+        action_id: &str,
+        subject: Option<&Subject>,
+    ) -> Result<()> {
+        super::authenticate(
+            &context.bus.get().unwrap(),
+            Some(&LinuxText::new("Authenticate")),
+            action_id,
+            &context.pending,
+            &context.process,
+            subject,
+        )
+    }
+
+    #[test]
+    fn authorized() {
+        let authority = MockAuthority::new(true, false);
+        let (context, _authority) = mock_context(&authority);
+        assert!(authenticate(&context, "rs.robius.authentication.authenticate").is_ok());
+        let (action_id, _, flags) = &authority.state().checks[0];
+        assert_eq!(action_id, "rs.robius.authentication.authenticate");
+        assert_eq!(*flags, ALLOW_USER_INTERACTION);
+    }
+
+    #[test]
+    fn owners_are_trusted() {
+        let trusted = |owner: Option<&str>, uid| {
+            let authority = MockAuthority {
+                owner: owner.map(str::to_owned),
+                ..MockAuthority::new(true, false)
+            };
+            let (context, _authority) = mock_context(&authority);
+            let connection = context.bus.get().unwrap();
+            let proxy = AuthorityProxy::new(&connection).unwrap();
+            is_trusted(
+                &proxy,
+                "rs.robius.authentication.authenticate",
+                uid,
+                "alice",
+            )
+        };
+        assert!(trusted(None, 0));
+        assert!(!trusted(None, 1000));
+        assert!(trusted(Some("unix-user:alice"), 1000));
+        assert!(trusted(Some("unix-user:bob unix-user:1000"), 1000));
+        assert!(!trusted(Some("unix-user:bob unix-group:alice"), 1000));
+    }
+
+    #[test]
+    fn details_are_passed_when_trusted() {
+        let authority = MockAuthority::new(true, false);
+        let (context, _authority) = mock_context(&authority);
+        authenticate(&context, "rs.robius.authentication.authenticate").unwrap();
+        let details = &authority.state().checks[0].1;
+        // Root may always pass details.
+        assert_eq!(unsafe { libc::getuid() } == 0, !details.is_empty());
+
+        let user = super::super::current_user().unwrap();
+        let authority = MockAuthority {
+            owner: Some(format!("unix-user:nobody unix-user:{user}")),
+            ..MockAuthority::new(true, false)
+        };
+        let (context, _authority) = mock_context(&authority);
+        authenticate(&context, "rs.robius.authentication.authenticate").unwrap();
+        let details = &authority.state().checks[0].1;
+        assert_eq!(details["polkit.message"], "Authenticate");
+        assert_eq!(details["user"], user);
+    }
+
+    /// Returns a mock authority trusting the current user.
+    fn trusting_authority() -> MockAuthority {
+        let user = super::super::current_user().unwrap();
+        MockAuthority {
+            owner: Some(format!("unix-user:{user}")),
+            ..MockAuthority::new(true, false)
+        }
+    }
+
+    #[test]
+    fn command_line_is_redacted() {
+        let authority = trusting_authority();
+        let (mut context, _authority) = mock_context(&authority);
+        context.set_command_line_redaction(Arc::new(|args| {
+            assert!(!args.is_empty());
+            vec!["app".to_owned(), "--token=<redacted>".to_owned()]
+        }));
+        authenticate(&context, "rs.robius.authentication.authenticate").unwrap();
+        let details = &authority.state().checks[0].1;
+        assert_eq!(details["command_line"], "app --token=<redacted>");
+        let program = std::fs::read_link("/proc/self/exe").unwrap();
+        assert_eq!(details["program"], program.to_str().unwrap());
+
+        // The command line is omitted without arguments.
+        let authority = trusting_authority();
+        let (mut context, _authority) = mock_context(&authority);
+        context.set_command_line_redaction(Arc::new(|_| Vec::new()));
+        authenticate(&context, "rs.robius.authentication.authenticate").unwrap();
+        let details = &authority.state().checks[0].1;
+        assert!(!details.contains_key("command_line"));
+        assert!(details.contains_key("program"));
+    }
+
+    #[test]
+    fn process_details_can_be_disabled() {
+        let authority = trusting_authority();
+        let (mut context, _authority) = mock_context(&authority);
+        context.set_process_details(false);
+        authenticate(&context, "rs.robius.authentication.authenticate").unwrap();
+        let details = &authority.state().checks[0].1;
+        assert!(!details.contains_key("program"));
+        assert!(!details.contains_key("command_line"));
+        assert_eq!(details["polkit.message"], "Authenticate");
+    }
+
+    #[test]
+    fn details_are_omitted_when_untrusted() {
+        let authority = MockAuthority {
+            owner: Some("unix-user:bob".to_owned()),
+            ..MockAuthority::new(true, false)
+        };
+        let (context, _authority) = mock_context(&authority);
+        let connection = context.bus.get().unwrap();
+        let proxy = AuthorityProxy::new(&connection).unwrap();
+        let text = LinuxText::new("Authenticate");
+        let process_details = ProcessDetails::default().details();
+        assert!(process_details.iter().any(|(key, _)| *key == "program"));
+        let details = |caller| {
+            details(
+                &proxy,
+                "rs.robius.authentication.authenticate",
+                (1000, caller),
+                Some("carol"),
+                Some(&text),
+                &process_details,
+                Some("app"),
+            )
+        };
+
+        assert!(details("alice").is_empty());
+        let details = details("bob");
+        assert_eq!(details["program"], process_details[0].1);
+        assert_eq!(details["polkit.icon_name"], "app");
+        assert_eq!(details["user"], "carol");
+    }
+
+    #[test]
+    fn process_subjects() {
+        let authority = trusting_authority();
+        let (context, _authority) = mock_context(&authority);
+        authenticate(&context, "rs.robius.authentication.authenticate").unwrap();
+        let (kind, details) = authority.state().subjects.remove(0);
+        assert_eq!(kind, "unix-process");
+        let pid: u32 = details["pid"].downcast_ref().unwrap();
+        assert_eq!(pid, std::process::id());
+        let uid: i32 = details["uid"].downcast_ref().unwrap();
+        assert_eq!(uid as u32, unsafe { libc::getuid() });
+        let start_time: u64 = details["start-time"].downcast_ref().unwrap();
+        let SubjectKind::Process {
+            start_time: expected,
+            pidfd,
+            ..
+        } = Subject::current_process().unwrap().kind
+        else {
+            unreachable!();
+        };
+        assert_eq!(start_time, expected);
+        // Linux 5.3 or newer.
+        assert_eq!(details.contains_key("pidfd"), pidfd.is_some());
+
+        // Without a pidfd, polkit identifies the process by its PID and start
+        // time.
+        let subject = Subject {
+            kind: SubjectKind::Process {
+                pid: 4242,
+                start_time: 1234,
+                uid: 65534,
+                pidfd: None,
+            },
+        };
+        let authority = trusting_authority();
+        let (context, _authority) = mock_context(&authority);
+        authenticate_subject(
+            &context,
+            "rs.robius.authentication.authenticate",
+            Some(&subject),
+        )
+        .unwrap();
+        let (kind, details) = authority.state().subjects.remove(0);
+        assert_eq!(kind, "unix-process");
+        assert!(!details.contains_key("pidfd"));
+        assert_eq!(details["pid"].downcast_ref::<u32>().unwrap(), 4242);
+        assert_eq!(details["start-time"].downcast_ref::<u64>().unwrap(), 1234);
+        assert_eq!(details["uid"].downcast_ref::<i32>().unwrap(), 65534);
+    }
+
+    #[test]
+    fn user_is_the_subjects() {
+        let subject = Subject {
+            kind: SubjectKind::Process {
+                pid: 4242,
+                start_time: 1234,
+                uid: 65534,
+                pidfd: None,
+            },
+        };
+        let authority = trusting_authority();
+        let (context, _authority) = mock_context(&authority);
+        authenticate_subject(
+            &context,
+            "rs.robius.authentication.authenticate",
+            Some(&subject),
+        )
+        .unwrap();
+        let details = &authority.state().checks[0].1;
+        let user = super::super::user_name(65534).unwrap();
+        assert_eq!(details["user"], user);
+        assert_eq!(details["user.display"], user);
+
+        // The user of a session is only known to logind, which the mock bus
+        // doesn't provide.
+        let authority = trusting_authority();
+        let (context, _authority) = mock_context(&authority);
+        authenticate_subject(
+            &context,
+            "rs.robius.authentication.authenticate",
+            Some(&Subject::session("c1")),
+        )
+        .unwrap();
+        let state = authority.state();
+        assert_eq!(state.subjects[0].0, "unix-session");
+        let details = &state.checks[0].1;
+        assert!(!details.contains_key("user"));
+        assert_eq!(details["polkit.message"], "Authenticate");
+    }
+
+    #[test]
+    fn not_authorized() {
+        let authority = MockAuthority::new(false, false);
+        let (context, _authority) = mock_context(&authority);
+        let result = authenticate(&context, "rs.robius.authentication.authenticate");
+        assert!(matches!(result, Err(Error::Authentication)));
+
+        let authority = MockAuthority::new(false, true);
+        let (context, _authority) = mock_context(&authority);
+        let result = authenticate(&context, "rs.robius.authentication.authenticate");
+        assert!(matches!(result, Err(Error::UserCanceled)));
+    }
+
+    #[test]
+    fn errors() {
+        let authority = MockAuthority {
+            error: Some("NotAuthorized"),
+            ..MockAuthority::new(true, false)
+        };
+        let (context, _authority) = mock_context(&authority);
+        let result = authenticate(&context, "rs.robius.authentication.authenticate");
+        assert!(matches!(result, Err(Error::PermissionDenied)));
+
+        let authority = MockAuthority {
+            error: Some("Failed"),
+            ..MockAuthority::new(true, false)
+        };
+        let (context, _authority) = mock_context(&authority);
+        let result = authenticate(&context, "rs.robius.authentication.authenticate");
+        assert!(matches!(result, Err(Error::Unavailable)));
+    }
+
+    #[test]
+    fn canceled() {
+        let authority = MockAuthority::new(true, false);
+        let (context, _authority) = mock_context(&authority);
+        let context = Arc::new(context);
+        let check = thread::spawn({
+            let context = context.clone();
+            move || authenticate(&context, BLOCKING_ACTION)
+        });
+
+        {
+            let (lock, condvar) = &*authority.state;
+            let _state = condvar
+                .wait_while(lock.lock().unwrap(), |state| state.checks.is_empty())
+                .unwrap();
+        }
+        context.cancel().unwrap();
+        assert!(matches!(check.join().unwrap(), Err(Error::AppCanceled)));
+    }
+
+    #[test]
+    fn check_without_interaction() {
+        let authority = MockAuthority::new(true, false);
+        let (context, _authority) = mock_context(&authority);
+        assert!(is_authorized(
+            &context.bus.get().unwrap(),
+            "rs.robius.authentication.authenticate",
+            None,
+        ));
+        assert_eq!(authority.state().checks[0].2, 0);
+
+        let authority = MockAuthority::new(false, false);
+        let (context, _authority) = mock_context(&authority);
+        assert!(!is_authorized(
+            &context.bus.get().unwrap(),
+            "rs.robius.authentication.authenticate",
+            None,
+        ));
+    }
+
+    #[test]
+    fn temporary_authorizations() {
+        let authority = MockAuthority::new(true, false);
+        let (mut context, _authority) = mock_context(&authority);
+        context.set_subject(Subject::session("c1"));
+
+        let authorizations = context.temporary_authorizations().unwrap();
+        assert_eq!(
+            authorizations,
+            [TemporaryAuthorization {
+                id: "tmpauthz1".to_owned(),
+                action_id: "rs.robius.authentication.authenticate".to_owned(),
+                obtained: SystemTime::UNIX_EPOCH + Duration::from_secs(1_000),
+                expires: SystemTime::UNIX_EPOCH + Duration::from_secs(1_300),
+            }]
+        );
+
+        context.revoke_cached_authorization().unwrap();
+        assert_eq!(authority.state().revoked, ["c1"]);
+    }
+}
@@ -0,0 +1,182 @@
+//! Details about the current process, displayed by polkit authentication
+//! agents so that the user knows which application is asking.
+
+use std::{env, fmt, fs, path::PathBuf, sync::Arc};
+
+pub(super) type Redaction = Arc<dyn Fn(Vec<String>) -> Vec<String> + Send + Sync>;
+
+#[derive(Clone, Default)]
+pub(super) struct ProcessDetails {
+    pub(super) disabled: bool,
+    pub(super) redaction: Option<Redaction>,
+}
+
+impl fmt::Debug for ProcessDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProcessDetails")
+            .field("disabled", &self.disabled)
+            .field("redaction", &self.redaction.is_some())
+            .finish()
+    }
+}
+
+impl ProcessDetails {
+    /// Returns the `program` and `command_line` details, as set by `pkexec`.
+    pub(super) fn details(&self) -> Vec<(&'static str, String)> {
+        let mut details = Vec::new();
+        if self.disabled {
+            return details;
+        }
+
+        if let Ok(program) = fs::read_link("/proc/self/exe") {
+            details.push(("program", program.to_string_lossy().into_owned()));
+        }
+        if let Ok(command_line) = fs::read("/proc/self/cmdline") {
+            let command_line = command_line.strip_suffix(&[0]).unwrap_or(&command_line);
+            let mut args: Vec<_> = command_line
+                .split(|&byte| byte == 0)
+                .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                .collect();
+            if let Some(redact) = &self.redaction {
+                args = redact(args);
+            }
+            if !args.is_empty() {
+                details.push(("command_line", args.join(" ")));
+            }
+        }
+        details
+    }
+}
+
+/// Returns the icon name of the current application from its desktop entry.
+///
+/// The desktop entry is the one that launched the application, if known, or
+/// one named after the executable.
+pub(super) fn icon_name() -> Option<String> {
+    if let Some(path) = env::var_os("GIO_LAUNCHED_DESKTOP_FILE") {
+        if let Some(icon_name) = desktop_entry_icon(&PathBuf::from(path)) {
+            return Some(icon_name);
+        }
+    }
+    // Flatpak exports the application's icon using its ID.
+    if let Ok(app_id) = env::var("FLATPAK_ID") {
+        return Some(app_id);
+    }
+
+    let program = fs::read_link("/proc/self/exe").ok()?;
+    let file_name = format!("{}.desktop", program.file_stem()?.to_str()?);
+    data_dirs()
+        .into_iter()
+        .find_map(|dir| desktop_entry_icon(&dir.join("applications").join(&file_name)))
+}
+
+/// Returns the XDG data directories in order of preference.
+fn data_dirs() -> Vec<PathBuf> {
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")));
+    let data_dirs = env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_owned());
+
+    data_home
+        .into_iter()
+        .chain(data_dirs.split(':').map(PathBuf::from))
+        .collect()
+}
+
+/// Reads the `Icon` key of a desktop entry, ignoring icons given as paths.
+fn desktop_entry_icon(path: &std::path::Path) -> Option<String> {
+    let entry = fs::read_to_string(path).ok()?;
+    let mut in_desktop_entry = false;
+    for line in entry.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_desktop_entry = line == "[Desktop Entry]";
+        } else if let (true, Some(value)) = (in_desktop_entry, line.strip_prefix("Icon")) {
+            let Some(value) = value.trim_start().strip_prefix('=') else {
+                // E.g., a localized key such as `Icon[de]`.
+                continue;
+            };
+            let value = value.trim();
+            return (!value.is_empty() && !value.contains('/')).then(|| value.to_owned());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, process};
+
+    use super::*;
+
+    #[test]
+    fn command_line_redaction() {
+        let details = |redaction: Option<Redaction>| {
+            let process = ProcessDetails {
+                disabled: false,
+                redaction,
+            };
+            process.details().into_iter().collect::<HashMap<_, _>>()
+        };
+
+        let program = fs::read_link("/proc/self/exe").unwrap();
+        let unredacted = details(None);
+        assert_eq!(unredacted["program"], program.to_str().unwrap());
+        let args: Vec<_> = env::args().collect();
+        assert_eq!(unredacted["command_line"], args.join(" "));
+
+        let redacted = details(Some(Arc::new(move |redacted_args| {
+            assert_eq!(redacted_args, args);
+            vec!["app".to_owned(), "<redacted>".to_owned()]
+        })));
+        assert_eq!(redacted["command_line"], "app <redacted>");
+        assert_eq!(redacted["program"], unredacted["program"]);
+
+        let omitted = details(Some(Arc::new(|_| Vec::new())));
+        assert!(!omitted.contains_key("command_line"));
+    }
+
+    #[test]
+    fn disabled() {
+        let process = ProcessDetails {
+            disabled: true,
+            redaction: Some(Arc::new(|_| panic!("the command line was read"))),
+        };
+        assert!(process.details().is_empty());
+    }
+
+    #[test]
+    fn desktop_entry_icons() {
+        let dir = env::temp_dir().join(format!("robius-process-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let icon = |entry: &str| {
+            let path = dir.join("app.desktop");
+            fs::write(&path, entry).unwrap();
+            desktop_entry_icon(&path)
+        };
+
+        assert_eq!(
+            icon("[Desktop Entry]\nName=App\nIcon=org.example.App\n"),
+            Some("org.example.App".to_owned())
+        );
+        assert_eq!(
+            icon("[Desktop Entry]\n  Icon = app  \n"),
+            Some("app".to_owned())
+        );
+        // Localized keys and other groups are ignored.
+        assert_eq!(
+            icon("[Desktop Action new]\nIcon=new\n[Desktop Entry]\nIcon[de]=de\nIcon=app\n"),
+            Some("app".to_owned())
+        );
+        assert_eq!(icon("[Desktop Action new]\nIcon=new\n"), None);
+        assert_eq!(icon("[Desktop Entry]\nIconName=app\n"), None);
+        // Icons given as paths can't be passed to the agent.
+        assert_eq!(icon("[Desktop Entry]\nIcon=/usr/share/app.png\n"), None);
+        assert_eq!(icon("[Desktop Entry]\nIcon=\n"), None);
+        assert_eq!(desktop_entry_icon(&dir.join("missing.desktop")), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,186 @@
+//! Storing secrets in the user's keyring using the Secret Service API, e.g.,
+//! GNOME Keyring or KWallet.
+//!
+//! Secrets are transferred unencrypted over the session bus, which is only
+//! accessible to the user.
+//!
+//! See the [Secret Service API] for more details.
+//!
+//! [Secret Service API]: https://specifications.freedesktop.org/secret-service-spec/latest/
+
+use std::collections::HashMap;
+
+use zbus::{
+    blocking::Connection,
+    zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value},
+};
+
+use crate::{Error, Result};
+
+const DEFAULT_COLLECTION: &str = "/org/freedesktop/secrets/aliases/default";
+
+/// A secret: its session, parameters, value and content type.
+type Secret = (OwnedObjectPath, Vec<u8>, Vec<u8>, String);
+
+#[zbus::proxy(
+    interface = "org.freedesktop.Secret.Service",
+    default_service = "org.freedesktop.secrets",
+    default_path = "/org/freedesktop/secrets",
+    gen_async = false,
+    blocking_name = "ServiceProxy"
+)]
+trait Service {
+    fn open_session(
+        &self,
+        algorithm: &str,
+        input: &Value<'_>,
+    ) -> zbus::Result<(OwnedValue, OwnedObjectPath)>;
+
+    fn search_items(
+        &self,
+        attributes: HashMap<&str, &str>,
+    ) -> zbus::Result<(Vec<OwnedObjectPath>, Vec<OwnedObjectPath>)>;
+
+    fn unlock(
+        &self,
+        objects: &[ObjectPath<'_>],
+    ) -> zbus::Result<(Vec<OwnedObjectPath>, OwnedObjectPath)>;
+
+    fn get_secrets(
+        &self,
+        items: &[ObjectPath<'_>],
+        session: &ObjectPath<'_>,
+    ) -> zbus::Result<HashMap<OwnedObjectPath, Secret>>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.Secret.Collection",
+    default_service = "org.freedesktop.secrets",
+    gen_async = false,
+    blocking_name = "CollectionProxy"
+)]
+trait Collection {
+    fn create_item(
+        &self,
+        properties: HashMap<&str, Value<'_>>,
+        secret: &Secret,
+        replace: bool,
+    ) -> zbus::Result<(OwnedObjectPath, OwnedObjectPath)>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.Secret.Prompt",
+    default_service = "org.freedesktop.secrets",
+    gen_async = false,
+    blocking_name = "PromptProxy"
+)]
+trait Prompt {
+    fn prompt(&self, window_id: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn completed(&self, dismissed: bool, result: Value<'_>) -> zbus::Result<()>;
+}
+
+/// Returns the secret stored for the service and account, if any.
+pub(crate) fn read(service: &str, account: &str) -> Result<Option<Vec<u8>>> {
+    let connection = Connection::session().map_err(|_| Error::Unavailable)?;
+    let proxy = ServiceProxy::new(&connection).map_err(|_| Error::Unavailable)?;
+    let session = open_session(&proxy)?;
+
+    let (unlocked, locked) = proxy
+        .search_items(attributes(service, account))
+        .map_err(|_| Error::Unavailable)?;
+    let item = match (unlocked.into_iter().next(), locked.into_iter().next()) {
+        (Some(item), _) => item,
+        (None, Some(item)) => {
+            let (_, prompt) = proxy
+                .unlock(&[item.as_ref()])
+                .map_err(|_| Error::Unavailable)?;
+            complete(&connection, prompt)?;
+            item
+        }
+        (None, None) => return Ok(None),
+    };
+
+    let mut secrets = proxy
+        .get_secrets(&[item.as_ref()], &session.as_ref())
+        .map_err(|_| Error::Unavailable)?;
+    let (_, _, value, _) = secrets.remove(&item).ok_or(Error::Unknown)?;
+    Ok(Some(value))
+}
+
+/// Stores the secret for the service and account in the default collection,
+/// replacing any secret stored for them.
+pub(crate) fn write(service: &str, account: &str, secret: &[u8]) -> Result<()> {
+    let connection = Connection::session().map_err(|_| Error::Unavailable)?;
+    let proxy = ServiceProxy::new(&connection).map_err(|_| Error::Unavailable)?;
+    let session = open_session(&proxy)?;
+
+    let (_, prompt) = proxy
+        .unlock(&[ObjectPath::from_static_str_unchecked(DEFAULT_COLLECTION)])
+        .map_err(|_| Error::Unavailable)?;
+    complete(&connection, prompt)?;
+
+    let collection = CollectionProxy::builder(&connection)
+        .path(DEFAULT_COLLECTION)
+        .and_then(|builder| builder.build())
+        .map_err(|_| Error::Unavailable)?;
+    let properties = HashMap::from([
+        (
+            "org.freedesktop.Secret.Item.Label",
+            Value::from(format!("{service} ({account})")),
+        ),
+        (
+            "org.freedesktop.Secret.Item.Attributes",
+            Value::from(attributes(service, account)),
+        ),
+    ]);
+    let secret = (
+        session,
+        Vec::new(),
+        secret.to_vec(),
+        "application/octet-stream".to_owned(),
+    );
+    let (_, prompt) = collection
+        .create_item(properties, &secret, true)
+        .map_err(|_| Error::Unavailable)?;
+    complete(&connection, prompt)
+}
+
+/// Returns the attributes identifying the secret of the service and account.
+fn attributes<'a>(service: &'a str, account: &'a str) -> HashMap<&'a str, &'a str> {
+    HashMap::from([("service", service), ("username", account)])
+}
+
+/// Opens a session transferring secrets unencrypted.
+fn open_session(proxy: &ServiceProxy<'_>) -> Result<OwnedObjectPath> {
+    proxy
+        .open_session("plain", &Value::from(""))
+        .map(|(_, session)| session)
+        .map_err(|_| Error::Unavailable)
+}
+
+/// Displays the prompt, e.g., to unlock the keyring, and waits until the
+/// user responded to it.
+///
+/// Does nothing if no prompt is required, i.e., if the path is `/`.
+fn complete(connection: &Connection, prompt: OwnedObjectPath) -> Result<()> {
+    if prompt.as_str() == "/" {
+        return Ok(());
+    }
+    let prompt = PromptProxy::builder(connection)
+        .path(prompt)
+        .and_then(|builder| builder.build())
+        .map_err(|_| Error::Unavailable)?;
+    // Subscribed first so that the signal is not missed.
+    let mut completed = prompt.receive_completed().map_err(|_| Error::Unavailable)?;
+    prompt.prompt("").map_err(|_| Error::Unavailable)?;
+
+    let completed = completed.next().ok_or(Error::Unavailable)?;
+    let args = completed.args().map_err(|_| Error::Unknown)?;
+    if args.dismissed {
+        Err(Error::UserCanceled)
+    } else {
+        Ok(())
+    }
+}
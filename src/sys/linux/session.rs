@@ -0,0 +1,247 @@
+//! Detecting whether the user can respond to a prompt.
+//!
+//! Prompting in a cron job, a system service, a remote session without a
+//! terminal or a locked session would either fail or block until polkit
+//! times out, so such sessions are detected before prompting.
+
+use std::{env, fs::OpenOptions};
+
+use zbus::blocking::Connection;
+
+use super::logind;
+use crate::linux::Subject;
+
+/// The state of a login session.
+#[derive(Debug)]
+pub(super) struct Session {
+    /// Whether the session is in the foreground of its seat.
+    pub(super) active: bool,
+    /// Whether the user is logged in from another machine, e.g., over SSH.
+    pub(super) remote: bool,
+    /// Whether the screen of the session is locked.
+    pub(super) locked: bool,
+    /// Whether the session has a graphical display, and thus usually an
+    /// authentication agent.
+    pub(super) graphical: bool,
+}
+
+impl Session {
+    /// Returns the state of the login session of the subject, or of the
+    /// current process.
+    ///
+    /// Returns `None` outside of a login session, e.g., in cron jobs and
+    /// system services, or without logind.
+    pub(super) fn of(connection: &Connection, subject: Option<&Subject>) -> Option<Self> {
+        let session = logind::session(connection, subject).ok()?;
+        let property =
+            |get: fn(&logind::SessionProxy<'static>) -> zbus::Result<bool>| get(&session).ok();
+
+        // The environment only describes the session of the current process.
+        let kind = env::var("XDG_SESSION_TYPE")
+            .ok()
+            .filter(|_| subject.is_none())
+            .or_else(|| session.kind().ok())
+            .filter(|kind| !kind.is_empty() && kind != "unspecified");
+        let graphical = match kind.as_deref() {
+            Some(kind) => matches!(kind, "x11" | "wayland" | "mir"),
+            // Without a known type, e.g., in containers, a display is the
+            // best indication of a graphical session.
+            None => {
+                subject.is_none()
+                    && (env::var_os("WAYLAND_DISPLAY").is_some()
+                        || env::var_os("DISPLAY").is_some())
+            }
+        };
+
+        Some(Self {
+            active: property(logind::SessionProxy::active).unwrap_or(false),
+            remote: property(logind::SessionProxy::remote).unwrap_or(true),
+            locked: property(logind::SessionProxy::locked_hint).unwrap_or(false),
+            graphical,
+        })
+    }
+}
+
+/// Returns whether the current process has a controlling terminal.
+pub(super) fn has_terminal() -> bool {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use zbus::{fdo, zvariant::OwnedObjectPath};
+
+    use super::super::Context;
+    use crate::{
+        linux::{Conversation, Subject},
+        Error, Result,
+    };
+
+    const SESSION_PATH: &str = "/org/freedesktop/login1/session/c1";
+    const ACTION_ID: &str = "rs.robius.authentication.authenticate";
+
+    /// Finds the session, if any.
+    struct MockManager {
+        session: bool,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.login1.Manager")]
+    impl MockManager {
+        fn get_session(&self, _session_id: &str) -> fdo::Result<OwnedObjectPath> {
+            self.get_session_by_pid(0)
+        }
+
+        #[zbus(name = "GetSessionByPID")]
+        fn get_session_by_pid(&self, _pid: u32) -> fdo::Result<OwnedObjectPath> {
+            match self.session {
+                true => Ok(OwnedObjectPath::try_from(SESSION_PATH).unwrap()),
+                false => Err(fdo::Error::Failed("No session".to_owned())),
+            }
+        }
+    }
+
+    #[derive(Clone, Copy)]
+    struct MockSession {
+        active: bool,
+        remote: bool,
+        locked: bool,
+    }
+
+    const ACTIVE: MockSession = MockSession {
+        active: true,
+        remote: false,
+        locked: false,
+    };
+
+    #[zbus::interface(name = "org.freedesktop.login1.Session")]
+    impl MockSession {
+        #[zbus(property, name = "Type")]
+        fn kind(&self) -> &str {
+            "tty"
+        }
+
+        #[zbus(property)]
+        fn active(&self) -> bool {
+            self.active
+        }
+
+        #[zbus(property)]
+        fn remote(&self) -> bool {
+            self.remote
+        }
+
+        #[zbus(property)]
+        fn locked_hint(&self) -> bool {
+            self.locked
+        }
+    }
+
+    struct Silent;
+
+    impl Conversation for Silent {
+        fn prompt(&mut self, _: &str, _: bool) -> Result<String> {
+            Err(Error::UserCanceled)
+        }
+    }
+
+    /// Returns a context using a mock logind with the given session, and the
+    /// connection serving it.
+    fn mock_context(
+        session: Option<MockSession>,
+        conversation: bool,
+    ) -> (Context, zbus::blocking::Connection) {
+        let (connection, logind) = super::super::mock_service(move |builder| {
+            let manager = MockManager {
+                session: session.is_some(),
+            };
+            let builder = builder.serve_at("/org/freedesktop/login1", manager)?;
+            match session {
+                Some(session) => builder.serve_at(SESSION_PATH, session),
+                None => Ok(builder),
+            }
+        });
+        let mut context = Context::new(());
+        context.bus = connection.into();
+        if conversation {
+            context.set_conversation(Box::new(Silent));
+        }
+        (context, logind)
+    }
+
+    #[test]
+    fn no_session() {
+        // E.g., in a cron job.
+        let (context, _logind) = mock_context(None, false);
+        let result = context.check_interactive(true, true, Some(ACTION_ID), None);
+        assert!(matches!(result, Err(Error::NotInteractive)));
+
+        // Only the conversation may reach the user, who may not be at the
+        // fingerprint reader.
+        let (context, _logind) = mock_context(None, true);
+        let result = context.check_interactive(true, true, None, None);
+        assert!(matches!(result, Ok(false)));
+        let result = context.check_interactive(true, false, None, None);
+        assert!(matches!(result, Err(Error::NotInteractive)));
+    }
+
+    #[test]
+    fn locked_or_inactive() {
+        for session in [
+            MockSession {
+                locked: true,
+                ..ACTIVE
+            },
+            MockSession {
+                active: false,
+                ..ACTIVE
+            },
+        ] {
+            let (context, _logind) = mock_context(Some(session), true);
+            let result = context.check_interactive(true, true, Some(ACTION_ID), None);
+            assert!(matches!(result, Err(Error::NotInteractive)));
+        }
+    }
+
+    #[test]
+    fn biometrics() {
+        let (context, _logind) = mock_context(Some(ACTIVE), true);
+        let result = context.check_interactive(true, true, Some(ACTION_ID), None);
+        assert!(matches!(result, Ok(true)));
+
+        let remote = MockSession {
+            remote: true,
+            ..ACTIVE
+        };
+        let (context, _logind) = mock_context(Some(remote), true);
+        let result = context.check_interactive(true, true, Some(ACTION_ID), None);
+        assert!(matches!(result, Ok(false)));
+        let result = context.check_interactive(true, false, None, None);
+        assert!(matches!(result, Err(Error::NotInteractive)));
+    }
+
+    #[test]
+    fn subject() {
+        let subject = Subject::session("c1");
+        let locked = MockSession {
+            locked: true,
+            ..ACTIVE
+        };
+        let (context, _logind) = mock_context(Some(locked), true);
+        let result = context.check_interactive(true, true, Some(ACTION_ID), Some(&subject));
+        assert!(matches!(result, Err(Error::NotInteractive)));
+
+        // The fingerprint reader verifies the user of the current process.
+        let (context, _logind) = mock_context(Some(ACTIVE), false);
+        let result = context.check_interactive(true, true, Some(ACTION_ID), Some(&subject));
+        assert!(matches!(result, Ok(false)));
+
+        // The conversation doesn't reach the subject.
+        let (context, _logind) = mock_context(None, true);
+        let result = context.check_interactive(true, true, Some(ACTION_ID), Some(&subject));
+        assert!(matches!(result, Err(Error::NotInteractive)));
+    }
+}
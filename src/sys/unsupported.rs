@@ -1,4 +1,4 @@
-use crate::{BiometricStrength, Error, Result, Text};
+use crate::{BiometricStrength, Error, Identity, LinuxBackend, Outcome, PolicyError, Result, Text};
 
 pub(crate) type RawContext = ();
 
@@ -15,13 +15,21 @@ impl Context {
     // #[cfg(feature = "async")]
     // pub(crate) async fn authenticate_async(
     //     &self,
-    //     _: Text<'_, '_, '_, '_, '_, '_>,
+    //     _: Text<'_>,
     //     _: &Policy,
     // ) -> Result<()> {
     //     Err(Error::Unknown)
     // }
 
-    pub(crate) fn authenticate(&self, _: Text, _: &Policy) -> Result<()> {
+    pub(crate) fn revoke_cached_authorization(&self) -> Result<()> {
+        // Authorization is never retained.
+        Ok(())
+    }
+
+    pub(crate) fn authenticate<F>(&self, _: Text, _: &crate::Policy, _: F) -> Result<()>
+    where
+        F: Fn(Result<Outcome>) + Send + 'static,
+    {
         Err(Error::Unknown)
     }
 }
@@ -53,7 +61,25 @@ impl PolicyBuilder {
         Self
     }
 
-    pub(crate) const fn build(self) -> Option<Policy> {
-        None
+    pub(crate) const fn linux_backend(self, _: &LinuxBackend) -> Self {
+        self
+    }
+
+    pub(crate) const fn identity(self, _: &Identity) -> Self {
+        self
+    }
+
+    pub(crate) const fn try_build(self) -> std::result::Result<Policy, PolicyError> {
+        Err(PolicyError::UnsupportedMethods)
     }
 }
+
+#[cfg(feature = "audit-chain")]
+pub(crate) fn read_secret(_: &str, _: &str) -> Result<Option<Vec<u8>>> {
+    Err(Error::Unavailable)
+}
+
+#[cfg(feature = "audit-chain")]
+pub(crate) fn write_secret(_: &str, _: &str, _: &[u8]) -> Result<()> {
+    Err(Error::Unavailable)
+}
@@ -0,0 +1,77 @@
+//! Storing secrets in the Windows Credential Manager.
+
+use std::{ptr, slice};
+
+use windows::{
+    core::{PCWSTR, PWSTR},
+    Win32::{
+        Foundation::ERROR_NOT_FOUND,
+        Security::Credentials::{
+            CredFree, CredReadW, CredWriteW, CREDENTIALW, CRED_PERSIST_LOCAL_MACHINE,
+            CRED_TYPE_GENERIC,
+        },
+    },
+};
+
+use crate::{Error, Result};
+
+/// Returns the nul-terminated name of the generic credential of the service
+/// and account.
+fn target_name(service: &str, account: &str) -> Vec<u16> {
+    format!("{account}.{service}")
+        .encode_utf16()
+        .chain([0])
+        .collect()
+}
+
+/// Returns the secret stored for the service and account, if any.
+pub(crate) fn read(service: &str, account: &str) -> Result<Option<Vec<u8>>> {
+    let target_name = target_name(service, account);
+    let mut credential = ptr::null_mut();
+    let result = unsafe {
+        CredReadW(
+            PCWSTR(target_name.as_ptr()),
+            CRED_TYPE_GENERIC,
+            0,
+            &mut credential,
+        )
+    };
+    if let Err(e) = result {
+        if e.code() == ERROR_NOT_FOUND.to_hresult() {
+            return Ok(None);
+        }
+        return Err(Error::Unavailable);
+    }
+
+    // SAFETY: The credential was allocated by `CredReadW` and is freed after
+    // copying its blob.
+    let secret = unsafe {
+        let blob = slice::from_raw_parts(
+            (*credential).CredentialBlob,
+            (*credential).CredentialBlobSize as usize,
+        );
+        let secret = blob.to_vec();
+        CredFree(credential.cast());
+        secret
+    };
+    Ok(Some(secret))
+}
+
+/// Stores the secret for the service and account, replacing any secret
+/// stored for them.
+pub(crate) fn write(service: &str, account: &str, secret: &[u8]) -> Result<()> {
+    let mut target_name = target_name(service, account);
+    let mut user_name: Vec<u16> = account.encode_utf16().chain([0]).collect();
+    let mut blob = secret.to_vec();
+
+    let credential = CREDENTIALW {
+        Type: CRED_TYPE_GENERIC,
+        TargetName: PWSTR(target_name.as_mut_ptr()),
+        UserName: PWSTR(user_name.as_mut_ptr()),
+        CredentialBlobSize: blob.len() as u32,
+        CredentialBlob: blob.as_mut_ptr(),
+        Persist: CRED_PERSIST_LOCAL_MACHINE,
+        ..Default::default()
+    };
+    unsafe { CredWriteW(&credential, 0) }.map_err(|_| Error::Unavailable)
+}
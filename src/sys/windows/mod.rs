@@ -1,6 +1,8 @@
 // For the `uwp` feature gates.
 #![allow(unexpected_cfgs)]
 
+#[cfg(feature = "audit-chain")]
+mod credentials;
 mod fallback;
 
 use windows::{
@@ -11,7 +13,13 @@ use windows::{
     },
 };
 
-use crate::{text::WindowsText, BiometricStrength, Error, Result, Text};
+use crate::{
+    text::WindowsText, AuthenticationMethod, BiometricStrength, Error, Identity, LinuxBackend,
+    Outcome, PolicyError, Result, Text,
+};
+
+#[cfg(feature = "audit-chain")]
+pub(crate) use credentials::{read as read_secret, write as write_secret};
 
 pub(crate) type RawContext = ();
 
@@ -28,7 +36,7 @@ impl Context {
     // #[cfg(feature = "async")]
     // pub(crate) async fn authenticate_async(
     //     &self,
-    //     message: Text<'_, '_, '_, '_, '_, '_>,
+    //     message: Text<'_>,
     //     _: &Policy,
     // ) -> Result<()> {
     //     // NOTE: If we don't check availability, `request_verification` will hang.
@@ -42,26 +50,35 @@ impl Context {
     //     }
     // }
 
+    pub(crate) fn revoke_cached_authorization(&self) -> Result<()> {
+        // Authorization is never retained.
+        Ok(())
+    }
+
     pub(crate) fn authenticate<F>(
         &self,
         message: Text,
-        _: &Policy,
+        _: &crate::Policy,
         callback: F,
     ) -> Result<()>
     where
-        F: Fn(Result<()>) + Send + 'static,
+        F: Fn(Result<Outcome>) + Send + 'static,
     {
         // NOTE: If we don't check availability, `request_verification` will hang.
         let available =
             check_availability()?.get() == Ok(UserConsentVerifierAvailability::Available);
 
-        let result = if available {
+        // Windows Hello doesn't report which method the user chose.
+        let (result, method) = if available {
             let verification = request_verification(message.windows)?;
-            convert(verification.get()?)
+            (convert(verification.get()?), None)
         } else {
-            fallback::authenticate(message.windows)
+            (
+                fallback::authenticate(message.windows),
+                Some(AuthenticationMethod::Password),
+            )
         };
-        callback(result);
+        callback(result.map(|()| Outcome { user: None, method }));
         Ok(())
     }
 }
@@ -72,16 +89,23 @@ pub(crate) struct Policy;
 #[derive(Debug)]
 pub(crate) struct PolicyBuilder {
     valid: bool,
+    current_user: bool,
 }
 
 impl PolicyBuilder {
     pub(crate) const fn new() -> Self {
-        Self { valid: true }
+        Self {
+            valid: true,
+            current_user: true,
+        }
     }
 
     pub(crate) const fn biometrics(self, biometrics: Option<BiometricStrength>) -> Self {
         if biometrics.is_none() {
-            Self { valid: false }
+            Self {
+                valid: false,
+                ..self
+            }
         } else {
             self
         }
@@ -91,7 +115,10 @@ impl PolicyBuilder {
         if password {
             self
         } else {
-            Self { valid: false }
+            Self {
+                valid: false,
+                ..self
+            }
         }
     }
 
@@ -103,11 +130,24 @@ impl PolicyBuilder {
         self
     }
 
-    pub(crate) const fn build(self) -> Option<Policy> {
-        if self.valid {
-            Some(Policy)
+    pub(crate) const fn linux_backend(self, _: &LinuxBackend) -> Self {
+        self
+    }
+
+    pub(crate) const fn identity(self, identity: &Identity) -> Self {
+        Self {
+            current_user: matches!(identity, Identity::CurrentUser),
+            ..self
+        }
+    }
+
+    pub(crate) const fn try_build(self) -> std::result::Result<Policy, PolicyError> {
+        if !self.current_user {
+            Err(PolicyError::UnsupportedIdentity)
+        } else if self.valid {
+            Ok(Policy)
         } else {
-            None
+            Err(PolicyError::UnsupportedMethods)
         }
     }
 }
@@ -120,7 +160,7 @@ fn check_availability() -> Result<IAsyncOperation<UserConsentVerifierAvailabilit
 fn request_verification(
     text: WindowsText,
 ) -> Result<IAsyncOperation<UserConsentVerificationResult>> {
-    let caption = caption(text.description);
+    let caption = caption(&text.description);
 
     UserConsentVerifier::RequestVerificationAsync(&HSTRING::from_wide(&caption[..])?)
         .map_err(|e| e.into())
@@ -198,7 +238,7 @@ fn request_verification(
     }
 
     let window = unsafe { GetDesktopWindow() };
-    let caption = caption(text.description);
+    let caption = caption(&text.description);
 
     let factory = factory::<UserConsentVerifier, IUserConsentVerifierInterop>()?;
 
@@ -1,46 +1,183 @@
+mod macros;
+mod template;
+mod truncate;
+mod validate;
+
+use std::borrow::Cow;
+
+pub use template::{Param, TemplateError};
+pub use validate::{Platform, TextField, TextIssue, TextIssueKind, ValidationError};
+#[doc(hidden)]
+pub use macros::ConstText;
+
 /// The text contents displayed by an authentication prompt.
-pub struct Text<'a, 'b, 'c, 'd, 'e, 'f> {
+///
+/// See [`TextBuf`] for an owned version that can be built from run-time data.
+#[derive(Clone, Debug)]
+pub struct Text<'a> {
     /// The text of the authentication prompt on Android.
-    pub android: AndroidText<'a, 'b, 'c>,
+    pub android: AndroidText<'a>,
     /// The description of the authentication prompt on Apple devices.
     ///
     /// Appears as "$(binary_name) is trying to $(description)".
-    pub apple: &'d str,
+    pub apple: &'a str,
+    /// The text of the authentication prompt on Linux.
+    pub linux: LinuxText<'a>,
     /// The description of the authentication prompt on Windows.
-    pub windows: WindowsText<'e, 'f>,
+    pub windows: WindowsText<'a>,
+}
+
+impl Text<'_> {
+    /// Derives the text for every platform from a single reason.
+    ///
+    /// This is a shorthand for `Text::builder().reason(reason).build()`,
+    /// see [`TextBuilder`] for how the platform strings are derived.
+    ///
+    /// Returns an error if the derived text is not valid on every platform,
+    /// e.g., if `reason` is empty, see [`TextBuilder::build`].
+    #[inline]
+    pub fn from_reason(reason: impl Into<String>) -> Result<TextBuf, ValidationError> {
+        Self::builder().reason(reason).build()
+    }
+
+    /// Returns a builder that derives the text for every platform from a
+    /// single reason, see [`TextBuilder`].
+    #[inline]
+    pub const fn builder() -> TextBuilder {
+        TextBuilder::new()
+    }
+
+    /// Checks the text against the documented constraints of every platform,
+    /// regardless of the current target.
+    ///
+    /// This allows invalid prompt strings to be caught, e.g., in CI, before
+    /// they fail at run-time on a device. Returns all problems found.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use robius_authentication::{
+    ///     AndroidText, LinuxText, Text, TextField, TextIssueKind, WindowsText,
+    /// };
+    ///
+    /// let text = Text {
+    ///     android: AndroidText {
+    ///         title: "Title",
+    ///         subtitle: None,
+    ///         description: None,
+    ///     },
+    ///     apple: "",
+    ///     linux: LinuxText::new("Description"),
+    ///     windows: WindowsText::new_truncated("Title", "Description"),
+    /// };
+    ///
+    /// let issues = text.validate().unwrap_err();
+    /// assert_eq!(issues.len(), 1);
+    /// assert_eq!(issues[0].field, TextField::Apple);
+    /// assert_eq!(issues[0].kind, TextIssueKind::Empty);
+    /// ```
+    pub fn validate(&self) -> Result<(), Vec<TextIssue>> {
+        validate::validate(self)
+    }
 }
 
 /// The text of the authentication prompt on Android.
-pub struct AndroidText<'a, 'b, 'c> {
+#[derive(Clone, Debug)]
+pub struct AndroidText<'a> {
     pub title: &'a str,
-    pub subtitle: Option<&'b str>,
-    pub description: Option<&'c str>,
+    pub subtitle: Option<&'a str>,
+    pub description: Option<&'a str>,
+}
+
+/// The text of the authentication prompt on Linux.
+///
+/// With the [`polkit`] backend, these are passed to the authentication agent as
+/// the `polkit.message`, `polkit.icon_name` and `polkit.gettext_domain`
+/// details, alongside any additional `details`.
+///
+/// polkit only accepts details from trusted callers, i.e., processes running
+/// as root or as a user owning the action, listed in the
+/// `org.freedesktop.policykit.owner` annotation of the action, see
+/// [`LinuxBackend::Polkit`](crate::LinuxBackend::Polkit). Otherwise no
+/// details are passed, so the agent displays the message and icon declared in
+/// the policy file instead of this text.
+///
+/// [`polkit`]: https://www.freedesktop.org/software/polkit/docs/latest/polkit.8.html
+#[derive(Clone, Debug)]
+pub struct LinuxText<'a> {
+    /// The message displayed by the authentication prompt.
+    pub message: &'a str,
+    /// The name of the icon displayed by the authentication prompt, following
+    /// the freedesktop.org icon naming specification.
+    ///
+    /// Defaults to the icon of the application's desktop entry, if any.
+    pub icon_name: Option<&'a str>,
+    /// The gettext domain used to translate the `message`.
+    pub gettext_domain: Option<&'a str>,
+    /// Additional key-value pairs that the authentication agent may display.
+    pub details: &'a [LinuxDetail<'a>],
+}
+
+impl<'a> LinuxText<'a> {
+    /// Creates a new `LinuxText` instance with the given message and no icon,
+    /// gettext domain or details.
+    #[inline]
+    pub const fn new(message: &'a str) -> Self {
+        Self {
+            message,
+            icon_name: None,
+            gettext_domain: None,
+            details: &[],
+        }
+    }
+}
+
+/// A key-value pair that the authentication agent may display on Linux.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LinuxDetail<'a> {
+    pub key: Cow<'a, str>,
+    pub value: Cow<'a, str>,
+}
+
+impl<'a> LinuxDetail<'a> {
+    /// Creates a new `LinuxDetail` instance.
+    #[inline]
+    pub const fn new(key: &'a str, value: &'a str) -> Self {
+        Self {
+            key: Cow::Borrowed(key),
+            value: Cow::Borrowed(value),
+        }
+    }
 }
 
 /// The text of the authentication prompt on Windows,
 /// including a title ("caption") and description ("message").
-pub struct WindowsText<'a, 'b> {
-    #[allow(dead_code)]
-    pub(crate) title: &'a str,
-    #[allow(dead_code)]
-    pub(crate) description: &'b str,
+#[derive(Clone, Debug)]
+pub struct WindowsText<'a> {
+    pub(crate) title: Cow<'a, str>,
+    pub(crate) description: Cow<'a, str>,
 }
 
-impl<'a, 'b> WindowsText<'a, 'b> {
+impl<'a> WindowsText<'a> {
+    /// The maximum length of the title ("caption") in UTF-16 code units.
+    pub const MAX_TITLE_LEN: usize = 128;
+    /// The maximum length of the description ("message") in UTF-16 code units.
+    pub const MAX_DESCRIPTION_LEN: usize = 1024;
+
     /// Creates a new `WindowsText` instance.
     ///
-    /// Returns `None` if `title` exceeds 128 bytes in length
-    /// or if `description` exceeds 1024 bytes in length.
-    #[cfg(target_os = "windows")]
-    pub const fn new(title: &'a str, description: &'b str) -> Option<Self> {
-        use windows::Win32::Security::Credentials::{
-            CREDUI_MAX_CAPTION_LENGTH, CREDUI_MAX_MESSAGE_LENGTH,
-        };
-
-        if title.len() <= CREDUI_MAX_CAPTION_LENGTH as usize
-            && description.len() <= CREDUI_MAX_MESSAGE_LENGTH as usize
+    /// Returns `None` if `title` exceeds 128 UTF-16 code units in length
+    /// or if `description` exceeds 1024 UTF-16 code units in length.
+    ///
+    /// The limits are checked on every target, not just on Windows.
+    pub const fn new(title: &'a str, description: &'a str) -> Option<Self> {
+        if truncate::utf16_len(title) <= Self::MAX_TITLE_LEN
+            && truncate::utf16_len(description) <= Self::MAX_DESCRIPTION_LEN
         {
-            Some(Self { title, description })
+            Some(Self {
+                title: Cow::Borrowed(title),
+                description: Cow::Borrowed(description),
+            })
         } else {
             None
         }
@@ -48,37 +185,535 @@ impl<'a, 'b> WindowsText<'a, 'b> {
 
     /// Creates a new `WindowsText` instance.
     ///
-    /// Returns `None` if `title` exceeds 128 bytes in length
-    /// or if `description` exceeds 1024 bytes in length.
-    #[cfg(not(target_os = "windows"))]
-    pub const fn new(title: &'a str, description: &'b str) -> Option<Self> {
-        Some(Self { title, description })
+    /// The `title` ("caption") will be truncated to 128 UTF-16 code units in
+    /// length, and the `description` ("message") will be truncated to 1024
+    /// UTF-16 code units in length. Strings are only ever cut between grapheme
+    /// clusters, and an ellipsis ("…") is appended to any truncated string.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use robius_authentication::WindowsText;
+    ///
+    /// let title = "認証".repeat(100);
+    /// let text = WindowsText::new_truncated(&title, "Description");
+    ///
+    /// assert!(text.title().encode_utf16().count() <= WindowsText::MAX_TITLE_LEN);
+    /// assert!(text.title().ends_with('…'));
+    /// assert_eq!(text.description(), "Description");
+    /// ```
+    pub fn new_truncated(title: &'a str, description: &'a str) -> Self {
+        Self {
+            title: truncate::truncate_utf16(title, Self::MAX_TITLE_LEN),
+            description: truncate::truncate_utf16(description, Self::MAX_DESCRIPTION_LEN),
+        }
     }
 
-    /// Creates a new `WindowsText` instance.
+    /// Returns the title ("caption").
+    #[inline]
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Returns the description ("message").
+    #[inline]
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+#[cfg(target_os = "windows")]
+const _: () = {
+    use windows::Win32::Security::Credentials::{
+        CREDUI_MAX_CAPTION_LENGTH, CREDUI_MAX_MESSAGE_LENGTH,
+    };
+
+    assert!(WindowsText::MAX_TITLE_LEN == CREDUI_MAX_CAPTION_LENGTH as usize);
+    assert!(WindowsText::MAX_DESCRIPTION_LEN == CREDUI_MAX_MESSAGE_LENGTH as usize);
+};
+
+/// An owned version of [`Text`].
+///
+/// A `TextBuf` owns all of its strings, so it can be built from run-time data
+/// (e.g., a file name or a username) and moved across threads or into a
+/// callback. Use [`TextBuf::as_text`] to borrow it as a [`Text`] when
+/// displaying a prompt.
+///
+/// # Example
+///
+/// ```no_run
+/// use robius_authentication::{Context, PolicyBuilder, TextBuf};
+///
+/// let file_name = "secrets.txt";
+/// let text = TextBuf::new()
+///     .android_title("Export file")
+///     .android_description(format!("Export {file_name}"))
+///     .apple(format!("export {file_name}"))
+///     .linux_message(format!("Authentication is required to export {file_name}"))
+///     .windows("Export file", format!("Export {file_name}"));
+///
+/// let policy = PolicyBuilder::new().build().unwrap();
+/// Context::new(())
+///     .authenticate(text.as_text(), &policy, |_| {})
+///     .expect("authentication failed");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TextBuf {
+    pub(crate) android_title: String,
+    pub(crate) android_subtitle: Option<String>,
+    pub(crate) android_description: Option<String>,
+    pub(crate) apple: String,
+    pub(crate) linux_message: String,
+    pub(crate) linux_icon_name: Option<String>,
+    pub(crate) linux_gettext_domain: Option<String>,
+    pub(crate) linux_details: Vec<LinuxDetail<'static>>,
+    pub(crate) windows_title: String,
+    pub(crate) windows_description: String,
+}
+
+impl TextBuf {
+    /// Returns a new `TextBuf` with all fields empty.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            android_title: String::new(),
+            android_subtitle: None,
+            android_description: None,
+            apple: String::new(),
+            linux_message: String::new(),
+            linux_icon_name: None,
+            linux_gettext_domain: None,
+            linux_details: Vec::new(),
+            windows_title: String::new(),
+            windows_description: String::new(),
+        }
+    }
+
+    /// Sets the title of the authentication prompt on Android.
+    #[inline]
+    #[must_use]
+    pub fn android_title(self, title: impl Into<String>) -> Self {
+        Self {
+            android_title: title.into(),
+            ..self
+        }
+    }
+
+    /// Sets the subtitle of the authentication prompt on Android.
+    #[inline]
+    #[must_use]
+    pub fn android_subtitle(self, subtitle: impl Into<String>) -> Self {
+        Self {
+            android_subtitle: Some(subtitle.into()),
+            ..self
+        }
+    }
+
+    /// Sets the description of the authentication prompt on Android.
+    #[inline]
+    #[must_use]
+    pub fn android_description(self, description: impl Into<String>) -> Self {
+        Self {
+            android_description: Some(description.into()),
+            ..self
+        }
+    }
+
+    /// Sets the description of the authentication prompt on Apple devices.
     ///
-    /// The `title` ("caption") will be truncated to 128 bytes in length,
-    /// and the `description` ("message") will be truncated to 1024 bytes in length.
-    #[cfg(target_os = "windows")]
-    pub fn new_truncated(title: &'a str, description: &'b str) -> Self {
-        use windows::Win32::Security::Credentials::{
-            CREDUI_MAX_CAPTION_LENGTH, CREDUI_MAX_MESSAGE_LENGTH,
-        };
+    /// Appears as "$(binary_name) is trying to $(description)".
+    #[inline]
+    #[must_use]
+    pub fn apple(self, description: impl Into<String>) -> Self {
+        Self {
+            apple: description.into(),
+            ..self
+        }
+    }
 
-        let title_max_len = std::cmp::min(CREDUI_MAX_CAPTION_LENGTH as usize, title.len());
-        let description_max_len = std::cmp::min(CREDUI_MAX_MESSAGE_LENGTH as usize, description.len());
+    /// Sets the message of the authentication prompt on Linux.
+    #[inline]
+    #[must_use]
+    pub fn linux_message(self, message: impl Into<String>) -> Self {
         Self {
-            title: &title[..title_max_len],
-            description: &description[..description_max_len],
+            linux_message: message.into(),
+            ..self
         }
     }
 
-    /// Creates a new `WindowsText` instance.
+    /// Sets the name of the icon displayed by the authentication prompt on
+    /// Linux.
+    #[inline]
+    #[must_use]
+    pub fn linux_icon_name(self, icon_name: impl Into<String>) -> Self {
+        Self {
+            linux_icon_name: Some(icon_name.into()),
+            ..self
+        }
+    }
+
+    /// Sets the gettext domain used to translate the message of the
+    /// authentication prompt on Linux.
+    #[inline]
+    #[must_use]
+    pub fn linux_gettext_domain(self, gettext_domain: impl Into<String>) -> Self {
+        Self {
+            linux_gettext_domain: Some(gettext_domain.into()),
+            ..self
+        }
+    }
+
+    /// Adds a key-value pair that the authentication agent may display on
+    /// Linux.
+    #[inline]
+    #[must_use]
+    pub fn linux_detail(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.linux_details.push(LinuxDetail {
+            key: Cow::Owned(key.into()),
+            value: Cow::Owned(value.into()),
+        });
+        self
+    }
+
+    /// Sets the title ("caption") and description ("message") of the
+    /// authentication prompt on Windows.
     ///
-    /// The `title` ("caption") will be truncated to 128 bytes in length,
-    /// and the `description` ("message") will be truncated to 1024 bytes in length.
-    #[cfg(not(target_os = "windows"))]
-    pub fn new_truncated(title: &'a str, description: &'b str) -> Self {
-        Self { title, description }
+    /// Strings exceeding the Windows limits are truncated when the text is
+    /// borrowed using [`Self::as_text`], see [`WindowsText::new_truncated`].
+    #[inline]
+    #[must_use]
+    pub fn windows(self, title: impl Into<String>, description: impl Into<String>) -> Self {
+        Self {
+            windows_title: title.into(),
+            windows_description: description.into(),
+            ..self
+        }
+    }
+
+    /// Borrows this `TextBuf` as a [`Text`].
+    pub fn as_text(&self) -> Text<'_> {
+        Text {
+            android: AndroidText {
+                title: &self.android_title,
+                subtitle: self.android_subtitle.as_deref(),
+                description: self.android_description.as_deref(),
+            },
+            apple: &self.apple,
+            linux: LinuxText {
+                message: &self.linux_message,
+                icon_name: self.linux_icon_name.as_deref(),
+                gettext_domain: self.linux_gettext_domain.as_deref(),
+                details: &self.linux_details,
+            },
+            windows: WindowsText::new_truncated(&self.windows_title, &self.windows_description),
+        }
+    }
+}
+
+impl From<Text<'_>> for TextBuf {
+    fn from(text: Text<'_>) -> Self {
+        Self {
+            android_title: text.android.title.to_owned(),
+            android_subtitle: text.android.subtitle.map(str::to_owned),
+            android_description: text.android.description.map(str::to_owned),
+            apple: text.apple.to_owned(),
+            linux_message: text.linux.message.to_owned(),
+            linux_icon_name: text.linux.icon_name.map(str::to_owned),
+            linux_gettext_domain: text.linux.gettext_domain.map(str::to_owned),
+            linux_details: text
+                .linux
+                .details
+                .iter()
+                .map(|detail| LinuxDetail {
+                    key: Cow::Owned(detail.key.clone().into_owned()),
+                    value: Cow::Owned(detail.value.clone().into_owned()),
+                })
+                .collect(),
+            windows_title: text.windows.title.into_owned(),
+            windows_description: text.windows.description.into_owned(),
+        }
+    }
+}
+
+/// The title used by [`TextBuilder`] if neither a title nor an app name is set.
+pub const DEFAULT_TITLE: &str = "Authentication required";
+
+/// A builder that derives the text for every platform from a single reason.
+///
+/// The reason should complete the sentence "$(app_name) is trying to ...",
+/// e.g., "unlock your vault", as that is how it is displayed on Apple devices.
+/// Given the reason, an optional title and an optional app name, the platform
+/// strings are derived as follows:
+/// - The Android and Windows titles are the title, falling back to the app name
+///   and then to "Authentication required".
+/// - The Android and Windows descriptions and the Linux message are
+///   "$(app_name) is trying to $(reason)." if an app name is set, or
+///   "Authenticate to $(reason)." otherwise.
+/// - The Apple description is the reason itself.
+///
+/// Each derived string can be overridden individually.
+///
+/// The builder also serves as a template for text containing run-time values,
+/// see [`TextBuilder::render`].
+///
+/// # Example
+///
+/// ```
+/// use robius_authentication::Text;
+///
+/// let text = Text::builder()
+///     .reason("unlock your vault")
+///     .app_name("Vault")
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(text.as_text().apple, "unlock your vault");
+/// assert_eq!(text.as_text().android.title, "Vault");
+/// assert_eq!(
+///     text.as_text().android.description,
+///     Some("Vault is trying to unlock your vault.")
+/// );
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct TextBuilder {
+    reason: String,
+    title: Option<String>,
+    app_name: Option<String>,
+    android_title: Option<String>,
+    android_subtitle: Option<String>,
+    android_description: Option<String>,
+    apple: Option<String>,
+    linux_message: Option<String>,
+    linux_icon_name: Option<String>,
+    linux_gettext_domain: Option<String>,
+    linux_details: Vec<LinuxDetail<'static>>,
+    windows_title: Option<String>,
+    windows_description: Option<String>,
+}
+
+impl TextBuilder {
+    /// Returns a new builder with no reason set.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            reason: String::new(),
+            title: None,
+            app_name: None,
+            android_title: None,
+            android_subtitle: None,
+            android_description: None,
+            apple: None,
+            linux_message: None,
+            linux_icon_name: None,
+            linux_gettext_domain: None,
+            linux_details: Vec::new(),
+            windows_title: None,
+            windows_description: None,
+        }
+    }
+
+    /// Sets the reason for the authentication, e.g., "unlock your vault".
+    #[inline]
+    #[must_use]
+    pub fn reason(self, reason: impl Into<String>) -> Self {
+        Self {
+            reason: reason.into(),
+            ..self
+        }
+    }
+
+    /// Sets the title of the authentication prompt.
+    #[inline]
+    #[must_use]
+    pub fn title(self, title: impl Into<String>) -> Self {
+        Self {
+            title: Some(title.into()),
+            ..self
+        }
+    }
+
+    /// Sets the name of the app requesting authentication.
+    #[inline]
+    #[must_use]
+    pub fn app_name(self, app_name: impl Into<String>) -> Self {
+        Self {
+            app_name: Some(app_name.into()),
+            ..self
+        }
+    }
+
+    /// Overrides the title of the authentication prompt on Android.
+    #[inline]
+    #[must_use]
+    pub fn android_title(self, title: impl Into<String>) -> Self {
+        Self {
+            android_title: Some(title.into()),
+            ..self
+        }
+    }
+
+    /// Sets the subtitle of the authentication prompt on Android.
+    ///
+    /// No subtitle is derived by default.
+    #[inline]
+    #[must_use]
+    pub fn android_subtitle(self, subtitle: impl Into<String>) -> Self {
+        Self {
+            android_subtitle: Some(subtitle.into()),
+            ..self
+        }
+    }
+
+    /// Overrides the description of the authentication prompt on Android.
+    #[inline]
+    #[must_use]
+    pub fn android_description(self, description: impl Into<String>) -> Self {
+        Self {
+            android_description: Some(description.into()),
+            ..self
+        }
+    }
+
+    /// Overrides the description of the authentication prompt on Apple
+    /// devices.
+    #[inline]
+    #[must_use]
+    pub fn apple(self, description: impl Into<String>) -> Self {
+        Self {
+            apple: Some(description.into()),
+            ..self
+        }
+    }
+
+    /// Overrides the message of the authentication prompt on Linux.
+    #[inline]
+    #[must_use]
+    pub fn linux_message(self, message: impl Into<String>) -> Self {
+        Self {
+            linux_message: Some(message.into()),
+            ..self
+        }
+    }
+
+    /// Sets the name of the icon displayed by the authentication prompt on
+    /// Linux.
+    ///
+    /// No icon is set by default.
+    #[inline]
+    #[must_use]
+    pub fn linux_icon_name(self, icon_name: impl Into<String>) -> Self {
+        Self {
+            linux_icon_name: Some(icon_name.into()),
+            ..self
+        }
+    }
+
+    /// Sets the gettext domain used to translate the message of the
+    /// authentication prompt on Linux.
+    ///
+    /// No gettext domain is set by default.
+    #[inline]
+    #[must_use]
+    pub fn linux_gettext_domain(self, gettext_domain: impl Into<String>) -> Self {
+        Self {
+            linux_gettext_domain: Some(gettext_domain.into()),
+            ..self
+        }
+    }
+
+    /// Adds a key-value pair that the authentication agent may display on
+    /// Linux.
+    #[inline]
+    #[must_use]
+    pub fn linux_detail(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.linux_details.push(LinuxDetail {
+            key: Cow::Owned(key.into()),
+            value: Cow::Owned(value.into()),
+        });
+        self
+    }
+
+    /// Overrides the title ("caption") of the authentication prompt on
+    /// Windows.
+    #[inline]
+    #[must_use]
+    pub fn windows_title(self, title: impl Into<String>) -> Self {
+        Self {
+            windows_title: Some(title.into()),
+            ..self
+        }
+    }
+
+    /// Overrides the description ("message") of the authentication prompt on
+    /// Windows.
+    #[inline]
+    #[must_use]
+    pub fn windows_description(self, description: impl Into<String>) -> Self {
+        Self {
+            windows_description: Some(description.into()),
+            ..self
+        }
+    }
+
+    /// Derives the text for every platform and checks it using
+    /// [`Text::validate`].
+    ///
+    /// The Windows strings are truncated to their maximum lengths when the
+    /// text is displayed, see [`WindowsText::new_truncated`].
+    ///
+    /// Returns an error if the text is not valid on every platform, e.g., if
+    /// the reason is empty or contains control characters.
+    #[inline]
+    pub fn build(self) -> Result<TextBuf, ValidationError> {
+        self.build_valid().map_err(ValidationError::new)
+    }
+
+    /// Derives the text for every platform, like [`Self::build`], returning
+    /// the issues found.
+    ///
+    /// If the reason is empty, the strings derived from it are empty, so that
+    /// the issues are reported for the fields that are actually displayed.
+    pub(crate) fn build_valid(self) -> Result<TextBuf, Vec<TextIssue>> {
+        let text = self.derive();
+        text.as_text().validate()?;
+        Ok(text)
+    }
+
+    /// Returns the reason without surrounding whitespace and trailing periods.
+    fn trimmed_reason(&self) -> &str {
+        // The reason is embedded in the middle of a sentence, so trailing
+        // punctuation would be duplicated.
+        self.reason.trim().trim_end_matches('.').trim_end()
+    }
+
+    fn derive(self) -> TextBuf {
+        let reason = self.trimmed_reason().to_owned();
+        let title = self
+            .title
+            .or_else(|| self.app_name.clone())
+            .unwrap_or_else(|| DEFAULT_TITLE.to_owned());
+        let description = match (&self.app_name, reason.as_str()) {
+            (_, "") => String::new(),
+            (Some(app_name), reason) => format!("{app_name} is trying to {reason}."),
+            (None, reason) => format!("Authenticate to {reason}."),
+        };
+
+        TextBuf {
+            android_title: self.android_title.unwrap_or_else(|| title.clone()),
+            android_subtitle: self.android_subtitle,
+            android_description: Some(
+                self.android_description
+                    .unwrap_or_else(|| description.clone()),
+            ),
+            apple: self.apple.unwrap_or(reason),
+            linux_message: self
+                .linux_message
+                .unwrap_or_else(|| description.clone()),
+            linux_icon_name: self.linux_icon_name,
+            linux_gettext_domain: self.linux_gettext_domain,
+            linux_details: self.linux_details,
+            windows_title: self.windows_title.unwrap_or(title),
+            windows_description: self.windows_description.unwrap_or(description),
+        }
     }
 }
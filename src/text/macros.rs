@@ -0,0 +1,251 @@
+//! The [`text!`](crate::text!) macro and its compile-time support code.
+
+use std::borrow::Cow;
+
+use super::{validate, AndroidText, LinuxDetail, LinuxText, Text, TextField, WindowsText};
+
+/// Constructs a [`Text`] at compile time, checking it against the constraints
+/// of every platform regardless of the current target.
+///
+/// The platform strings are derived from a title, an app name and a reason in
+/// the same way as [`TextBuilder`](crate::TextBuilder) derives them, except
+/// that the reason can't be trimmed at compile time, so it must not end with a
+/// `.` or start or end with whitespace. The
+/// `title` and `app_name` are optional, but if present they must come before
+/// the `reason`, and all three must be string literals. Any of the derived
+/// strings can then be overridden using the names of the `TextBuilder`
+/// methods, i.e., `android_title`, `android_subtitle`, `android_description`,
+/// `apple`, `linux_message`, `linux_icon_name`, `linux_gettext_domain`,
+/// `windows_title` and `windows_description`. Linux details can be set using
+/// `linux_details`, which takes a slice of [`LinuxDetail`](crate::LinuxDetail)s.
+///
+/// If the text is invalid on any platform, compilation fails with an error
+/// naming the offending platform and field, see [`Text::validate`].
+///
+/// # Example
+///
+/// ```
+/// use robius_authentication::{text, Text};
+///
+/// const TEXT: Text = text! {
+///     title: "Unlock vault",
+///     reason: "unlock your vault",
+///     android_subtitle: "Vault",
+/// };
+///
+/// assert_eq!(TEXT.apple, "unlock your vault");
+/// assert_eq!(TEXT.windows.description(), "Authenticate to unlock your vault.");
+/// ```
+///
+/// Invalid text fails to compile:
+///
+/// ```compile_fail
+/// use robius_authentication::{text, Text};
+///
+/// // error: Apple description must not be empty
+/// const TEXT: Text = text! {
+///     title: "Unlock vault",
+///     reason: "",
+/// };
+/// ```
+///
+/// ```compile_fail
+/// use robius_authentication::{text, Text};
+///
+/// // error: the reason must not end with a `.`
+/// const TEXT: Text = text! {
+///     reason: "unlock your vault.",
+/// };
+/// ```
+#[macro_export]
+macro_rules! text {
+    (
+        $(title: $title:literal,)?
+        $(app_name: $app_name:literal,)?
+        reason: $reason:literal
+        $(, $field:ident: $value:expr)*
+        $(,)?
+    ) => {{
+        const TEXT: $crate::Text<'static> =
+            $crate::__private::ConstText::new(
+                $crate::__text_title!($($title)?; $($app_name)?),
+                $crate::__text_description!($reason; $($app_name)?),
+                $reason,
+            )
+            $(.$field($value))*
+            .build();
+        TEXT
+    }};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __text_title {
+    ($title:literal; $($app_name:literal)?) => {
+        $title
+    };
+    (; $app_name:literal) => {
+        $app_name
+    };
+    (;) => {
+        $crate::__private::DEFAULT_TITLE
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __text_description {
+    ($reason:literal; $app_name:literal) => {
+        concat!($app_name, " is trying to ", $reason, ".")
+    };
+    ($reason:literal;) => {
+        concat!("Authenticate to ", $reason, ".")
+    };
+}
+
+/// A `const` builder for [`Text`] used by the [`text!`](crate::text!) macro.
+#[doc(hidden)]
+#[derive(Clone, Copy, Debug)]
+pub struct ConstText {
+    android_title: &'static str,
+    android_subtitle: Option<&'static str>,
+    android_description: Option<&'static str>,
+    apple: &'static str,
+    linux_message: &'static str,
+    linux_icon_name: Option<&'static str>,
+    linux_gettext_domain: Option<&'static str>,
+    linux_details: &'static [LinuxDetail<'static>],
+    windows_title: &'static str,
+    windows_description: &'static str,
+}
+
+impl ConstText {
+    pub const fn new(title: &'static str, description: &'static str, reason: &'static str) -> Self {
+        // `TextBuilder::build` trims these, but the description is already
+        // concatenated, so they would be duplicated.
+        if let [first, .., last] | [first @ last] = reason.as_bytes() {
+            if *last == b'.' {
+                panic!("the reason must not end with a `.`");
+            }
+            if first.is_ascii_whitespace() || last.is_ascii_whitespace() {
+                panic!("the reason must not start or end with whitespace");
+            }
+        }
+        Self {
+            android_title: title,
+            android_subtitle: None,
+            android_description: Some(description),
+            apple: reason,
+            linux_message: description,
+            linux_icon_name: None,
+            linux_gettext_domain: None,
+            linux_details: &[],
+            windows_title: title,
+            windows_description: description,
+        }
+    }
+
+    pub const fn android_title(self, title: &'static str) -> Self {
+        Self {
+            android_title: title,
+            ..self
+        }
+    }
+
+    pub const fn android_subtitle(self, subtitle: &'static str) -> Self {
+        Self {
+            android_subtitle: Some(subtitle),
+            ..self
+        }
+    }
+
+    pub const fn android_description(self, description: &'static str) -> Self {
+        Self {
+            android_description: Some(description),
+            ..self
+        }
+    }
+
+    pub const fn apple(self, description: &'static str) -> Self {
+        Self {
+            apple: description,
+            ..self
+        }
+    }
+
+    pub const fn linux_message(self, message: &'static str) -> Self {
+        Self {
+            linux_message: message,
+            ..self
+        }
+    }
+
+    pub const fn linux_icon_name(self, icon_name: &'static str) -> Self {
+        Self {
+            linux_icon_name: Some(icon_name),
+            ..self
+        }
+    }
+
+    pub const fn linux_gettext_domain(self, gettext_domain: &'static str) -> Self {
+        Self {
+            linux_gettext_domain: Some(gettext_domain),
+            ..self
+        }
+    }
+
+    pub const fn linux_details(self, details: &'static [LinuxDetail<'static>]) -> Self {
+        Self {
+            linux_details: details,
+            ..self
+        }
+    }
+
+    pub const fn windows_title(self, title: &'static str) -> Self {
+        Self {
+            windows_title: title,
+            ..self
+        }
+    }
+
+    pub const fn windows_description(self, description: &'static str) -> Self {
+        Self {
+            windows_description: description,
+            ..self
+        }
+    }
+
+    /// Constructs the text, panicking if it is invalid on any platform.
+    pub const fn build(self) -> Text<'static> {
+        validate::assert_valid(TextField::AndroidTitle, self.android_title);
+        if let Some(subtitle) = self.android_subtitle {
+            validate::assert_valid(TextField::AndroidSubtitle, subtitle);
+        }
+        if let Some(description) = self.android_description {
+            validate::assert_valid(TextField::AndroidDescription, description);
+        }
+        validate::assert_valid(TextField::Apple, self.apple);
+        validate::assert_valid(TextField::LinuxMessage, self.linux_message);
+        validate::assert_valid(TextField::WindowsTitle, self.windows_title);
+        validate::assert_valid(TextField::WindowsDescription, self.windows_description);
+
+        Text {
+            android: AndroidText {
+                title: self.android_title,
+                subtitle: self.android_subtitle,
+                description: self.android_description,
+            },
+            apple: self.apple,
+            linux: LinuxText {
+                message: self.linux_message,
+                icon_name: self.linux_icon_name,
+                gettext_domain: self.linux_gettext_domain,
+                details: self.linux_details,
+            },
+            windows: WindowsText {
+                title: Cow::Borrowed(self.windows_title),
+                description: Cow::Borrowed(self.windows_description),
+            },
+        }
+    }
+}
@@ -0,0 +1,386 @@
+//! Substitution of run-time values into prompt text.
+
+use std::{borrow::Cow, fmt};
+
+use super::{truncate::truncate_utf16, TextBuf, TextBuilder, TextIssue};
+
+/// The maximum length of a substituted text parameter in UTF-16 code units.
+const MAX_PARAM_LEN: usize = 64;
+
+/// A value substituted into a [`TextBuilder`] template.
+///
+/// Text values are treated as untrusted, see [`TextBuilder::render`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Param<'a> {
+    Text(Cow<'a, str>),
+    Integer(i128),
+    Float(f64),
+}
+
+impl<'a> From<&'a str> for Param<'a> {
+    fn from(value: &'a str) -> Self {
+        Self::Text(Cow::Borrowed(value))
+    }
+}
+
+impl From<String> for Param<'_> {
+    fn from(value: String) -> Self {
+        Self::Text(Cow::Owned(value))
+    }
+}
+
+impl From<f64> for Param<'_> {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+macro_rules! impl_from_integer {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for Param<'_> {
+                fn from(value: $ty) -> Self {
+                    Self::Integer(value.into())
+                }
+            }
+        )*
+    };
+}
+
+impl_from_integer!(i8, i16, i32, i64, u8, u16, u32, u64);
+
+impl Param<'_> {
+    /// Returns the text that replaces a placeholder.
+    fn render(&self) -> Cow<'_, str> {
+        match self {
+            Self::Text(text) => {
+                let sanitized = sanitize(text);
+                Cow::Owned(truncate_utf16(&sanitized, MAX_PARAM_LEN).into_owned())
+            }
+            Self::Integer(integer) => Cow::Owned(integer.to_string()),
+            Self::Float(float) => Cow::Owned(float.to_string()),
+        }
+    }
+}
+
+/// An error produced while rendering a template, see [`TextBuilder::render`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TemplateError {
+    /// A placeholder refers to a parameter that wasn't provided.
+    MissingParameter(String),
+    /// A `{` or `}` in a template is neither part of a placeholder nor escaped
+    /// by doubling it.
+    UnbalancedBrace { template: String, index: usize },
+    /// The rendered text is invalid on some platform, see [`Text::validate`].
+    ///
+    /// [`Text::validate`]: crate::Text::validate
+    Invalid(Vec<TextIssue>),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingParameter(name) => write!(f, "missing template parameter `{name}`"),
+            Self::UnbalancedBrace { template, index } => {
+                write!(
+                    f,
+                    "unbalanced brace at byte {index} of template {template:?}"
+                )
+            }
+            Self::Invalid(issues) => {
+                let issues: Vec<_> = issues.iter().map(ToString::to_string).collect();
+                write!(f, "rendered text is invalid: {}", issues.join("; "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+impl TextBuilder {
+    /// Substitutes the given parameters into every string of the builder, and
+    /// derives the text for every platform.
+    ///
+    /// A placeholder `{name}` is replaced by the parameter called `name`.
+    /// Literal braces are written as `{{` and `}}`.
+    ///
+    /// Text parameters are considered untrusted: line breaks and other control
+    /// characters are replaced by spaces, invisible format characters (such as
+    /// bidirectional formatting characters, which could be used to reorder the
+    /// displayed text, and zero-width spaces, which could be used to hide it)
+    /// are removed, and the result is truncated to 64 UTF-16 code units. Numbers are
+    /// substituted as is. The rendered text is then checked using
+    /// [`Text::validate`](crate::Text::validate), after the usual truncation of
+    /// the Windows strings.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use robius_authentication::Text;
+    ///
+    /// let template = Text::builder()
+    ///     .title("Approve transfer")
+    ///     .reason("approve the transfer of {amount} EUR to {recipient}");
+    ///
+    /// let text = template
+    ///     .render(&[("amount", 42.into()), ("recipient", "Bob\n\u{202e}evil".into())])
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     text.as_text().apple,
+    ///     "approve the transfer of 42 EUR to Bob evil"
+    /// );
+    /// ```
+    pub fn render(&self, params: &[(&str, Param<'_>)]) -> Result<TextBuf, TemplateError> {
+        let render = |template: &String| substitute(template, params);
+        let render_option = |template: &Option<String>| template.as_ref().map(render).transpose();
+
+        let mut linux_details = Vec::with_capacity(self.linux_details.len());
+        for detail in &self.linux_details {
+            let mut detail = detail.clone();
+            detail.value = Cow::Owned(substitute(&detail.value, params)?);
+            linux_details.push(detail);
+        }
+
+        let builder = TextBuilder {
+            reason: render(&self.reason)?,
+            title: render_option(&self.title)?,
+            app_name: render_option(&self.app_name)?,
+            android_title: render_option(&self.android_title)?,
+            android_subtitle: render_option(&self.android_subtitle)?,
+            android_description: render_option(&self.android_description)?,
+            apple: render_option(&self.apple)?,
+            linux_message: render_option(&self.linux_message)?,
+            linux_icon_name: self.linux_icon_name.clone(),
+            linux_gettext_domain: self.linux_gettext_domain.clone(),
+            linux_details,
+            windows_title: render_option(&self.windows_title)?,
+            windows_description: render_option(&self.windows_description)?,
+        };
+
+        builder.build_valid().map_err(TemplateError::Invalid)
+    }
+}
+
+/// Replaces the placeholders in `template` with the given parameters.
+fn substitute(template: &str, params: &[(&str, Param<'_>)]) -> Result<String, TemplateError> {
+    let unbalanced = |index| TemplateError::UnbalancedBrace {
+        template: template.to_owned(),
+        index,
+    };
+
+    let mut rendered = String::with_capacity(template.len());
+    let mut chars = template.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '{' if chars.next_if(|&(_, c)| c == '{').is_some() => rendered.push('{'),
+            '}' if chars.next_if(|&(_, c)| c == '}').is_some() => rendered.push('}'),
+            '{' => {
+                let start = index + 1;
+                let end = template[start..]
+                    .find('}')
+                    .map(|len| start + len)
+                    .ok_or(unbalanced(index))?;
+                let name = template[start..end].trim();
+                let (_, param) = params
+                    .iter()
+                    .find(|(param, _)| *param == name)
+                    .ok_or_else(|| TemplateError::MissingParameter(name.to_owned()))?;
+                rendered.push_str(&param.render());
+
+                while chars.next_if(|&(index, _)| index <= end).is_some() {}
+            }
+            '}' => return Err(unbalanced(index)),
+            c => rendered.push(c),
+        }
+    }
+
+    Ok(rendered)
+}
+
+/// Makes untrusted text safe to display in a prompt.
+fn sanitize(text: &str) -> String {
+    let mut sanitized = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            c if is_format(c) => {}
+            // Line and paragraph separators.
+            '\u{2028}' | '\u{2029}' => sanitized.push(' '),
+            c if c.is_control() => sanitized.push(' '),
+            c => sanitized.push(c),
+        }
+    }
+
+    // Collapse the whitespace left behind by removed line breaks.
+    sanitized.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Returns whether `c` is an invisible format character, i.e., in the Unicode
+/// general category `Cf`.
+///
+/// This includes the zero-width joiner, so emoji sequences are displayed as
+/// their separate emoji.
+const fn is_format(c: char) -> bool {
+    matches!(
+        c,
+        '\u{ad}'
+            | '\u{600}'..='\u{605}'
+            | '\u{61c}'
+            | '\u{6dd}'
+            | '\u{70f}'
+            | '\u{890}'..='\u{891}'
+            | '\u{8e2}'
+            | '\u{180e}'
+            | '\u{200b}'..='\u{200f}'
+            | '\u{202a}'..='\u{202e}'
+            | '\u{2060}'..='\u{2064}'
+            | '\u{2066}'..='\u{206f}'
+            | '\u{feff}'
+            | '\u{fff9}'..='\u{fffb}'
+            | '\u{110bd}'
+            | '\u{110cd}'
+            | '\u{13430}'..='\u{1343f}'
+            | '\u{1bca0}'..='\u{1bca3}'
+            | '\u{1d173}'..='\u{1d17a}'
+            | '\u{e0001}'
+            | '\u{e0020}'..='\u{e007f}'
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Text, TextField, TextIssueKind};
+
+    #[test]
+    fn format_characters_are_removed() {
+        assert_eq!(sanitize("B\u{200b}o\u{2060}b\u{feff}"), "Bob");
+        assert_eq!(sanitize("\u{202e}evil\u{2069}"), "evil");
+        assert_eq!(sanitize("soft\u{ad}hyphen"), "softhyphen");
+        assert_eq!(sanitize("two\nlines\u{2028}here"), "two lines here");
+    }
+
+    #[test]
+    fn escaped_braces() {
+        let params = [("name", "Bob".into())];
+        assert_eq!(
+            substitute("{{name}} is {name}", &params).unwrap(),
+            "{name} is Bob"
+        );
+        assert_eq!(substitute("}}{{{name}}}", &params).unwrap(), "}{Bob}");
+        // Braces in parameters are not placeholders.
+        let params = [("name", "{name}".into())];
+        assert_eq!(substitute("{name}", &params).unwrap(), "{name}");
+    }
+
+    #[test]
+    fn placeholders() {
+        let params = [("amount", 42.into()), ("recipient", "Bob".into())];
+        assert_eq!(
+            substitute("{ amount } to {recipient}", &params).unwrap(),
+            "42 to Bob"
+        );
+        // Parameters without placeholders are ignored.
+        assert_eq!(substitute("a transfer", &params).unwrap(), "a transfer");
+        assert_eq!(
+            substitute("{amount} to {account}", &params),
+            Err(TemplateError::MissingParameter("account".to_owned()))
+        );
+        assert_eq!(
+            substitute("{}", &params),
+            Err(TemplateError::MissingParameter(String::new()))
+        );
+        assert_eq!(
+            Text::builder()
+                .title("Transfer to {recipient}")
+                .reason("approve the transfer")
+                .render(&[]),
+            Err(TemplateError::MissingParameter("recipient".to_owned()))
+        );
+    }
+
+    #[test]
+    fn unbalanced_braces() {
+        let params = [("name", "Bob".into())];
+        let unbalanced = |template: &str, index| {
+            Err(TemplateError::UnbalancedBrace {
+                template: template.to_owned(),
+                index,
+            })
+        };
+        assert_eq!(substitute("{name", &params), unbalanced("{name", 0));
+        assert_eq!(substitute("{name} {", &params), unbalanced("{name} {", 7));
+        assert_eq!(substitute("name}", &params), unbalanced("name}", 4));
+        assert_eq!(substitute("{name}}", &params), unbalanced("{name}}", 6));
+    }
+
+    #[test]
+    fn text_parameters_are_truncated() {
+        let long = "a".repeat(100);
+        let rendered = substitute("{name}", &[("name", long.as_str().into())]).unwrap();
+        assert_eq!(rendered.encode_utf16().count(), MAX_PARAM_LEN);
+        assert!(rendered.ends_with('…'));
+
+        // Measured in UTF-16 code units, without splitting characters.
+        let emoji = "\u{1f600}".repeat(MAX_PARAM_LEN / 2);
+        let params = [("name", emoji.as_str().into())];
+        assert_eq!(substitute("{name}", &params).unwrap(), emoji);
+        let params = [("name", format!("{emoji}a").into())];
+        let rendered = substitute("{name}", &params).unwrap();
+        assert!(rendered.encode_utf16().count() <= MAX_PARAM_LEN);
+        assert!(rendered.starts_with("\u{1f600}"));
+
+        // Numbers are substituted as is.
+        let params = [("amount", u64::MAX.into())];
+        assert_eq!(
+            substitute("{amount}", &params).unwrap(),
+            u64::MAX.to_string()
+        );
+    }
+
+    #[test]
+    fn text_parameters_are_sanitized() {
+        let params = [(
+            "name",
+            "Bob\r\n\t\u{7}\u{1b}[31m\u{202e}lave\u{202c} \u{2067}x\u{2069}".into(),
+        )];
+        assert_eq!(substitute("{name}!", &params).unwrap(), "Bob [31mlave x!");
+        // Only parameters are sanitized.
+        let params = [("name", "Bob".into())];
+        assert_eq!(
+            substitute("\u{202e}{name}", &params).unwrap(),
+            "\u{202e}Bob"
+        );
+    }
+
+    #[test]
+    fn validation_issues_are_returned() {
+        let template = Text::builder()
+            .reason("{reason}")
+            .apple("approve {reason}\u{7}");
+        let Err(TemplateError::Invalid(issues)) = template.render(&[("reason", "".into())]) else {
+            panic!("the rendered text is invalid");
+        };
+        assert_eq!(
+            issues,
+            [TextIssue {
+                field: TextField::Apple,
+                kind: TextIssueKind::ControlCharacter {
+                    index: 8,
+                    character: '\u{7}',
+                },
+            }]
+        );
+
+        let template = Text::builder().reason("{reason}");
+        let Err(TemplateError::Invalid(issues)) = template.render(&[("reason", " ".into())]) else {
+            panic!("the rendered reason is empty");
+        };
+        assert_eq!(
+            issues,
+            [TextIssue {
+                field: TextField::Apple,
+                kind: TextIssueKind::Empty,
+            }]
+        );
+    }
+}
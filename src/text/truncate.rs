@@ -0,0 +1,137 @@
+//! Truncation of prompt strings to the limits of native APIs.
+
+use std::borrow::Cow;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// The string appended to truncated strings.
+const ELLIPSIS: &str = "…";
+
+/// Returns the length of `s` in UTF-16 code units.
+pub(crate) const fn utf16_len(s: &str) -> usize {
+    let bytes = s.as_bytes();
+    let mut len = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let byte = bytes[i];
+        // Continuation bytes don't start a new code point.
+        if byte & 0xc0 != 0x80 {
+            // Code points encoded in four bytes lie outside the BMP and are
+            // encoded as a surrogate pair.
+            len += if byte >= 0xf0 { 2 } else { 1 };
+        }
+        i += 1;
+    }
+
+    len
+}
+
+/// Truncates `s` to at most `max_len` UTF-16 code units.
+///
+/// The string is only cut between grapheme clusters, and [`ELLIPSIS`] is
+/// appended if the string was truncated. The ellipsis counts towards
+/// `max_len`.
+pub(crate) fn truncate_utf16(s: &str, max_len: usize) -> Cow<'_, str> {
+    if utf16_len(s) <= max_len {
+        return Cow::Borrowed(s);
+    }
+
+    let ellipsis_len = utf16_len(ELLIPSIS);
+    if max_len < ellipsis_len {
+        return Cow::Borrowed("");
+    }
+
+    let budget = max_len - ellipsis_len;
+    let mut len = 0;
+    let mut end = 0;
+    for (index, grapheme) in s.grapheme_indices(true) {
+        len += utf16_len(grapheme);
+        if len > budget {
+            break;
+        }
+        end = index + grapheme.len();
+    }
+
+    let mut truncated = s[..end].trim_end().to_owned();
+    truncated.push_str(ELLIPSIS);
+    Cow::Owned(truncated)
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    /// Returns strings mixing ASCII, combining marks, characters outside the
+    /// BMP (encoded as surrogate pairs) and ZWJ emoji sequences.
+    fn text() -> impl Strategy<Value = String> {
+        let piece = prop_oneof![
+            "[a-z ]{1,4}",
+            Just("e\u{301}".to_owned()),
+            Just("a\u{302}\u{323}".to_owned()),
+            Just("😀".to_owned()),
+            Just("𝔘".to_owned()),
+            Just("👩\u{200d}👩\u{200d}👧\u{200d}👦".to_owned()),
+            Just("🏳\u{fe0f}\u{200d}🌈".to_owned()),
+            Just("🇦🇺".to_owned()),
+            any::<char>().prop_map(String::from),
+        ];
+        prop::collection::vec(piece, 0..48).prop_map(|pieces| pieces.concat())
+    }
+
+    fn is_grapheme_boundary(s: &str, index: usize) -> bool {
+        index == 0
+            || s.grapheme_indices(true)
+                .any(|(start, grapheme)| start + grapheme.len() == index)
+    }
+
+    proptest! {
+        #[test]
+        fn utf16_len_matches_encoding(s in any::<String>()) {
+            prop_assert_eq!(utf16_len(&s), s.encode_utf16().count());
+        }
+
+        #[test]
+        fn utf16_len_matches_encoding_of_text(s in text()) {
+            prop_assert_eq!(utf16_len(&s), s.encode_utf16().count());
+        }
+
+        #[test]
+        fn truncated_within_limit(s in text(), max_len in 0..160usize) {
+            prop_assert!(utf16_len(&truncate_utf16(&s, max_len)) <= max_len);
+        }
+
+        #[test]
+        fn unchanged_within_limit(s in text(), extra in 0..16usize) {
+            let max_len = utf16_len(&s) + extra;
+            prop_assert!(matches!(truncate_utf16(&s, max_len), Cow::Borrowed(t) if t == s));
+        }
+
+        #[test]
+        fn truncated_on_grapheme_boundary(s in text(), max_len in 1..160usize) {
+            prop_assume!(utf16_len(&s) > max_len);
+            let truncated = truncate_utf16(&s, max_len);
+            let prefix = truncated.strip_suffix(ELLIPSIS);
+            prop_assert!(prefix.is_some(), "no ellipsis: {:?}", truncated);
+            let prefix = prefix.unwrap();
+            prop_assert!(s.starts_with(prefix));
+            prop_assert!(is_grapheme_boundary(&s, prefix.len()));
+        }
+    }
+
+    #[test]
+    fn surrogate_pairs_are_not_split() {
+        // Each emoji is two code units, so only one fits before the ellipsis.
+        assert_eq!(truncate_utf16("😀😀😀", 4), "😀…");
+        assert_eq!(truncate_utf16("😀😀😀", 2), "…");
+    }
+
+    #[test]
+    fn zwj_sequences_and_combining_marks_are_not_split() {
+        let family = "👩\u{200d}👩\u{200d}👧\u{200d}👦";
+        assert_eq!(truncate_utf16(&format!("a{family}b"), 5), "a…");
+        assert_eq!(truncate_utf16("e\u{301}e\u{301}e\u{301}", 4), "e\u{301}…");
+    }
+}
@@ -0,0 +1,589 @@
+//! Validation of prompt text against the constraints of every platform.
+
+use std::fmt;
+
+use super::{truncate::utf16_len, Text, WindowsText};
+
+/// A platform on which an authentication prompt can be displayed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Platform {
+    Android,
+    Apple,
+    Linux,
+    Windows,
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Android => "Android",
+            Self::Apple => "Apple",
+            Self::Linux => "Linux",
+            Self::Windows => "Windows",
+        })
+    }
+}
+
+/// A field of [`Text`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TextField {
+    /// [`AndroidText::title`](crate::AndroidText::title).
+    AndroidTitle,
+    /// [`AndroidText::subtitle`](crate::AndroidText::subtitle).
+    AndroidSubtitle,
+    /// [`AndroidText::description`](crate::AndroidText::description).
+    AndroidDescription,
+    /// [`Text::apple`].
+    Apple,
+    /// [`LinuxText::message`](crate::LinuxText::message).
+    LinuxMessage,
+    /// [`WindowsText::title`].
+    WindowsTitle,
+    /// [`WindowsText::description`].
+    WindowsDescription,
+}
+
+impl TextField {
+    /// Returns the platform on which this field is displayed.
+    pub const fn platform(self) -> Platform {
+        match self {
+            Self::AndroidTitle | Self::AndroidSubtitle | Self::AndroidDescription => {
+                Platform::Android
+            }
+            Self::Apple => Platform::Apple,
+            Self::LinuxMessage => Platform::Linux,
+            Self::WindowsTitle | Self::WindowsDescription => Platform::Windows,
+        }
+    }
+
+    /// Returns whether the platform requires this field to be non-empty.
+    const fn is_required(self) -> bool {
+        // `BiometricPrompt.Builder.build` throws if the title is empty, and
+        // `LAContext` throws if the localized reason is empty.
+        matches!(self, Self::AndroidTitle | Self::Apple)
+    }
+
+    /// Returns the maximum length of this field in UTF-16 code units.
+    ///
+    /// Android and Apple don't limit the length of their strings.
+    const fn max_len(self) -> Option<usize> {
+        match self {
+            Self::WindowsTitle => Some(WindowsText::MAX_TITLE_LEN),
+            Self::WindowsDescription => Some(WindowsText::MAX_DESCRIPTION_LEN),
+            _ => None,
+        }
+    }
+
+    /// Returns whether this field may span multiple lines.
+    const fn is_multiline(self) -> bool {
+        matches!(self, Self::AndroidDescription | Self::WindowsDescription)
+    }
+}
+
+impl fmt::Display for TextField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::AndroidTitle => "Android title",
+            Self::AndroidSubtitle => "Android subtitle",
+            Self::AndroidDescription => "Android description",
+            Self::Apple => "Apple description",
+            Self::LinuxMessage => "Linux message",
+            Self::WindowsTitle => "Windows title",
+            Self::WindowsDescription => "Windows description",
+        })
+    }
+}
+
+/// A problem with a field of [`Text`], as reported by [`Text::validate`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TextIssue {
+    /// The offending field.
+    pub field: TextField,
+    /// The kind of problem.
+    pub kind: TextIssueKind,
+}
+
+/// The kind of a [`TextIssue`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TextIssueKind {
+    /// The field is empty but the platform requires it to be set.
+    Empty,
+    /// The field exceeds the maximum length allowed by the platform.
+    ///
+    /// Both lengths are measured in UTF-16 code units. This is only reported
+    /// by the [`text!`](crate::text!) macro, as a [`WindowsText`] is always
+    /// truncated or checked when it is constructed.
+    TooLong { len: usize, max: usize },
+    /// The field contains a control character.
+    ///
+    /// Line feeds are allowed in descriptions.
+    ControlCharacter { index: usize, character: char },
+    /// The field contains a bidirectional formatting character, which can
+    /// reorder the displayed text, e.g., to disguise an amount or recipient.
+    BidiControl { index: usize, character: char },
+}
+
+impl fmt::Display for TextIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            TextIssueKind::Empty => write!(f, "{} must not be empty", self.field),
+            TextIssueKind::TooLong { len, max } => write!(
+                f,
+                "{} is {len} UTF-16 code units long, exceeding the maximum of {max}",
+                self.field
+            ),
+            TextIssueKind::ControlCharacter { index, character } => write!(
+                f,
+                "{} contains the control character U+{:04X} at byte {index}",
+                self.field, character as u32
+            ),
+            TextIssueKind::BidiControl { index, character } => write!(
+                f,
+                "{} contains the bidirectional formatting character U+{:04X} at byte {index}",
+                self.field, character as u32
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TextIssue {}
+
+/// The issues found when building a [`TextBuf`](crate::TextBuf), see
+/// [`TextBuilder::build`](crate::TextBuilder::build).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationError {
+    issues: Vec<TextIssue>,
+}
+
+impl ValidationError {
+    pub(crate) fn new(issues: Vec<TextIssue>) -> Self {
+        Self { issues }
+    }
+
+    /// Returns the issues, of which there is at least one.
+    #[inline]
+    pub fn issues(&self) -> &[TextIssue] {
+        &self.issues
+    }
+
+    /// Returns the issues, of which there is at least one.
+    #[inline]
+    pub fn into_issues(self) -> Vec<TextIssue> {
+        self.issues
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid prompt text: ")?;
+        for (i, issue) in self.issues.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{issue}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl TextIssue {
+    /// Returns a description of the issue that can be emitted at compile time.
+    const fn static_message(&self) -> &'static str {
+        use TextField::*;
+        use TextIssueKind::*;
+
+        match (self.field, self.kind) {
+            (AndroidTitle, Empty) => "Android title must not be empty",
+            (Apple, Empty) => "Apple description must not be empty",
+            (WindowsTitle, TooLong { .. }) => "Windows title exceeds 128 UTF-16 code units",
+            (WindowsDescription, TooLong { .. }) => {
+                "Windows description exceeds 1024 UTF-16 code units"
+            }
+            (AndroidTitle, ControlCharacter { .. }) => "Android title contains a control character",
+            (AndroidSubtitle, ControlCharacter { .. }) => {
+                "Android subtitle contains a control character"
+            }
+            (AndroidDescription, ControlCharacter { .. }) => {
+                "Android description contains a control character"
+            }
+            (Apple, ControlCharacter { .. }) => "Apple description contains a control character",
+            (LinuxMessage, ControlCharacter { .. }) => "Linux message contains a control character",
+            (WindowsTitle, ControlCharacter { .. }) => "Windows title contains a control character",
+            (WindowsDescription, ControlCharacter { .. }) => {
+                "Windows description contains a control character"
+            }
+            (_, BidiControl { .. }) => "prompt text contains a bidirectional formatting character",
+            _ => "invalid prompt text",
+        }
+    }
+}
+
+/// Returns the issues with the given value of `field`.
+///
+/// The length is only checked if `check_len` is true.
+const fn check(field: TextField, value: &str, check_len: bool) -> [Option<TextIssueKind>; 4] {
+    let mut issues = [None; 4];
+
+    if field.is_required() && is_blank(value) {
+        issues[0] = Some(TextIssueKind::Empty);
+    }
+    if let (true, Some(max)) = (check_len, field.max_len()) {
+        let len = utf16_len(value);
+        if len > max {
+            issues[1] = Some(TextIssueKind::TooLong { len, max });
+        }
+    }
+    if let Some((index, character)) = find_control_character(value, field.is_multiline()) {
+        issues[2] = Some(TextIssueKind::ControlCharacter { index, character });
+    }
+    if let Some((index, character)) = find_bidi_control(value) {
+        issues[3] = Some(TextIssueKind::BidiControl { index, character });
+    }
+
+    issues
+}
+
+/// Panics at compile time if the given value of `field` has any issues.
+///
+/// Unlike [`validate`], this checks the length of the Windows strings, as the
+/// [`text!`](crate::text!) macro doesn't truncate them.
+pub(super) const fn assert_valid(field: TextField, value: &str) {
+    let issues = check(field, value, true);
+    let mut i = 0;
+    while i < issues.len() {
+        if let Some(kind) = issues[i] {
+            panic!("{}", TextIssue { field, kind }.static_message());
+        }
+        i += 1;
+    }
+}
+
+/// Decodes the character starting at byte `index` of `s`.
+///
+/// Returns the character and the index of the next character.
+const fn decode_char(s: &str, index: usize) -> (char, usize) {
+    let bytes = s.as_bytes();
+    let first = bytes[index] as u32;
+    let (mut code_point, len) = match first {
+        0x00..=0x7f => (first, 1),
+        0xc0..=0xdf => (first & 0x1f, 2),
+        0xe0..=0xef => (first & 0x0f, 3),
+        _ => (first & 0x07, 4),
+    };
+
+    let mut i = 1;
+    while i < len {
+        code_point = (code_point << 6) | (bytes[index + i] as u32 & 0x3f);
+        i += 1;
+    }
+
+    match char::from_u32(code_point) {
+        Some(c) => (c, index + len),
+        // `s` is valid UTF-8, so this can't happen.
+        None => (char::REPLACEMENT_CHARACTER, index + len),
+    }
+}
+
+/// Returns whether `s` is empty or only contains whitespace.
+const fn is_blank(s: &str) -> bool {
+    let mut index = 0;
+    while index < s.len() {
+        let (c, next) = decode_char(s, index);
+        if !c.is_whitespace() {
+            return false;
+        }
+        index = next;
+    }
+    true
+}
+
+/// Returns the first control character in `s` and its byte index.
+const fn find_control_character(s: &str, allow_newline: bool) -> Option<(usize, char)> {
+    let mut index = 0;
+    while index < s.len() {
+        let (c, next) = decode_char(s, index);
+        // `char::is_control` isn't `const`.
+        let is_control = matches!(c, '\u{0}'..='\u{1f}' | '\u{7f}'..='\u{9f}');
+        if is_control && !(allow_newline && c == '\n') {
+            return Some((index, c));
+        }
+        index = next;
+    }
+    None
+}
+
+/// Returns the first bidirectional formatting character in `s` and its byte
+/// index.
+const fn find_bidi_control(s: &str) -> Option<(usize, char)> {
+    let mut index = 0;
+    while index < s.len() {
+        let (c, next) = decode_char(s, index);
+        if is_bidi_control(c) {
+            return Some((index, c));
+        }
+        index = next;
+    }
+    None
+}
+
+/// Returns whether `c` is a bidirectional formatting character, i.e., an
+/// embedding, override or isolate, or an implicit directional mark.
+const fn is_bidi_control(c: char) -> bool {
+    matches!(
+        c,
+        '\u{061c}' | '\u{200e}' | '\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}'
+    )
+}
+
+pub(super) fn validate(text: &Text<'_>) -> Result<(), Vec<TextIssue>> {
+    let fields = [
+        (TextField::AndroidTitle, Some(text.android.title)),
+        (TextField::AndroidSubtitle, text.android.subtitle),
+        (TextField::AndroidDescription, text.android.description),
+        (TextField::Apple, Some(text.apple)),
+        (TextField::LinuxMessage, Some(text.linux.message)),
+        (TextField::WindowsTitle, Some(&*text.windows.title)),
+        (TextField::WindowsDescription, Some(&*text.windows.description)),
+    ];
+
+    let issues: Vec<_> = fields
+        .into_iter()
+        .filter_map(|(field, value)| Some((field, value?)))
+        .flat_map(|(field, value)| {
+            check(field, value, false)
+                .into_iter()
+                .flatten()
+                .map(move |kind| TextIssue { field, kind })
+        })
+        .collect();
+
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(issues)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{assert_valid, check};
+    use crate::{AndroidText, LinuxText, Text, TextField, TextIssue, TextIssueKind, WindowsText};
+
+    /// Returns a text without issues.
+    fn valid() -> Text<'static> {
+        Text {
+            android: AndroidText {
+                title: "Unlock",
+                subtitle: Some("Vault"),
+                description: Some("Unlock your vault\nto continue"),
+            },
+            apple: "unlock your vault",
+            linux: LinuxText::new("Authentication is required to unlock your vault"),
+            windows: WindowsText::new("Unlock", "Unlock your vault\nto continue").unwrap(),
+        }
+    }
+
+    /// Returns the issues of a valid text with the given field replaced by
+    /// `value`.
+    fn issues(field: TextField, value: &str) -> Vec<TextIssue> {
+        let mut text = valid();
+        let (title, description) = ("Unlock", "Unlock your vault\nto continue");
+        match field {
+            TextField::AndroidTitle => text.android.title = value,
+            TextField::AndroidSubtitle => text.android.subtitle = Some(value),
+            TextField::AndroidDescription => text.android.description = Some(value),
+            TextField::Apple => text.apple = value,
+            TextField::LinuxMessage => text.linux.message = value,
+            TextField::WindowsTitle => text.windows = WindowsText::new(value, description).unwrap(),
+            TextField::WindowsDescription => text.windows = WindowsText::new(title, value).unwrap(),
+        }
+        text.validate().err().unwrap_or_default()
+    }
+
+    const FIELDS: [TextField; 7] = [
+        TextField::AndroidTitle,
+        TextField::AndroidSubtitle,
+        TextField::AndroidDescription,
+        TextField::Apple,
+        TextField::LinuxMessage,
+        TextField::WindowsTitle,
+        TextField::WindowsDescription,
+    ];
+
+    #[test]
+    fn valid_text() {
+        assert_eq!(valid().validate(), Ok(()));
+    }
+
+    #[test]
+    fn required_fields() {
+        let empty = |field| TextIssue {
+            field,
+            kind: TextIssueKind::Empty,
+        };
+        assert_eq!(
+            issues(TextField::AndroidTitle, ""),
+            [empty(TextField::AndroidTitle)]
+        );
+        assert_eq!(issues(TextField::Apple, "  "), [empty(TextField::Apple)]);
+        // The Android subtitle and description are optional, and the other
+        // platforms display a default text.
+        for field in [
+            TextField::AndroidSubtitle,
+            TextField::AndroidDescription,
+            TextField::LinuxMessage,
+            TextField::WindowsTitle,
+            TextField::WindowsDescription,
+        ] {
+            assert_eq!(issues(field, ""), [], "{field}");
+        }
+    }
+
+    #[test]
+    fn control_characters() {
+        for field in FIELDS {
+            assert_eq!(
+                issues(field, "a\u{1b}b\u{7f}"),
+                [TextIssue {
+                    field,
+                    kind: TextIssueKind::ControlCharacter {
+                        index: 1,
+                        character: '\u{1b}',
+                    },
+                }],
+                "{field}"
+            );
+
+            // Line feeds are only allowed in descriptions.
+            let multiline = matches!(
+                field,
+                TextField::AndroidDescription | TextField::WindowsDescription
+            );
+            assert_eq!(issues(field, "a\nb").is_empty(), multiline, "{field}");
+            assert_eq!(issues(field, "a\r\nb").len(), 1, "{field}");
+        }
+    }
+
+    #[test]
+    fn bidi_controls() {
+        for field in FIELDS {
+            assert_eq!(
+                issues(field, "pay \u{202e}321\u{202c} EUR"),
+                [TextIssue {
+                    field,
+                    kind: TextIssueKind::BidiControl {
+                        index: 4,
+                        character: '\u{202e}',
+                    },
+                }],
+                "{field}"
+            );
+        }
+        for c in ['\u{61c}', '\u{200e}', '\u{200f}', '\u{2066}', '\u{2069}'] {
+            assert_eq!(issues(TextField::Apple, &format!("a{c}")).len(), 1);
+        }
+        // Other format characters are allowed.
+        assert_eq!(issues(TextField::Apple, "a\u{200d}b"), []);
+    }
+
+    #[test]
+    fn windows_lengths() {
+        let title = "a".repeat(WindowsText::MAX_TITLE_LEN);
+        let description = "\u{1f600}".repeat(WindowsText::MAX_DESCRIPTION_LEN / 2);
+        assert!(WindowsText::new(&title, &description).is_some());
+        // Measured in UTF-16 code units.
+        let long_title = format!("{title}a");
+        let long_description = format!("{description}a");
+        assert!(WindowsText::new(&long_title, &description).is_none());
+        assert!(WindowsText::new(&title, &long_description).is_none());
+
+        assert_eq!(
+            check(TextField::WindowsTitle, &long_title, true),
+            [
+                None,
+                Some(TextIssueKind::TooLong {
+                    len: WindowsText::MAX_TITLE_LEN + 1,
+                    max: WindowsText::MAX_TITLE_LEN,
+                }),
+                None,
+                None,
+            ]
+        );
+        assert_eq!(
+            check(TextField::WindowsDescription, &long_description, true)[1],
+            Some(TextIssueKind::TooLong {
+                len: WindowsText::MAX_DESCRIPTION_LEN + 1,
+                max: WindowsText::MAX_DESCRIPTION_LEN,
+            })
+        );
+        assert_eq!(
+            check(TextField::WindowsTitle, &long_title, false),
+            [None; 4]
+        );
+        assert_eq!(check(TextField::Apple, &long_description, true), [None; 4]);
+        assert_valid(TextField::WindowsTitle, &title);
+    }
+
+    #[test]
+    #[should_panic(expected = "Windows title exceeds 128 UTF-16 code units")]
+    fn long_windows_titles_are_rejected() {
+        assert_valid(TextField::WindowsTitle, &"a".repeat(129));
+    }
+
+    #[test]
+    #[should_panic(expected = "Android title must not be empty")]
+    fn empty_android_titles_are_rejected() {
+        assert_valid(TextField::AndroidTitle, "");
+    }
+
+    #[test]
+    fn bidi_controls_are_reported() {
+        let error = Text::builder()
+            .reason("send 100 EUR to \u{202e}boB")
+            .build()
+            .unwrap_err();
+        assert!(error.issues().contains(&TextIssue {
+            field: TextField::Apple,
+            kind: TextIssueKind::BidiControl {
+                index: 16,
+                character: '\u{202e}',
+            },
+        }));
+    }
+
+    #[test]
+    fn control_characters_are_rejected() {
+        let error = Text::from_reason("unlock\u{7}your vault").unwrap_err();
+        assert!(error.issues().contains(&TextIssue {
+            field: TextField::Apple,
+            kind: TextIssueKind::ControlCharacter {
+                index: 6,
+                character: '\u{7}',
+            },
+        }));
+    }
+
+    #[test]
+    fn long_reasons_are_truncated_on_windows() {
+        let text = Text::from_reason("b".repeat(2000)).unwrap();
+        let text = text.as_text();
+        assert_eq!(text.apple.len(), 2000);
+        assert_eq!(
+            text.windows.description.encode_utf16().count(),
+            WindowsText::MAX_DESCRIPTION_LEN
+        );
+        assert!(text.windows.description.ends_with('…'));
+    }
+
+    #[test]
+    fn long_android_and_windows_strings_are_valid() {
+        let text = Text::builder()
+            .title("a".repeat(200))
+            .reason("b".repeat(2000))
+            .build()
+            .unwrap();
+        assert_eq!(text.as_text().validate(), Ok(()));
+    }
+}
@@ -0,0 +1,193 @@
+//! Tests of the PAM backend using [`pam_wrapper`] and the service files in
+//! `tests/pam`, without touching the system configuration.
+//!
+//! The tests using PAM are ignored unless requested, as they must run under
+//! `pam_wrapper`, e.g.:
+//!
+//! ```text
+//! LD_PRELOAD=libpam_wrapper.so PAM_WRAPPER=1 \
+//!     PAM_WRAPPER_SERVICE_DIR=tests/pam PAM_MATRIX_PASSWD=tests/pam/passdb \
+//!     cargo test --features pam --test pam -- --include-ignored
+//! ```
+//!
+//! `pam_matrix.so`, which is shipped with `pam_wrapper`, must be installed
+//! in the PAM module directory.
+//!
+//! [`pam_wrapper`]: https://cwrap.org/pam_wrapper.html
+
+#![cfg(all(target_os = "linux", feature = "pam"))]
+
+use std::{
+    collections::VecDeque,
+    sync::{mpsc, Arc, Mutex},
+    time::Duration,
+};
+
+use robius_authentication::{
+    linux::Conversation, AuthenticationMethod, Context, Error, Identity, LinuxBackend, Outcome,
+    PolicyBuilder, PolicyError, Result, Text,
+};
+
+const TEXT: Text = robius_authentication::text! {
+    title: "Test",
+    reason: "run the PAM tests",
+};
+
+const USER: &str = "alice";
+const PASSWORD: &str = "secret";
+
+/// A conversation answering the prompts with the given responses, and
+/// retrying until they run out.
+#[derive(Clone, Default)]
+struct Scripted {
+    responses: Arc<Mutex<VecDeque<Result<String>>>>,
+    prompts: Arc<Mutex<Vec<(String, bool)>>>,
+}
+
+impl Scripted {
+    fn new(responses: impl IntoIterator<Item = Result<String>>) -> Self {
+        Self {
+            responses: Arc::new(Mutex::new(responses.into_iter().collect())),
+            prompts: Default::default(),
+        }
+    }
+
+    fn prompts(&self) -> Vec<(String, bool)> {
+        self.prompts.lock().unwrap().clone()
+    }
+}
+
+impl Conversation for Scripted {
+    fn prompt(&mut self, prompt: &str, echo: bool) -> Result<String> {
+        self.prompts.lock().unwrap().push((prompt.to_owned(), echo));
+        match self.responses.lock().unwrap().pop_front() {
+            Some(response) => response,
+            None => panic!("unexpected prompt: {prompt}"),
+        }
+    }
+
+    fn retry(&mut self, _: &Error) -> bool {
+        !self.responses.lock().unwrap().is_empty()
+    }
+}
+
+/// A conversation that panics when prompting.
+struct Panicking;
+
+impl Conversation for Panicking {
+    fn prompt(&mut self, _: &str, _: bool) -> Result<String> {
+        panic!("the conversation panicked");
+    }
+}
+
+/// Authenticates `identity` against `service`, returning the result passed
+/// to the callback.
+fn authenticate(
+    service: &'static str,
+    identity: Identity,
+    conversation: impl Conversation + 'static,
+) -> Result<Outcome> {
+    let context = Context::new(()).with_conversation(conversation);
+    let policy = PolicyBuilder::new()
+        .biometrics(None)
+        .password(true)
+        .linux_backend(LinuxBackend::Pam {
+            service: service.into(),
+        })
+        .identity(identity)
+        .build()
+        .unwrap();
+
+    let (sender, receiver) = mpsc::channel();
+    let sender = Mutex::new(sender);
+    context.authenticate_with_outcome(TEXT, &policy, move |result| {
+        sender.lock().unwrap().send(result).unwrap();
+    })?;
+    receiver.recv_timeout(Duration::from_secs(10)).unwrap()
+}
+
+#[test]
+#[ignore = "requires pam_wrapper"]
+fn correct_password() {
+    let conversation = Scripted::new([Ok(PASSWORD.to_owned())]);
+    let outcome = authenticate(
+        "robius-test",
+        Identity::User(USER.into()),
+        conversation.clone(),
+    );
+    let outcome = outcome.unwrap();
+    assert_eq!(outcome.user.as_deref(), Some(USER));
+    assert_eq!(outcome.method, Some(AuthenticationMethod::Password));
+    // The password is a secret.
+    assert!(matches!(&conversation.prompts()[..], [(_, false)]));
+}
+
+#[test]
+#[ignore = "requires pam_wrapper"]
+fn wrong_password() {
+    let conversation = Scripted::new([Ok("wrong".to_owned())]);
+    let result = authenticate("robius-test", Identity::User(USER.into()), conversation);
+    assert!(matches!(result, Err(Error::Authentication)));
+}
+
+#[test]
+#[ignore = "requires pam_wrapper"]
+fn retries() {
+    let conversation = Scripted::new([
+        Ok("wrong".to_owned()),
+        Ok("still wrong".to_owned()),
+        Ok(PASSWORD.to_owned()),
+    ]);
+    let result = authenticate(
+        "robius-test",
+        Identity::User(USER.into()),
+        conversation.clone(),
+    );
+    assert!(result.is_ok());
+    assert_eq!(conversation.prompts().len(), 3);
+}
+
+#[test]
+#[ignore = "requires pam_wrapper"]
+fn canceled() {
+    let conversation = Scripted::new([Err(Error::UserCanceled)]);
+    let result = authenticate("robius-test", Identity::User(USER.into()), conversation);
+    assert!(matches!(result, Err(Error::UserCanceled)));
+}
+
+#[test]
+#[ignore = "requires pam_wrapper"]
+fn panicking_conversation() {
+    let result = authenticate("robius-test", Identity::User(USER.into()), Panicking);
+    assert!(matches!(result, Err(Error::Unknown)));
+}
+
+#[test]
+#[ignore = "requires pam_wrapper"]
+fn expired() {
+    let result = authenticate(
+        "robius-account-expired",
+        Identity::User(USER.into()),
+        Scripted::default(),
+    );
+    assert!(matches!(result, Err(Error::AccountExpired)));
+
+    let result = authenticate(
+        "robius-password-expired",
+        Identity::User(USER.into()),
+        Scripted::default(),
+    );
+    assert!(matches!(result, Err(Error::PasswordExpired)));
+}
+
+#[test]
+fn administrator_is_rejected() {
+    let policy = PolicyBuilder::new()
+        .biometrics(None)
+        .linux_backend(LinuxBackend::Pam {
+            service: "robius-test".into(),
+        })
+        .identity(Identity::Administrator)
+        .try_build();
+    assert_eq!(policy.unwrap_err(), PolicyError::UnsupportedIdentity);
+}
@@ -0,0 +1,100 @@
+//! Tests of the exit status of the `verify-audit-log` tool.
+
+#![cfg(feature = "audit-chain")]
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{self, Command},
+};
+
+use robius_authentication::{AuditKey, JsonLinesSink};
+
+/// A chained log without records and its key file in a temporary directory,
+/// removed when dropped.
+struct Log {
+    dir: PathBuf,
+}
+
+impl Log {
+    fn new(name: &str) -> Self {
+        let dir = env::temp_dir().join(format!("robius-verify-audit-log-{}-{name}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let log = Self { dir };
+        let key = AuditKey::from_file(log.key()).unwrap();
+        JsonLinesSink::new(log.path())
+            .unwrap()
+            .with_hash_chain(key)
+            .unwrap();
+        log
+    }
+
+    fn path(&self) -> PathBuf {
+        self.dir.join("audit.jsonl")
+    }
+
+    fn head(&self) -> PathBuf {
+        self.dir.join("audit.jsonl.head")
+    }
+
+    fn key(&self) -> PathBuf {
+        self.dir.join("audit.key")
+    }
+}
+
+impl Drop for Log {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Runs the tool, returning its exit status.
+fn verify(args: &[&Path]) -> i32 {
+    Command::new(env!("CARGO_BIN_EXE_verify-audit-log"))
+        .args(args)
+        .output()
+        .unwrap()
+        .status
+        .code()
+        .unwrap()
+}
+
+#[test]
+fn intact() {
+    let log = Log::new("intact");
+    let args = [&log.path(), Path::new("--key-file"), &log.key()];
+    assert_eq!(verify(&args), 0);
+}
+
+#[test]
+fn tampered() {
+    let log = Log::new("tampered");
+    let head = fs::read_to_string(log.head()).unwrap();
+    fs::write(log.head(), head.replace("\"first\":1", "\"first\":2")).unwrap();
+    let args = [&log.path(), Path::new("--key-file"), &log.key()];
+    assert_eq!(verify(&args), 1);
+
+    fs::remove_file(log.head()).unwrap();
+    assert_eq!(verify(&args), 1);
+}
+
+#[test]
+fn unreadable() {
+    let log = Log::new("unreadable");
+    fs::remove_file(log.head()).unwrap();
+    fs::create_dir(log.head()).unwrap();
+    let args = [&log.path(), Path::new("--key-file"), &log.key()];
+    assert_eq!(verify(&args), 2);
+}
+
+#[test]
+fn invalid_arguments() {
+    let log = Log::new("invalid-arguments");
+    assert_eq!(verify(&[&log.path()]), 2);
+
+    // The key is not generated.
+    let key = log.dir.join("missing.key");
+    assert_eq!(verify(&[&log.path(), Path::new("--key-file"), &key]), 2);
+    assert!(!key.exists());
+}